@@ -1,4 +1,11 @@
+from typing import Any
+
 class CServer:
     def __init__(self) -> None: ...
-    async def start(self, workspace: str, port: int) -> None: ...
+    async def start(
+        self, workspace: str, port: int, config: dict[str, Any] | None = None
+    ) -> str: ...
     async def stop(self) -> None: ...
+    def is_running(self) -> bool: ...
+    def status(self) -> dict[str, Any]: ...
+    def list_tools(self) -> list[dict[str, Any]]: ...
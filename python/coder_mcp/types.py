@@ -2,13 +2,59 @@ from typing import Literal
 
 CoderToolName = Literal[
     "bash",
+    "bash_check",
+    "bash_start",
+    "bash_peek",
+    "cancel_bash",
+    "bash_command_info",
+    "list_bash_history",
+    "bash_sandbox",
+    "session_env_diff",
+    "slowest_commands",
     "view_file",
+    "view_with_matches",
+    "error_context",
+    "extract_block",
+    "resolve_import",
     "list_directory",
+    "list_directory_detailed",
+    "file_stat",
+    "tree",
     "create_file",
+    "create_files",
     "str_replace",
+    "multi_edit",
+    "apply_edit_blocks",
+    "apply_patch",
+    "conditional_replace",
     "insert_lines",
+    "insert_near",
+    "append_to_file",
     "delete_file",
+    "swap_files",
+    "move_file",
+    "move_glob",
+    "sort_file",
+    "normalize_whitespace",
+    "toggle_comment",
+    "diff_files",
     "undo_edit",
+    "redo_edit",
+    "revert_file",
     "search_filenames",
     "search_content",
+    "search_all",
+    "list_mutations",
+    "list_active_edits",
+    "create_checkpoint",
+    "restore_checkpoint",
+    "watch_dir",
+    "tool_versions",
+    "disk_usage",
+    "code_stats",
+    "fetch_url",
+    "get_capabilities",
+    "get_logs",
+    "get_version",
+    "git_info",
 ]
@@ -0,0 +1,175 @@
+use chrono::Utc;
+use rmcp::model::{CallToolResult, JsonObject, RawContent};
+use rmcp::ErrorData as McpError;
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Arguments that carry file contents. Their values never reach the audit log, at any depth.
+const REDACTED_KEYS: &[&str] = &[
+    "content", "old_str", "new_str", "search", "replace", "patch", "anchor", "contains",
+];
+
+/// Longest error message kept in a record.
+const MAX_ERROR_CHARS: usize = 200;
+
+/// Append-only JSON-lines record of every tool call.
+#[derive(Debug)]
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Opens `path` for appending, creating it and its parent directories if needed.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Appends one record for a finished tool call. Write failures are logged, not returned,
+    /// so auditing never changes a tool's result.
+    pub fn record(
+        &self,
+        tool: &str,
+        args: Option<&JsonObject>,
+        result: &Result<CallToolResult, McpError>,
+    ) {
+        let (outcome, error) = match result {
+            Ok(r) if r.is_error == Some(true) => ("error", None),
+            Ok(r) => match first_text(r) {
+                Some(text) if text.starts_with("Error") => ("error", Some(text)),
+                _ => ("ok", None),
+            },
+            Err(e) => ("failed", Some(e.message.as_ref())),
+        };
+        let args = args.map(|a| Value::Object(a.clone()));
+        let error = error.map(|text| redact_message(text, args.as_ref()));
+        let args = args.as_ref().map_or(Value::Null, redact);
+        let record = serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "tool": tool,
+            "args": args,
+            "outcome": outcome,
+            "error": error,
+        });
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(file, "{}", record).and_then(|_| file.flush()) {
+            tracing::error!("Failed to write audit record for {}: {}", tool, e);
+        }
+    }
+}
+
+fn first_text(result: &CallToolResult) -> Option<&str> {
+    result.content.first().and_then(|c| match &c.raw {
+        RawContent::Text(t) => Some(t.text.as_str()),
+        _ => None,
+    })
+}
+
+/// Errors often quote the arguments back, e.g. an `old_str` that was not found. Keeps only the
+/// first line, with every redacted argument value blanked out, cut to `MAX_ERROR_CHARS`.
+fn redact_message(text: &str, args: Option<&Value>) -> String {
+    let mut secrets = Vec::new();
+    if let Some(args) = args {
+        collect_redacted(args, &mut secrets);
+    }
+    // Longest first, so a value containing another is blanked out as a whole
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    let mut text = text.to_string();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        text = text.replace(secret.as_str(), "<redacted>");
+    }
+    let first_line = text.lines().next().unwrap_or_default();
+    if first_line.chars().count() > MAX_ERROR_CHARS {
+        let cut: String = first_line.chars().take(MAX_ERROR_CHARS).collect();
+        format!("{}...", cut)
+    } else {
+        first_line.to_string()
+    }
+}
+
+/// Gathers the string values `redact` hides.
+fn collect_redacted(value: &Value, secrets: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map {
+                match v {
+                    Value::String(s) if REDACTED_KEYS.contains(&key.as_str()) => {
+                        secrets.push(s.clone())
+                    }
+                    _ => collect_redacted(v, secrets),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_redacted(v, secrets)),
+        _ => {}
+    }
+}
+
+/// Replaces content-bearing values with their size.
+fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, v)| {
+                    let v = if REDACTED_KEYS.contains(&key.as_str()) {
+                        let bytes = match v {
+                            Value::String(s) => s.len(),
+                            other => other.to_string().len(),
+                        };
+                        Value::String(format!("<redacted {} bytes>", bytes))
+                    } else {
+                        redact(v)
+                    };
+                    (key.clone(), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_nested_content() {
+        let args = serde_json::json!({
+            "path": "a.txt",
+            "content": "secret",
+            "edits": [{ "old_str": "x", "new_str": "y", "replace_all": true }],
+        });
+        let redacted = redact(&args);
+        assert_eq!(redacted["path"], "a.txt");
+        assert_eq!(redacted["content"], "<redacted 6 bytes>");
+        assert_eq!(redacted["edits"][0]["old_str"], "<redacted 1 bytes>");
+        assert_eq!(redacted["edits"][0]["replace_all"], true);
+        assert!(!redacted.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn test_redact_message_hides_quoted_arguments() {
+        let args = serde_json::json!({ "path": "a.txt", "old_str": "let key = 42;\nmore" });
+        let message =
+            "Error: old_str `let key = 42;\nmore` did not appear verbatim in a.txt.\n  1\tline";
+        assert_eq!(
+            redact_message(message, Some(&args)),
+            "Error: old_str `<redacted>` did not appear verbatim in a.txt."
+        );
+        let long = format!("Error: {}", "x".repeat(500));
+        assert_eq!(
+            redact_message(&long, None).chars().count(),
+            MAX_ERROR_CHARS + 3
+        );
+    }
+}
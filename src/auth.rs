@@ -0,0 +1,131 @@
+use axum::extract::{Request, State};
+use axum::http::{header::AUTHORIZATION, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// What a caller may do with the full service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// Only the tools the read-only service offers
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Bearer tokens and their capabilities. Callers without a known token are read-only.
+#[derive(Clone, Debug, Default)]
+pub struct CapabilityTokens {
+    tokens: HashMap<String, Capability>,
+}
+
+impl CapabilityTokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>, capability: Capability) -> Self {
+        self.tokens.insert(token.into(), capability);
+        self
+    }
+
+    /// Parses a comma-separated list of `token:ro` and `token:rw` entries.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut tokens = Self::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (token, capability) = match entry.rsplit_once(':') {
+                Some((token, "ro")) if !token.is_empty() => (token, Capability::ReadOnly),
+                Some((token, "rw")) if !token.is_empty() => (token, Capability::ReadWrite),
+                _ => {
+                    return Err(format!(
+                        "invalid capability token entry '{}'; expected <token>:ro or <token>:rw",
+                        entry
+                    ));
+                }
+            };
+            tokens = tokens.with_token(token, capability);
+        }
+        Ok(tokens)
+    }
+
+    /// Capability of the bearer token in `headers`.
+    pub fn capability(&self, headers: &HeaderMap) -> Capability {
+        bearer_token(headers)
+            .and_then(|token| self.tokens.get(token))
+            .copied()
+            .unwrap_or(Capability::ReadOnly)
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.tokens.keys().map(String::as_str)
+    }
+}
+
+/// The token of an `Authorization: Bearer <token>` header.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Compares in time independent of where the inputs first differ.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Middleware answering 401 unless the request carries one of `accepted` as its bearer token.
+pub async fn require_bearer(
+    State(accepted): State<Arc<Vec<String>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = bearer_token(request.headers())
+        .is_some_and(|token| accepted.iter().any(|a| constant_time_eq(a, token)));
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [("WWW-Authenticate", "Bearer")],
+            "Missing or invalid bearer token",
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_lookup_tokens() {
+        let tokens = CapabilityTokens::parse("reader:ro, writer:rw").unwrap();
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+        assert_eq!(
+            tokens.capability(&headers("Bearer writer")),
+            Capability::ReadWrite
+        );
+        assert_eq!(
+            tokens.capability(&headers("Bearer reader")),
+            Capability::ReadOnly
+        );
+        assert_eq!(
+            tokens.capability(&headers("Bearer unknown")),
+            Capability::ReadOnly
+        );
+        assert_eq!(tokens.capability(&HeaderMap::new()), Capability::ReadOnly);
+
+        assert!(CapabilityTokens::parse("writer:admin").is_err());
+        assert!(CapabilityTokens::parse(":rw").is_err());
+    }
+}
@@ -1,10 +1,15 @@
+pub mod audit;
+pub mod auth;
 pub mod logger;
 pub mod models;
 pub mod runtime;
 pub mod server;
 
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use tokio::sync::oneshot;
 
@@ -13,6 +18,8 @@ use tokio::sync::oneshot;
 struct CServer {
     shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>,
     server_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// Bound address and workspace of the last successful `start`
+    bound: Arc<Mutex<Option<(SocketAddr, String)>>>,
 }
 
 #[pymethods]
@@ -22,32 +29,86 @@ impl CServer {
         CServer {
             shutdown_tx: Arc::new(Mutex::new(None)),
             server_handle: Arc::new(Mutex::new(None)),
+            bound: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// Starts the server and resolves to the bound address once it is listening. Raises
+    /// `RuntimeError` when the port cannot be bound. `config` holds `server::ServerConfig`
+    /// fields, which take precedence over the matching environment variables.
+    #[pyo3(signature = (workspace, port, config=None))]
     fn start<'p>(
         &self,
         py: Python<'p>,
         workspace: String,
         port: u16,
+        config: Option<Bound<'p, PyDict>>,
     ) -> PyResult<Bound<'p, PyAny>> {
+        let mut config: server::ServerConfig = match config {
+            Some(config) => {
+                let json: String = py
+                    .import("json")?
+                    .call_method1("dumps", (config,))?
+                    .extract()?;
+                serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))?
+            }
+            None => server::ServerConfig::default(),
+        };
+        config.workspace_path = std::path::PathBuf::from(&workspace);
+        config.port = port;
+
         let shutdown_tx = self.shutdown_tx.clone();
         let server_handle = self.server_handle.clone();
-        let workspace_path = std::path::PathBuf::from(workspace);
+        let bound = self.bound.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let (tx, rx) = oneshot::channel();
             *shutdown_tx.lock().unwrap() = Some(tx);
 
+            let (ready_tx, ready_rx) = oneshot::channel();
             let handle = tokio::spawn(async move {
-                server::run_server(workspace_path, port, rx).await;
+                server::run_server(config, rx, Some(ready_tx)).await;
             });
 
             *server_handle.lock().unwrap() = Some(handle);
-            Ok(())
+            let addr = ready_rx
+                .await
+                .unwrap_or_else(|_| Err("Server exited before binding its port".to_string()))
+                .map_err(PyRuntimeError::new_err)?;
+            *bound.lock().unwrap() = Some((addr, workspace));
+            Ok(addr.to_string())
         })
     }
 
+    /// Whether the server task is alive. False before `start`, after `stop`, and once the
+    /// task has exited on its own, e.g. after a panic.
+    fn is_running(&self) -> bool {
+        self.server_handle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Returns `running`, plus the `address`, `port` and `workspace` of the last successful
+    /// `start` (`None` before the first).
+    fn status<'p>(&self, py: Python<'p>) -> PyResult<Bound<'p, PyDict>> {
+        let status = PyDict::new(py);
+        status.set_item("running", self.is_running())?;
+        let bound = self.bound.lock().unwrap().clone();
+        status.set_item("address", bound.as_ref().map(|(addr, _)| addr.to_string()))?;
+        status.set_item("port", bound.as_ref().map(|(addr, _)| addr.port()))?;
+        status.set_item("workspace", bound.map(|(_, workspace)| workspace))?;
+        Ok(status)
+    }
+
+    /// Returns every tool's `name`, `description` and `inputSchema` as a list of dicts.
+    fn list_tools<'p>(&self, py: Python<'p>) -> PyResult<Bound<'p, PyAny>> {
+        let tools = serde_json::to_string(&service::CoderMcpService::list_tools())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        py.import("json")?.call_method1("loads", (tools,))
+    }
+
     fn stop<'p>(&self, py: Python<'p>) -> PyResult<Bound<'p, PyAny>> {
         let shutdown_tx_mutex = self.shutdown_tx.clone();
         let server_handle_mutex = self.server_handle.clone();
@@ -73,4 +134,5 @@ fn coder_mcp(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     Ok(())
 }
 pub mod service;
+pub mod session_manager;
 pub mod tools;
@@ -1,13 +1,28 @@
 use chrono::Local;
 use colored::*;
 use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+// Bytes read at a time when tailing the log file from its end
+const TAIL_BLOCK_BYTES: u64 = 8 * 1024;
+
 /// Initializes the global logging system with colorized output and environment-based level filtering.
 ///
 /// The `RUST_LOG` environment variable can be used to control the log level (default: info).
 /// Example: `RUST_LOG=debug cargo run --example remote_test`
-pub fn init_logging() {
+///
+/// When `CODER_MCP_LOG_FILE` is set, logs are also appended to that file without colors; if
+/// it cannot be opened, nothing is initialized and the reason is returned. A subscriber that
+/// is already installed, e.g. by an earlier start in the same process, is kept.
+pub fn init_logging() -> Result<(), String> {
     if env::var("RUST_LOG").is_err() {
         unsafe { env::set_var("RUST_LOG", "info") };
     }
@@ -15,15 +30,83 @@ pub fn init_logging() {
     // Force colored output even if not a TTY
     colored::control::set_override(true);
 
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .with_ansi(true)
-        .with_writer(std::io::stdout)
-        .event_format(CustomFormatter)
-        .init();
+    let log_file = env::var("CODER_MCP_LOG_FILE")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| {
+            let path = PathBuf::from(p.trim());
+            let file = open_log_file(&path)
+                .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?;
+            let _ = LOG_FILE.set(path);
+            Ok::<_, String>(file)
+        })
+        .transpose()?;
+
+    let _ = subscriber(log_file).try_init();
+    Ok(())
+}
+
+/// The file logs are written to, if file logging was configured by `init_logging`.
+pub fn log_file() -> Option<&'static Path> {
+    LOG_FILE.get().map(PathBuf::as_path)
+}
+
+/// Opens `path` for appending, creating it and its parent directories if needed.
+pub fn open_log_file(path: &Path) -> io::Result<File> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Builds the subscriber used by `init_logging`: colorized stdout, plus plain text in
+/// `log_file` when given.
+pub fn subscriber(log_file: Option<File>) -> impl tracing::Subscriber + Send + Sync {
+    let file_layer = log_file.map(|file| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .event_format(CustomFormatter { ansi: false })
+    });
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(true)
+                .with_writer(std::io::stdout)
+                .event_format(CustomFormatter { ansi: true }),
+        )
+        .with(file_layer)
+}
+
+/// Returns the last `lines` lines of the log file at `path`, reading blocks backwards from
+/// the end so a large log is never loaded whole.
+pub fn tail_log(path: &Path, lines: usize) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut pos = file.metadata()?.len();
+    let mut tail = Vec::new();
+    let mut newlines = 0;
+    // One line break more than requested guarantees the first line kept is complete
+    while pos > 0 && newlines <= lines {
+        let size = TAIL_BLOCK_BYTES.min(pos);
+        pos -= size;
+        let mut block = vec![0; size as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut block)?;
+        newlines += block.iter().filter(|&&b| b == b'\n').count();
+        block.extend_from_slice(&tail);
+        tail = block;
+    }
+
+    let content = String::from_utf8_lossy(&tail);
+    let all_lines: Vec<&str> = content.lines().collect();
+    let skip = all_lines.len().saturating_sub(lines);
+    Ok(all_lines[skip..].join("\n"))
 }
 
-struct CustomFormatter;
+struct CustomFormatter {
+    ansi: bool,
+}
 
 impl<S, N> tracing_subscriber::fmt::FormatEvent<S, N> for CustomFormatter
 where
@@ -39,6 +122,15 @@ where
         let now = Local::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
         let level = *event.metadata().level();
 
+        if !self.ansi {
+            write!(writer, "{} {} ", now, level)?;
+            let mut message = String::new();
+            event.record(&mut MessageVisitor {
+                message: &mut message,
+            });
+            return writeln!(writer, "{}", message);
+        }
+
         let level_str = match level {
             tracing::Level::ERROR => "ERROR".red().bold().to_string(),
             tracing::Level::WARN => "WARN".yellow().bold().to_string(),
@@ -80,3 +172,28 @@ impl<'a> tracing::field::Visit for MessageVisitor<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tail_log_across_blocks() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("server.log");
+        let all: Vec<String> = (0..5000).map(|i| format!("line {}", i)).collect();
+        std::fs::write(&path, format!("{}\n", all.join("\n"))).unwrap();
+
+        assert_eq!(
+            tail_log(&path, 3).unwrap(),
+            "line 4997\nline 4998\nline 4999"
+        );
+        assert_eq!(tail_log(&path, 2000).unwrap(), all[3000..].join("\n"));
+        assert_eq!(tail_log(&path, 10_000).unwrap(), all.join("\n"));
+        assert_eq!(tail_log(&path, 0).unwrap(), "");
+
+        std::fs::write(&path, "first\nno trailing newline").unwrap();
+        assert_eq!(tail_log(&path, 1).unwrap(), "no trailing newline");
+    }
+}
@@ -1,4 +1,4 @@
-use coder_mcp::server::run_server;
+use coder_mcp::server::{parse_port, run_server, ServerConfig, DEFAULT_PORT};
 use std::env;
 use std::path::PathBuf;
 
@@ -12,7 +12,16 @@ async fn main() {
         .map(PathBuf::from)
         .unwrap_or_else(|_| cwd.join("workspace"));
 
-    let port = 3000;
+    let port = match env::var("CODER_MCP_PORT").or_else(|_| env::var("PORT")) {
+        Ok(value) => match parse_port(&value) {
+            Ok(port) => port,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        Err(_) => DEFAULT_PORT,
+    };
 
     // Create shutdown channel
     let (tx, rx) = tokio::sync::oneshot::channel();
@@ -23,5 +32,10 @@ async fn main() {
         let _ = tx.send(());
     });
 
-    run_server(workspace_path, port, rx).await;
+    let config = ServerConfig {
+        workspace_path,
+        port,
+        ..ServerConfig::default()
+    };
+    run_server(config, rx, None).await;
 }
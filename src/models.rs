@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -7,6 +8,9 @@ pub struct ExecuteBashRequest {
     pub command: String,
     pub cwd: Option<String>,
     pub timeout: Option<u64>,
+    /// Regex; the command is interrupted as soon as an output line matches it
+    #[serde(default)]
+    pub abort_on: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -32,6 +36,8 @@ pub struct BashCommand {
     pub command: String,
     pub cwd: Option<String>,
     pub timeout: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abort_on: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -48,5 +54,81 @@ pub struct BashOutput {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BashEventPage {
     pub items: Vec<BashEvent>,
+    /// Cursor to pass as `after` for the next page; `None` on the last page
     pub next_page_id: Option<String>,
 }
+
+/// Filters and paging for `search_bash_events_page`.
+#[derive(Debug, Clone, Default)]
+pub struct BashEventQuery {
+    pub command_id: Option<Uuid>,
+    /// `next_page_id` of the previous page
+    pub after: Option<String>,
+    /// Most events per page; unlimited by default
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BashPeek {
+    pub command_id: Uuid,
+    pub output: String,
+    pub is_running: bool,
+    pub exit_code: Option<i32>,
+}
+
+/// A command together with a summary of the output it produced.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandInfo {
+    pub command: BashCommand,
+    pub is_running: bool,
+    pub exit_code: Option<i32>,
+    /// Timestamp of the output event carrying the exit code
+    pub finished_at: Option<DateTime<Utc>>,
+    pub output_events: usize,
+    pub output_bytes: usize,
+    /// Last lines of the combined stdout and stderr
+    pub output_tail: String,
+}
+
+/// How long a finished command took, from its `BashCommand` event to its final `BashOutput`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandDuration {
+    pub command_id: Uuid,
+    pub command: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub exit_code: Option<i32>,
+}
+
+/// How the terminal session's environment and working directory differ from when it started.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EnvDiff {
+    pub added: BTreeMap<String, String>,
+    pub changed: BTreeMap<String, EnvChange>,
+    pub removed: Vec<String>,
+    pub initial_cwd: String,
+    pub cwd: String,
+    pub cwd_changed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EnvChange {
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationOp {
+    Create,
+    Edit,
+    Delete,
+}
+
+/// A workspace change made through one of the file tools.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Mutation {
+    pub op: MutationOp,
+    pub path: String,
+    pub timestamp: DateTime<Utc>,
+}
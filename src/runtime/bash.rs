@@ -1,29 +1,189 @@
-use crate::models::{BashCommand, BashEvent, BashEventPage, BashOutput, ExecuteBashRequest};
-use crate::runtime::terminal::TerminalSession;
-use chrono::Utc;
+use crate::models::{
+    BashCommand, BashEvent, BashEventPage, BashEventQuery, BashOutput, BashPeek, CommandDuration,
+    CommandInfo, EnvDiff, ExecuteBashRequest,
+};
+use crate::runtime::terminal::{Interrupter, TerminalSession};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use regex::Regex;
 use rusqlite::{params, Connection};
+use std::collections::VecDeque;
 use std::fs;
-use std::path::PathBuf;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+const ABORTED_NOTE: &str = "[command interrupted: output matched abort_on pattern]";
+const REPEAT_SUPPRESSED_NOTE: &str = "[repeated command suppressed; last result unchanged]";
+// Timeout for the `cd` issued when switching the session in and out of the sandbox
+const CHANGE_DIR_TIMEOUT_MS: u64 = 5000;
+// How long execute_and_wait keeps polling past a command's own timeout
+const WAIT_GRACE_SECS: u64 = 10;
+
+/// Suppresses an identical command once it has run `threshold` times within `window`.
+#[derive(Clone, Copy, Debug)]
+pub struct RepeatGuard {
+    pub threshold: usize,
+    pub window: Duration,
+}
+
+/// `ulimit` settings applied to every command. Limited commands run in a subshell, so the
+/// limits never reach the session shell itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResourceLimits {
+    /// Virtual memory limit in KiB (`ulimit -v`)
+    pub memory_kb: Option<u64>,
+    /// CPU time limit in seconds (`ulimit -t`)
+    pub cpu_secs: Option<u64>,
+}
+
+impl ResourceLimits {
+    fn is_empty(&self) -> bool {
+        self.memory_kb.is_none() && self.cpu_secs.is_none()
+    }
+
+    /// Runs `command` in a subshell with the limits set. The command is skipped if a limit
+    /// cannot be applied.
+    fn apply(&self, command: &str) -> String {
+        if self.is_empty() {
+            return command.to_string();
+        }
+        let mut prelude = Vec::new();
+        if let Some(kb) = self.memory_kb {
+            prelude.push(format!("ulimit -v {}", kb));
+        }
+        if let Some(secs) = self.cpu_secs {
+            prelude.push(format!("ulimit -t {}", secs));
+        }
+        subshell(&prelude.join(" && "), command)
+    }
+}
+
+struct RecentCommand {
+    at: Instant,
+    command: String,
+    cwd: Option<String>,
+    id: Uuid,
+}
+
+/// Scratch directory the terminal session can be switched into. It is created on first use and
+/// removed along with the service.
+#[derive(Default)]
+struct Sandbox {
+    dir: Option<PathBuf>,
+    enabled: bool,
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BashEventService {
     pub db: Arc<Mutex<Connection>>,
+    // Separate connection for queries, so polling does not wait for writes (WAL mode)
+    reader: Arc<Mutex<Connection>>,
+    events_dir: PathBuf,
     pub terminal_session: Arc<Mutex<TerminalSession>>,
+    interrupter: Interrupter,
+    // Command currently executing in the terminal session
+    running: Arc<Mutex<Option<Uuid>>>,
+    repeat_guard: Option<RepeatGuard>,
+    recent_commands: Arc<Mutex<VecDeque<RecentCommand>>>,
+    command_wrapper: Option<String>,
+    resource_limits: ResourceLimits,
+    workdir: PathBuf,
+    sandbox: Arc<Mutex<Sandbox>>,
 }
 
 impl BashEventService {
-    pub fn new(bash_events_dir: PathBuf, workdir: Option<PathBuf>) -> Self {
-        fs::create_dir_all(&bash_events_dir).expect("Failed to create bash events dir");
+    /// Opens the event store in `bash_events_dir` and starts the terminal session in
+    /// `workdir` with `shell` (default `$SHELL`, then `bash`).
+    pub fn new(bash_events_dir: PathBuf, workdir: Option<PathBuf>, shell: Option<String>) -> Self {
+        Self::try_new(bash_events_dir, workdir, shell).unwrap_or_else(|e| panic!("{:#}", e))
+    }
+
+    /// Like `new`, but returns why the event store or terminal session could not be set up.
+    pub fn try_new(
+        bash_events_dir: PathBuf,
+        workdir: Option<PathBuf>,
+        shell: Option<String>,
+    ) -> anyhow::Result<Self> {
+        fs::create_dir_all(&bash_events_dir).context("Failed to create bash events dir")?;
         let db_path = bash_events_dir.join("bash_events.db");
-        let conn = Connection::open(db_path).expect("Failed to open SQLite database");
+        let conn = Self::open_connection(&db_path).context("Failed to open SQLite database")?;
+        Self::init_schema(&conn);
+        let reader = Self::open_connection(&db_path).context("Failed to open SQLite database")?;
+        Self::with_database(
+            Arc::new(Mutex::new(conn)),
+            Arc::new(Mutex::new(reader)),
+            bash_events_dir,
+            workdir,
+            shell,
+        )
+    }
+
+    /// Like `new`, but keeps events in memory. Nothing is written to disk and the events are
+    /// gone once the service is dropped.
+    pub fn new_in_memory(workdir: Option<PathBuf>, shell: Option<String>) -> Self {
+        Self::try_new_in_memory(workdir, shell).unwrap_or_else(|e| panic!("{:#}", e))
+    }
+
+    /// Like `new_in_memory`, but returns why the terminal session could not be started.
+    pub fn try_new_in_memory(
+        workdir: Option<PathBuf>,
+        shell: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let conn = Connection::open_in_memory().context("Failed to open SQLite database")?;
+        Self::init_schema(&conn);
+        // Every in-memory connection is a separate database, so queries share the writer's
+        let db = Arc::new(Mutex::new(conn));
+        Self::with_database(db.clone(), db, PathBuf::new(), workdir, shell)
+    }
+
+    fn with_database(
+        db: Arc<Mutex<Connection>>,
+        reader: Arc<Mutex<Connection>>,
+        events_dir: PathBuf,
+        workdir: Option<PathBuf>,
+        shell: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let session_dir = workdir
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("/"));
+        let terminal_session = TerminalSession::new(workdir, shell)
+            .context("Failed to initialize terminal session")?;
+
+        Ok(Self {
+            db,
+            reader,
+            events_dir,
+            interrupter: terminal_session.interrupter(),
+            terminal_session: Arc::new(Mutex::new(terminal_session)),
+            running: Arc::new(Mutex::new(None)),
+            repeat_guard: None,
+            recent_commands: Arc::new(Mutex::new(VecDeque::new())),
+            command_wrapper: None,
+            resource_limits: ResourceLimits::default(),
+            workdir: session_dir,
+            sandbox: Arc::new(Mutex::new(Sandbox::default())),
+        })
+    }
 
+    fn init_schema(conn: &Connection) {
         // Initialize table
         conn.execute(
             "CREATE TABLE IF NOT EXISTS bash_events (
                 id TEXT PRIMARY KEY,
                 timestamp TEXT NOT NULL,
+                timestamp_ms INTEGER,
                 command_id TEXT NOT NULL,
                 event_type TEXT NOT NULL,
                 json_data TEXT NOT NULL
@@ -31,6 +191,7 @@ impl BashEventService {
             [],
         )
         .expect("Failed to create tables");
+        Self::migrate_timestamp_ms(conn).expect("Failed to migrate timestamp_ms column");
 
         // indexes
         conn.execute(
@@ -38,20 +199,153 @@ impl BashEventService {
             [],
         )
         .expect("Failed to create index on command_id");
-        
+
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_bash_events_timestamp ON bash_events (timestamp)",
             [],
         )
         .expect("Failed to create index on timestamp");
 
-        let terminal_session =
-            TerminalSession::new(workdir).expect("Failed to initialize terminal session");
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_bash_events_timestamp_ms ON bash_events (timestamp_ms)",
+            [],
+        )
+        .expect("Failed to create index on timestamp_ms");
+    }
 
-        Self {
-            db: Arc::new(Mutex::new(conn)),
-            terminal_session: Arc::new(Mutex::new(terminal_session)),
+    /// Opens the event database in WAL mode, so readers and the writer do not block each other,
+    /// waiting up to 5 seconds for locks held by other connections.
+    fn open_connection(path: &Path) -> rusqlite::Result<Connection> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        Ok(conn)
+    }
+
+    /// Adds the numeric `timestamp_ms` column to databases created before it existed and
+    /// fills it from the RFC3339 `timestamp` text.
+    fn migrate_timestamp_ms(conn: &Connection) -> rusqlite::Result<()> {
+        let has_column = conn
+            .prepare("SELECT 1 FROM pragma_table_info('bash_events') WHERE name = 'timestamp_ms'")?
+            .exists([])?;
+        if !has_column {
+            conn.execute(
+                "ALTER TABLE bash_events ADD COLUMN timestamp_ms INTEGER",
+                [],
+            )?;
         }
+
+        let mut stmt =
+            conn.prepare("SELECT rowid, timestamp FROM bash_events WHERE timestamp_ms IS NULL")?;
+        let legacy: Vec<(i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        for (rowid, timestamp) in legacy {
+            let millis = DateTime::parse_from_rfc3339(&timestamp)
+                .map(|t| t.timestamp_millis())
+                .unwrap_or(0);
+            conn.execute(
+                "UPDATE bash_events SET timestamp_ms = ?1 WHERE rowid = ?2",
+                params![millis, rowid],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Directory holding the event database; empty for an in-memory database.
+    pub fn events_dir(&self) -> &Path {
+        &self.events_dir
+    }
+
+    /// Enables the repeated-command guard: once an identical command (same text and cwd)
+    /// has run `threshold` times within `window`, further submissions return the previous
+    /// result instead of executing again.
+    pub fn with_repeat_guard(mut self, threshold: usize, window: Duration) -> Self {
+        self.repeat_guard = Some(RepeatGuard { threshold, window });
+        self
+    }
+
+    /// Wraps every command before it is written to the terminal, e.g. `nice -n 10 {cmd}`.
+    /// `{cmd}` is replaced with `bash -c '<command>'`, so pipelines, `;`, `&&` and redirections
+    /// all run under the wrapper; a template without the placeholder is used as a prefix.
+    /// Since wrapped commands run in a child shell, `cd` and `export` in them no longer carry
+    /// over to later commands. Events still record the original command.
+    pub fn with_command_wrapper(mut self, template: impl Into<String>) -> Self {
+        self.command_wrapper = Some(template.into());
+        self
+    }
+
+    /// Applies `ulimit` settings to every command. Since limited commands run in a subshell,
+    /// `cd` and `export` in them no longer carry over to later commands.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.resource_limits = limits;
+        self
+    }
+
+    fn wrap_command(&self, command: &str) -> String {
+        let wrapped = match &self.command_wrapper {
+            Some(template) => {
+                let payload = format!("bash -c {}", shell_quote(command));
+                if template.contains("{cmd}") {
+                    template.replace("{cmd}", &payload)
+                } else {
+                    format!("{} {}", template, payload)
+                }
+            }
+            None => command.to_string(),
+        };
+        self.resource_limits.apply(&wrapped)
+    }
+
+    /// Switches the terminal session into a scratch directory under the system temp dir, or
+    /// back to the workspace. The sandbox keeps its files while disabled. Returns the sandbox
+    /// path when enabled.
+    pub async fn set_sandbox_mode(&self, enabled: bool) -> anyhow::Result<Option<PathBuf>> {
+        let target = if enabled {
+            let mut sandbox = self.sandbox.lock().unwrap();
+            match &sandbox.dir {
+                Some(dir) => dir.clone(),
+                None => {
+                    let dir = std::env::temp_dir()
+                        .join(format!("coder-mcp-sandbox-{}", Uuid::new_v4().simple()));
+                    fs::create_dir_all(&dir)?;
+                    sandbox.dir = Some(dir.clone());
+                    dir
+                }
+            }
+        } else {
+            self.workdir.clone()
+        };
+
+        let session = self.terminal_session.clone();
+        let cd = format!("cd -- {}", shell_quote(&target.to_string_lossy()));
+        let (output, exit_code) = tokio::task::spawn_blocking(move || {
+            session.lock().unwrap().execute(&cd, CHANGE_DIR_TIMEOUT_MS)
+        })
+        .await??;
+        if exit_code != 0 {
+            return Err(anyhow::anyhow!(
+                "Failed to change to {}: {}",
+                target.display(),
+                output
+            ));
+        }
+
+        self.sandbox.lock().unwrap().enabled = enabled;
+        Ok(enabled.then_some(target))
+    }
+
+    /// How the terminal session's environment and directory drifted since it started. Waits
+    /// for a running command to finish first.
+    pub async fn session_env_diff(&self) -> anyhow::Result<EnvDiff> {
+        let session = self.terminal_session.clone();
+        tokio::task::spawn_blocking(move || session.lock().unwrap().env_diff()).await?
+    }
+
+    /// The directory commands run in while sandbox mode is on, or `None` when it is off.
+    pub fn sandbox_dir(&self) -> Option<PathBuf> {
+        let sandbox = self.sandbox.lock().unwrap();
+        sandbox.dir.clone().filter(|_| sandbox.enabled)
     }
 
     fn save_event(&self, event: &BashEvent) {
@@ -61,12 +355,14 @@ impl BashEventService {
         };
 
         let json = serde_json::to_string(event).expect("Failed to serialize event");
+        // RFC3339 is kept for display; ordering uses the numeric column
         let timestamp_str = event.timestamp().to_rfc3339();
+        let timestamp_ms = event.timestamp().timestamp_millis();
 
         let conn = self.db.lock().unwrap();
         conn.execute(
-            "INSERT INTO bash_events (id, timestamp, command_id, event_type, json_data) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![id.simple().to_string(), timestamp_str, command_id.simple().to_string(), event_type, json],
+            "INSERT INTO bash_events (id, timestamp, timestamp_ms, command_id, event_type, json_data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id.simple().to_string(), timestamp_str, timestamp_ms, command_id.simple().to_string(), event_type, json],
         )
         .expect("Failed to insert event info db");
     }
@@ -79,11 +375,32 @@ impl BashEventService {
             command: req.command.clone(),
             cwd: req.cwd.clone(),
             timeout: req.timeout.unwrap_or(300),
+            abort_on: req.abort_on.clone(),
         };
 
         // Save initial command event synchronously
         self.save_event(&BashEvent::BashCommand(bash_command.clone()));
 
+        if let Some(previous) = self.suppressed_result(&req) {
+            tracing::warn!("Suppressing repeated command: {}", req.command);
+            let mut output = previous.output;
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            output.push_str(REPEAT_SUPPRESSED_NOTE);
+            self.save_event(&BashEvent::BashOutput(BashOutput {
+                id: Uuid::new_v4(),
+                timestamp: Utc::now(),
+                command_id,
+                order: 0,
+                exit_code: previous.exit_code,
+                stdout: Some(output),
+                stderr: None,
+            }));
+            return bash_command;
+        }
+        self.record_execution(&req, command_id);
+
         let service = self.clone();
         let cmd_clone = bash_command.clone();
 
@@ -95,24 +412,140 @@ impl BashEventService {
         bash_command
     }
 
+    /// Returns the last finished result of an identical command if the repeat guard
+    /// threshold has been reached within its window.
+    fn suppressed_result(&self, req: &ExecuteBashRequest) -> Option<BashPeek> {
+        let guard = self.repeat_guard?;
+        let last_id = {
+            let mut recent = self.recent_commands.lock().unwrap();
+            let now = Instant::now();
+            recent.retain(|r| now.duration_since(r.at) <= guard.window);
+            let matching: Vec<Uuid> = recent
+                .iter()
+                .filter(|r| r.command == req.command && r.cwd == req.cwd)
+                .map(|r| r.id)
+                .collect();
+            if matching.len() < guard.threshold {
+                return None;
+            }
+            *matching.last()?
+        };
+
+        // Only a finished result can be replayed
+        self.command_progress(last_id).filter(|p| !p.is_running)
+    }
+
+    fn record_execution(&self, req: &ExecuteBashRequest, id: Uuid) {
+        if self.repeat_guard.is_none() {
+            return;
+        }
+        let mut recent = self.recent_commands.lock().unwrap();
+        recent.push_back(RecentCommand {
+            at: Instant::now(),
+            command: req.command.clone(),
+            cwd: req.cwd.clone(),
+            id,
+        });
+    }
+
+    /// Resolves a per-command working directory against the workspace.
+    fn resolve_cwd(&self, cwd: &str) -> Result<PathBuf, String> {
+        let dir = self.workdir.join(cwd);
+        if dir.is_dir() {
+            Ok(dir)
+        } else {
+            Err(format!(
+                "cwd {} is not an existing directory",
+                dir.display()
+            ))
+        }
+    }
+
+    /// Finishes a command that could not be started with `message` as its error output.
+    fn save_start_error(&self, command_id: Uuid, message: String) {
+        self.save_event(&BashEvent::BashOutput(BashOutput {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            command_id,
+            order: 0,
+            exit_code: Some(-1),
+            stdout: None,
+            stderr: Some(format!("Error: {}", message)),
+        }));
+    }
+
     async fn execute_bash_command_background(&self, command: BashCommand) {
         let terminal_session = self.terminal_session.clone();
-        let cmd_text = command.command.clone();
+        // Running without the requested guard would be worse than not running at all
+        let abort_re = match compile_abort_on(command.abort_on.as_deref()) {
+            Ok(abort_re) => abort_re,
+            Err(e) => {
+                self.save_start_error(command.id, e);
+                return;
+            }
+        };
+        // A per-command cwd applies to a subshell, so the session's own directory is unchanged
+        let cmd_text = match command.cwd.as_deref().map(|cwd| self.resolve_cwd(cwd)) {
+            None => self.wrap_command(&command.command),
+            Some(Ok(dir)) => subshell(
+                &format!("cd -- {}", shell_quote(&dir.to_string_lossy())),
+                &self.wrap_command(&command.command),
+            ),
+            Some(Err(e)) => {
+                self.save_start_error(command.id, e);
+                return;
+            }
+        };
         let timeout_val = command.timeout;
+        let service = self.clone();
+        let command_id = command.id;
 
+        // Partial output is stored as BashOutput events without an exit code, so that
+        // it can be read while the command is still running.
         let result = tokio::task::spawn_blocking(move || {
             let mut session = terminal_session.lock().unwrap();
-            session.execute(&cmd_text, timeout_val * 1000) // ms
+            *service.running.lock().unwrap() = Some(command_id);
+            let mut order = 0;
+            let mut aborted = false;
+            let result = session.execute_streaming(&cmd_text, timeout_val * 1000, |chunk| {
+                service.save_event(&BashEvent::BashOutput(BashOutput {
+                    id: Uuid::new_v4(),
+                    timestamp: Utc::now(),
+                    command_id,
+                    order,
+                    exit_code: None,
+                    stdout: Some(chunk.to_string()),
+                    stderr: None,
+                }));
+                order += 1;
+                if let Some(re) = &abort_re
+                    && chunk.lines().any(|line| re.is_match(line))
+                {
+                    aborted = true;
+                    return ControlFlow::Break(());
+                }
+                ControlFlow::Continue(())
+            });
+            *service.running.lock().unwrap() = None;
+            result.map(|(mut output, exit_code)| {
+                if aborted {
+                    if !output.is_empty() {
+                        output.push('\n');
+                    }
+                    output.push_str(ABORTED_NOTE);
+                }
+                (output, exit_code, order)
+            })
         })
         .await;
 
         match result {
-            Ok(Ok((output, exit_code))) => {
+            Ok(Ok((output, exit_code, order))) => {
                 let out = BashOutput {
                     id: Uuid::new_v4(),
                     timestamp: Utc::now(),
                     command_id: command.id,
-                    order: 0,
+                    order,
                     exit_code: Some(exit_code),
                     stdout: Some(output),
                     stderr: None, // We merged everything into stdout in this simple PTY model
@@ -147,47 +580,344 @@ impl BashEventService {
         }
     }
 
-    pub fn search_bash_events(&self, command_id: Option<Uuid>) -> BashEventPage {
-        let conn = self.db.lock().unwrap();
-        let mut stmt;
-        let mut rows = if let Some(cid) = command_id {
-            stmt = conn.prepare("SELECT json_data FROM bash_events WHERE command_id = ? ORDER BY timestamp ASC").unwrap();
-            stmt.query(params![cid.simple().to_string()]).unwrap()
-        } else {
-            stmt = conn.prepare("SELECT json_data FROM bash_events ORDER BY timestamp ASC").unwrap();
-            stmt.query([]).unwrap()
-        };
+    /// Sends Ctrl-C to the terminal if `command_id` is the command currently executing. The
+    /// command then finishes as usual, normally with exit code 130. Returns false if the
+    /// command is not running.
+    pub fn cancel_command(&self, command_id: Uuid) -> anyhow::Result<bool> {
+        let running = self.running.lock().unwrap();
+        if *running != Some(command_id) {
+            return Ok(false);
+        }
+        self.interrupter.interrupt()?;
+        Ok(true)
+    }
 
-        let mut events = Vec::new();
+    /// Starts a command and waits until it has finished, polling the event store. A command
+    /// still running well past its timeout is interrupted and returned with `is_running` set.
+    pub async fn execute_and_wait(&self, req: ExecuteBashRequest) -> BashPeek {
+        let cmd = self.start_bash_command(req);
+        let deadline = Instant::now() + Duration::from_secs(cmd.timeout + WAIT_GRACE_SECS);
+        loop {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            match self.command_progress(cmd.id) {
+                Some(progress) if !progress.is_running => return progress,
+                progress if Instant::now() >= deadline => {
+                    if let Err(e) = self.cancel_command(cmd.id) {
+                        tracing::warn!("Failed to interrupt command {}: {:#}", cmd.id, e);
+                    }
+                    return progress.unwrap_or(BashPeek {
+                        command_id: cmd.id,
+                        output: String::new(),
+                        is_running: true,
+                        exit_code: None,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the output accumulated so far for a command, or `None` if the command is unknown.
+    pub fn command_progress(&self, command_id: Uuid) -> Option<BashPeek> {
+        let page = self.search_bash_events(Some(command_id));
+        if page.items.is_empty() {
+            return None;
+        }
+
+        let mut output = String::new();
+        let mut exit_code = None;
+        for event in &page.items {
+            if let BashEvent::BashOutput(out) = event {
+                append_output(&mut output, out);
+                if out.exit_code.is_some() {
+                    exit_code = out.exit_code;
+                }
+            }
+        }
+
+        Some(BashPeek {
+            command_id,
+            output,
+            is_running: exit_code.is_none(),
+            exit_code,
+        })
+    }
+
+    /// Every command, or just `command_id`, oldest first, each with the last `tail_lines`
+    /// lines of its output.
+    pub fn command_history(&self, command_id: Option<Uuid>, tail_lines: usize) -> Vec<CommandInfo> {
+        let mut history: Vec<(CommandInfo, String)> = Vec::new();
+        for event in self.search_bash_events(command_id).items {
+            match event {
+                BashEvent::BashCommand(command) => history.push((
+                    CommandInfo {
+                        command,
+                        is_running: true,
+                        exit_code: None,
+                        finished_at: None,
+                        output_events: 0,
+                        output_bytes: 0,
+                        output_tail: String::new(),
+                    },
+                    String::new(),
+                )),
+                BashEvent::BashOutput(out) => {
+                    let Some((info, output)) = history
+                        .iter_mut()
+                        .rev()
+                        .find(|(info, _)| info.command.id == out.command_id)
+                    else {
+                        continue;
+                    };
+                    info.output_events += 1;
+                    info.output_bytes += out.stdout.as_ref().map_or(0, String::len);
+                    info.output_bytes += out.stderr.as_ref().map_or(0, String::len);
+                    append_output(output, &out);
+                    if out.exit_code.is_some() {
+                        info.is_running = false;
+                        info.exit_code = out.exit_code;
+                        info.finished_at = Some(out.timestamp);
+                    }
+                }
+            }
+        }
+
+        history
+            .into_iter()
+            .map(|(mut info, output)| {
+                let lines: Vec<&str> = output.lines().collect();
+                info.output_tail = lines[lines.len().saturating_sub(tail_lines)..].join("\n");
+                info
+            })
+            .collect()
+    }
+
+    /// Like `command_progress`, but keeps only the last `lines` lines of output.
+    pub fn peek_bash_output(&self, command_id: Uuid, lines: usize) -> Option<BashPeek> {
+        let mut peek = self.command_progress(command_id)?;
+        let all_lines: Vec<&str> = peek.output.lines().collect();
+        let skip = all_lines.len().saturating_sub(lines);
+        peek.output = all_lines[skip..].join("\n");
+        Some(peek)
+    }
+
+    /// Looks up a command by its ID or by the ID of one of its output events.
+    pub fn get_command(&self, id: Uuid) -> Option<BashCommand> {
+        let conn = self.reader.lock().unwrap();
+        let json_data: String = conn
+            .query_row(
+                "SELECT json_data FROM bash_events
+                 WHERE event_type = 'BashCommand'
+                   AND command_id = COALESCE((SELECT command_id FROM bash_events WHERE id = ?1), ?1)",
+                params![id.simple().to_string()],
+                |row| row.get(0),
+            )
+            .ok()?;
+        match serde_json::from_str(&json_data) {
+            Ok(BashEvent::BashCommand(command)) => Some(command),
+            _ => None,
+        }
+    }
+
+    /// The command behind `id` (see `get_command`) with a summary of its output, keeping the
+    /// last `tail_lines` lines of output.
+    pub fn command_info(&self, id: Uuid, tail_lines: usize) -> Option<CommandInfo> {
+        let command = self.get_command(id)?;
+        let peek = self.peek_bash_output(command.id, tail_lines)?;
+
+        let mut output_events = 0;
+        let mut output_bytes = 0;
+        let mut finished_at = None;
+        for event in self.search_bash_events(Some(command.id)).items {
+            if let BashEvent::BashOutput(out) = event {
+                output_events += 1;
+                output_bytes += out.stdout.as_ref().map_or(0, String::len);
+                output_bytes += out.stderr.as_ref().map_or(0, String::len);
+                if out.exit_code.is_some() {
+                    finished_at = Some(out.timestamp);
+                }
+            }
+        }
+
+        Some(CommandInfo {
+            command,
+            is_running: peek.is_running,
+            exit_code: peek.exit_code,
+            finished_at,
+            output_events,
+            output_bytes,
+            output_tail: peek.output,
+        })
+    }
+
+    /// Finished commands ordered by duration, longest first. The duration runs from the
+    /// command event to the output event carrying the exit code.
+    pub fn slowest_commands(&self, limit: usize) -> Vec<CommandDuration> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT c.json_data, o.timestamp_ms - c.timestamp_ms AS duration_ms,
+                        json_extract(o.json_data, '$.exit_code')
+                 FROM bash_events c
+                 JOIN bash_events o ON o.command_id = c.command_id
+                 WHERE c.event_type = 'BashCommand'
+                   AND o.event_type = 'BashOutput'
+                   AND json_extract(o.json_data, '$.exit_code') IS NOT NULL
+                 ORDER BY duration_ms DESC, c.timestamp_ms ASC
+                 LIMIT ?",
+            )
+            .unwrap();
+        let mut rows = stmt.query(params![limit as i64]).unwrap();
+
+        let mut commands = Vec::new();
         while let Some(row) = rows.next().unwrap() {
             let json_data: String = row.get(0).unwrap();
+            let Ok(BashEvent::BashCommand(command)) = serde_json::from_str(&json_data) else {
+                continue;
+            };
+            commands.push(CommandDuration {
+                command_id: command.id,
+                command: command.command,
+                started_at: command.timestamp,
+                duration_ms: row.get(1).unwrap(),
+                exit_code: row.get(2).unwrap(),
+            });
+        }
+        commands
+    }
+
+    /// Deletes events older than `older_than` and returns how many were removed.
+    pub fn prune_events(&self, older_than: DateTime<Utc>) -> anyhow::Result<usize> {
+        let conn = self.db.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM bash_events WHERE timestamp_ms < ?1",
+            params![older_than.timestamp_millis()],
+        )?;
+        Ok(deleted)
+    }
+
+    /// Deletes all but the newest `n` events and returns how many were removed. A command
+    /// can lose its earliest events this way while later output is kept.
+    pub fn prune_keep_last(&self, n: usize) -> anyhow::Result<usize> {
+        let conn = self.db.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM bash_events WHERE rowid NOT IN (
+                SELECT rowid FROM bash_events ORDER BY timestamp_ms DESC, rowid DESC LIMIT ?1
+            )",
+            params![n as i64],
+        )?;
+        Ok(deleted)
+    }
+
+    /// Returns every event, or those of one command, oldest first.
+    pub fn search_bash_events(&self, command_id: Option<Uuid>) -> BashEventPage {
+        self.search_bash_events_page(&BashEventQuery {
+            command_id,
+            ..Default::default()
+        })
+        .expect("a query without a cursor is always valid")
+    }
+
+    /// Returns one page of events, oldest first. Events are ordered by timestamp, then by
+    /// insertion, and the cursor records both, so pages stay stable as new events arrive.
+    pub fn search_bash_events_page(&self, query: &BashEventQuery) -> anyhow::Result<BashEventPage> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(cid) = query.command_id {
+            conditions.push("command_id = ?");
+            values.push(cid.simple().to_string().into());
+        }
+        if let Some(after) = &query.after {
+            let (timestamp_ms, rowid) = after
+                .split_once(':')
+                .and_then(|(ts, rowid)| Some((ts.parse::<i64>().ok()?, rowid.parse::<i64>().ok()?)))
+                .ok_or_else(|| anyhow::anyhow!("invalid page cursor '{}'", after))?;
+            conditions.push("(timestamp_ms > ? OR (timestamp_ms = ? AND rowid > ?))");
+            values.extend([timestamp_ms.into(), timestamp_ms.into(), rowid.into()]);
+        }
+        let mut sql = "SELECT json_data, timestamp_ms, rowid FROM bash_events".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        sql.push_str(" ORDER BY timestamp_ms ASC, rowid ASC");
+        if let Some(limit) = query.limit {
+            // One extra row tells whether another page follows
+            sql.push_str(&format!(" LIMIT {}", limit + 1));
+        }
+
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query(rusqlite::params_from_iter(values))?;
+        let mut events = Vec::new();
+        let mut last_cursor = None;
+        let mut has_more = false;
+        while let Some(row) = rows.next()? {
+            if query.limit.is_some_and(|limit| events.len() == limit) {
+                has_more = true;
+                break;
+            }
+            let json_data: String = row.get(0)?;
+            let timestamp_ms: i64 = row.get(1)?;
+            let rowid: i64 = row.get(2)?;
             if let Ok(event) = serde_json::from_str(&json_data) {
                 events.push(event);
             }
+            last_cursor = Some(format!("{}:{}", timestamp_ms, rowid));
         }
 
-        BashEventPage {
+        Ok(BashEventPage {
             items: events,
-            next_page_id: None,
+            next_page_id: if has_more { last_cursor } else { None },
+        })
+    }
+}
+
+/// Appends an output event to the combined output of its command, stderr on its own line.
+fn append_output(output: &mut String, out: &BashOutput) {
+    if let Some(stdout) = &out.stdout {
+        output.push_str(stdout);
+    }
+    if let Some(stderr) = &out.stderr {
+        if !output.is_empty() {
+            output.push('\n');
         }
+        output.push_str(stderr);
     }
 }
 
+/// Runs `command` in a subshell once `prelude` has succeeded.
+fn subshell(prelude: &str, command: &str) -> String {
+    // The `)` goes on its own line so a trailing `# comment` cannot swallow it
+    format!("({} && {}\n)", prelude, command)
+}
+
+/// Quotes `value` as a single shell word.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Compiles a command's `abort_on` pattern, if it has one.
+pub fn compile_abort_on(pattern: Option<&str>) -> Result<Option<Regex>, String> {
+    pattern
+        .map(|p| Regex::new(p).map_err(|e| format!("Invalid abort_on regex '{}': {}", p, e)))
+        .transpose()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::Duration;
     use tempfile::tempdir;
 
     #[tokio::test]
     async fn test_bash_event_service_execution() {
         let dir = tempdir().unwrap();
-        let service = BashEventService::new(dir.path().to_path_buf(), None);
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
 
         let req = ExecuteBashRequest {
             command: "echo test_bash_service".to_string(),
             cwd: None,
             timeout: Some(5),
+            abort_on: None,
         };
 
         let cmd = service.start_bash_command(req);
@@ -199,22 +929,689 @@ mod tests {
         while attempts < 20 {
             tokio::time::sleep(Duration::from_millis(200)).await;
             let page = service.search_bash_events(Some(cmd.id));
-            if let Some(last) = page.items.last() {
-                if let BashEvent::BashOutput(out) = last {
-                    found_output = true;
-                    assert_eq!(out.exit_code, Some(0));
-                    let output = out.stdout.as_ref().unwrap();
-                    assert!(
-                        output.contains("test_bash_service"),
-                        "Output did not contain expected string. Got: '{}'",
-                        output
-                    );
-                    break;
-                }
+            if let Some(BashEvent::BashOutput(out)) = page.items.last() {
+                found_output = true;
+                assert_eq!(out.exit_code, Some(0));
+                let output = out.stdout.as_ref().unwrap();
+                assert!(
+                    output.contains("test_bash_service"),
+                    "Output did not contain expected string. Got: '{}'",
+                    output
+                );
+                break;
             }
             attempts += 1;
         }
 
         assert!(found_output, "Did not find bash output");
     }
+
+    #[test]
+    fn test_try_new_reports_terminal_failure() {
+        let dir = tempdir().unwrap();
+        let shell = Some("/nonexistent/bash".to_string());
+        let Err(e) = BashEventService::try_new(dir.path().to_path_buf(), None, shell) else {
+            panic!("a missing shell cannot start");
+        };
+        assert!(format!("{:#}", e).starts_with("Failed to initialize terminal session"));
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_service_leaves_no_files() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new_in_memory(Some(dir.path().to_path_buf()), None);
+
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "echo in-memory".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: None,
+            })
+            .await;
+        assert_eq!(result.exit_code, Some(0));
+        assert!(result.output.contains("in-memory"));
+
+        let page = service.search_bash_events(Some(result.command_id));
+        assert!(page.items.len() >= 2);
+        assert!(service.prune_keep_last(1).unwrap() >= 1);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_peek_bash_output_while_running() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+
+        let req = ExecuteBashRequest {
+            command: "echo first; sleep 2; echo second".to_string(),
+            cwd: None,
+            timeout: Some(10),
+            abort_on: None,
+        };
+        let cmd = service.start_bash_command(req);
+
+        // First peek: only the early output is available and the command is still running
+        let mut first_peek = None;
+        for _ in 0..20 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let peek = service.peek_bash_output(cmd.id, 10).unwrap();
+            if peek.output.contains("first") {
+                first_peek = Some(peek);
+                break;
+            }
+        }
+        let first_peek = first_peek.expect("Did not see partial output");
+        assert!(first_peek.is_running);
+        assert!(!first_peek.output.contains("second"));
+
+        // Second peek: later output shows up once the command progresses
+        let mut second_peek = None;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let peek = service.peek_bash_output(cmd.id, 10).unwrap();
+            if !peek.is_running {
+                second_peek = Some(peek);
+                break;
+            }
+        }
+        let second_peek = second_peek.expect("Command did not finish");
+        assert_eq!(second_peek.exit_code, Some(0));
+        assert!(second_peek.output.contains("second"));
+
+        // Only the requested number of trailing lines is returned
+        let tail = service.peek_bash_output(cmd.id, 1).unwrap();
+        assert!(tail.output.contains("second"));
+        assert!(!tail.output.contains("first"));
+    }
+
+    #[tokio::test]
+    async fn test_command_info_by_command_or_output_id() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+        let req = ExecuteBashRequest {
+            command: "echo info_line".to_string(),
+            cwd: None,
+            timeout: Some(7),
+            abort_on: None,
+        };
+        let peek = service.execute_and_wait(req).await;
+
+        let info = service.command_info(peek.command_id, 20).unwrap();
+        assert_eq!(info.command.id, peek.command_id);
+        assert_eq!(info.command.command, "echo info_line");
+        assert_eq!(info.command.timeout, 7);
+        assert_eq!(info.exit_code, Some(0));
+        assert!(!info.is_running);
+        assert!(info.finished_at.unwrap() >= info.command.timestamp);
+        assert!(info.output_events >= 1);
+        assert!(info.output_tail.contains("info_line"));
+
+        let output_id = service
+            .search_bash_events(Some(peek.command_id))
+            .items
+            .iter()
+            .find_map(|event| match event {
+                BashEvent::BashOutput(out) => Some(out.id),
+                _ => None,
+            })
+            .unwrap();
+        let command = service.get_command(output_id).unwrap();
+        assert_eq!(command.id, peek.command_id);
+
+        assert!(service.get_command(Uuid::new_v4()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_mode_switches_directory() {
+        let dir = tempdir().unwrap();
+        let workspace = tempdir().unwrap();
+        let service = BashEventService::new(
+            dir.path().to_path_buf(),
+            Some(workspace.path().to_path_buf()),
+            None,
+        );
+        let pwd = ExecuteBashRequest {
+            command: "pwd".to_string(),
+            cwd: None,
+            timeout: Some(5),
+            abort_on: None,
+        };
+
+        let sandbox = service.set_sandbox_mode(true).await.unwrap().unwrap();
+        assert_eq!(service.sandbox_dir(), Some(sandbox.clone()));
+        assert!(sandbox.is_dir());
+        assert!(!sandbox.starts_with(workspace.path()));
+        let result = service.execute_and_wait(pwd.clone()).await;
+        assert_eq!(result.output.trim(), sandbox.to_string_lossy());
+
+        assert_eq!(service.set_sandbox_mode(false).await.unwrap(), None);
+        assert_eq!(service.sandbox_dir(), None);
+        let result = service.execute_and_wait(pwd).await;
+        assert_eq!(result.output.trim(), workspace.path().to_string_lossy());
+
+        drop(service);
+        assert!(!sandbox.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cwd_applies_to_single_command() {
+        let dir = tempdir().unwrap();
+        let workspace = tempdir().unwrap();
+        fs::create_dir(workspace.path().join("sub")).unwrap();
+        let service = BashEventService::new(
+            dir.path().to_path_buf(),
+            Some(workspace.path().to_path_buf()),
+            None,
+        );
+        let pwd_in = |cwd: Option<&str>| ExecuteBashRequest {
+            command: "pwd".to_string(),
+            cwd: cwd.map(str::to_string),
+            timeout: Some(5),
+            abort_on: None,
+        };
+
+        let result = service.execute_and_wait(pwd_in(Some("sub"))).await;
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(
+            result.output.trim(),
+            workspace.path().join("sub").to_string_lossy()
+        );
+
+        let result = service.execute_and_wait(pwd_in(None)).await;
+        assert_eq!(result.output.trim(), workspace.path().to_string_lossy());
+
+        let result = service.execute_and_wait(pwd_in(Some("missing"))).await;
+        assert_eq!(result.exit_code, Some(-1));
+        assert!(
+            result.output.contains("not an existing directory"),
+            "{}",
+            result.output
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cwd_command_with_trailing_comment() {
+        let dir = tempdir().unwrap();
+        let workspace = tempdir().unwrap();
+        fs::create_dir(workspace.path().join("sub")).unwrap();
+        let service = BashEventService::new(
+            dir.path().to_path_buf(),
+            Some(workspace.path().to_path_buf()),
+            None,
+        );
+
+        let started = Instant::now();
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "pwd # where are we".to_string(),
+                cwd: Some("sub".to_string()),
+                timeout: Some(10),
+                abort_on: None,
+            })
+            .await;
+        assert!(started.elapsed() < Duration::from_secs(8));
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(
+            result.output.trim(),
+            workspace.path().join("sub").to_string_lossy()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_command_interrupts_and_keeps_session() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+        let cmd = service.start_bash_command(ExecuteBashRequest {
+            command: "sleep 30".to_string(),
+            cwd: None,
+            timeout: Some(60),
+            abort_on: None,
+        });
+
+        let mut cancelled = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            if service.cancel_command(cmd.id).unwrap() {
+                cancelled = true;
+                break;
+            }
+        }
+        assert!(cancelled, "command never started running");
+
+        let mut finished = None;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let progress = service.command_progress(cmd.id).unwrap();
+            if !progress.is_running {
+                finished = Some(progress);
+                break;
+            }
+        }
+        let finished = finished.expect("command was not interrupted");
+        assert_eq!(finished.exit_code, Some(130));
+        assert!(!service.cancel_command(cmd.id).unwrap());
+
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "echo ok".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: None,
+            })
+            .await;
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(result.output.trim(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_peek_bash_output_unknown_command() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+        assert!(service.peek_bash_output(Uuid::new_v4(), 10).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_slowest_commands_ranks_by_duration() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+
+        for command in ["echo fast", "sleep 0.5", "echo quick"] {
+            let result = service
+                .execute_and_wait(ExecuteBashRequest {
+                    command: command.to_string(),
+                    cwd: None,
+                    timeout: Some(5),
+                    abort_on: None,
+                })
+                .await;
+            assert_eq!(result.exit_code, Some(0));
+        }
+        // A running command has no duration yet
+        service.start_bash_command(ExecuteBashRequest {
+            command: "sleep 2".to_string(),
+            cwd: None,
+            timeout: Some(5),
+            abort_on: None,
+        });
+
+        let slowest = service.slowest_commands(10);
+        assert_eq!(slowest.len(), 3);
+        assert_eq!(slowest[0].command, "sleep 0.5");
+        assert!(slowest[0].duration_ms >= 500);
+        assert_eq!(slowest[0].exit_code, Some(0));
+        assert!(slowest[1].duration_ms <= slowest[0].duration_ms);
+
+        assert_eq!(service.slowest_commands(1).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeat_guard_suppresses_after_threshold() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None)
+            .with_repeat_guard(2, Duration::from_secs(60));
+
+        let req = ExecuteBashRequest {
+            command: "echo repeated".to_string(),
+            cwd: None,
+            timeout: Some(5),
+            abort_on: None,
+        };
+
+        for _ in 0..2 {
+            let result = service.execute_and_wait(req.clone()).await;
+            assert_eq!(result.exit_code, Some(0));
+            assert!(result.output.contains("repeated"));
+            assert!(!result.output.contains(REPEAT_SUPPRESSED_NOTE));
+        }
+
+        let suppressed = service.execute_and_wait(req.clone()).await;
+        assert_eq!(suppressed.exit_code, Some(0));
+        assert!(suppressed.output.contains("repeated"));
+        assert!(suppressed.output.contains(REPEAT_SUPPRESSED_NOTE));
+
+        // A different command is unaffected
+        let other = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "echo other".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: None,
+            })
+            .await;
+        assert!(!other.output.contains(REPEAT_SUPPRESSED_NOTE));
+    }
+
+    #[tokio::test]
+    async fn test_repeat_guard_disabled_by_default() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+
+        let req = ExecuteBashRequest {
+            command: "echo repeated".to_string(),
+            cwd: None,
+            timeout: Some(5),
+            abort_on: None,
+        };
+        for _ in 0..3 {
+            let result = service.execute_and_wait(req.clone()).await;
+            assert!(!result.output.contains(REPEAT_SUPPRESSED_NOTE));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abort_on_interrupts_command() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+
+        let started = Instant::now();
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "echo start; sleep 0.5; echo 'ERROR: boom'; sleep 10; echo done"
+                    .to_string(),
+                cwd: None,
+                timeout: Some(30),
+                abort_on: Some("^ERROR".to_string()),
+            })
+            .await;
+
+        assert!(started.elapsed() < Duration::from_secs(8));
+        assert!(result.output.contains("ERROR: boom"));
+        assert!(!result.output.contains("done"));
+        assert!(result.output.contains(ABORTED_NOTE));
+        assert_ne!(result.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_abort_on_does_not_run_command() {
+        let dir = tempdir().unwrap();
+        let workdir = dir.path().to_path_buf();
+        let service = BashEventService::new(dir.path().join(".coder_mcp"), Some(workdir), None);
+
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "touch ran".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: Some("(unclosed".to_string()),
+            })
+            .await;
+        assert!(
+            result.output.contains("Invalid abort_on regex '(unclosed'"),
+            "{}",
+            result.output
+        );
+        assert_eq!(result.exit_code, Some(-1));
+        assert!(!dir.path().join("ran").exists());
+    }
+
+    #[tokio::test]
+    async fn test_command_wrapper_keeps_exit_code() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None)
+            .with_command_wrapper("nice -n 10 {cmd}");
+
+        // `nice` without arguments prints the niceness it runs at
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "nice".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: None,
+            })
+            .await;
+        assert_eq!(result.exit_code, Some(0));
+        assert!(result.output.trim().ends_with("10"));
+
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "sh -c 'exit 3'".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: None,
+            })
+            .await;
+        assert_eq!(result.exit_code, Some(3));
+
+        // Every part of a compound command runs under the wrapper
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "cd / && nice | cat; echo done > /dev/null".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: None,
+            })
+            .await;
+        assert_eq!(result.exit_code, Some(0));
+        assert!(result.output.trim().ends_with("10"), "{}", result.output);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_memory_limit_kills_command() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None)
+            .with_resource_limits(ResourceLimits {
+                memory_kb: Some(64 * 1024),
+                cpu_secs: Some(10),
+            });
+
+        // Building a 200 MB string in the shell needs more memory than the limit allows
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "x=$(head -c 200000000 /dev/zero | tr '\\0' a); echo done".to_string(),
+                cwd: None,
+                timeout: Some(10),
+                abort_on: None,
+            })
+            .await;
+        assert_ne!(result.exit_code, Some(0), "{}", result.output);
+        assert_ne!(result.exit_code, Some(-1), "{}", result.output);
+        assert!(!result.output.contains("done"));
+
+        // Each command gets the limit, and `exit` only leaves its subshell
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "ulimit -v; exit 4".to_string(),
+                cwd: None,
+                timeout: Some(5),
+                abort_on: None,
+            })
+            .await;
+        assert_eq!(result.exit_code, Some(4));
+        assert!(result.output.contains("65536"), "{}", result.output);
+
+        let started = Instant::now();
+        let result = service
+            .execute_and_wait(ExecuteBashRequest {
+                command: "echo limited # trailing comment".to_string(),
+                cwd: None,
+                timeout: Some(10),
+                abort_on: None,
+            })
+            .await;
+        assert!(started.elapsed() < Duration::from_secs(8));
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(result.output.trim(), "limited");
+    }
+
+    fn command_at(command: &str, timestamp: &str) -> BashEvent {
+        BashEvent::BashCommand(BashCommand {
+            id: Uuid::new_v4(),
+            timestamp: DateTime::parse_from_rfc3339(timestamp).unwrap().to_utc(),
+            command: command.to_string(),
+            cwd: None,
+            timeout: 5,
+            abort_on: None,
+        })
+    }
+
+    fn commands_in_order(service: &BashEventService) -> Vec<String> {
+        service
+            .search_bash_events(None)
+            .items
+            .into_iter()
+            .filter_map(|e| match e {
+                BashEvent::BashCommand(c) => Some(c.command),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_events_ordered_by_epoch_millis() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+
+        // Inserted out of order; whole seconds and fractional seconds mixed
+        service.save_event(&command_at("third", "2024-01-01T00:00:01Z"));
+        service.save_event(&command_at("first", "2024-01-01T00:00:00Z"));
+        service.save_event(&command_at("second", "2024-01-01T00:00:00.900Z"));
+        // Same millisecond keeps insertion order
+        service.save_event(&command_at("fourth", "2024-01-01T00:00:01Z"));
+
+        assert_eq!(
+            commands_in_order(&service),
+            vec!["first", "second", "third", "fourth"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_bash_events_pages_with_cursor() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+        for (command, timestamp) in [
+            ("a", "2024-01-01T00:00:00Z"),
+            ("b", "2024-01-01T00:00:01Z"),
+            ("c", "2024-01-01T00:00:01Z"),
+            ("d", "2024-01-01T00:00:02Z"),
+            ("e", "2024-01-01T00:00:03Z"),
+        ] {
+            service.save_event(&command_at(command, timestamp));
+        }
+
+        let mut query = BashEventQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut pages = Vec::new();
+        loop {
+            let page = service.search_bash_events_page(&query).unwrap();
+            pages.push(
+                page.items
+                    .iter()
+                    .filter_map(|e| match e {
+                        BashEvent::BashCommand(c) => Some(c.command.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            match page.next_page_id {
+                Some(cursor) => query.after = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+
+        query.after = Some("not-a-cursor".to_string());
+        assert!(service.search_bash_events_page(&query).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_keeps_recent_ones() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+        for (command, timestamp) in [
+            ("old", "2024-01-01T00:00:00Z"),
+            ("older", "2023-06-01T00:00:00+02:00"),
+            ("recent", "2024-03-01T00:00:00Z"),
+            ("newest", "2024-03-02T00:00:00Z"),
+            ("latest", "2024-03-03T00:00:00Z"),
+        ] {
+            service.save_event(&command_at(command, timestamp));
+        }
+
+        let cutoff = DateTime::parse_from_rfc3339("2024-02-01T00:00:00Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(service.prune_events(cutoff).unwrap(), 2);
+        assert_eq!(
+            commands_in_order(&service),
+            vec!["recent", "newest", "latest"]
+        );
+
+        assert_eq!(service.prune_keep_last(2).unwrap(), 1);
+        assert_eq!(commands_in_order(&service), vec!["newest", "latest"]);
+        assert_eq!(service.prune_keep_last(5).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writers_and_readers() {
+        let dir = tempdir().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let service = service.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let timestamp = format!("2024-01-01T00:{:02}:{:02}Z", w, i);
+                        service.save_event(&command_at(&format!("{}-{}", w, i), &timestamp));
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let service = service.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        let count = service.search_bash_events(None).items.len();
+                        assert!(count <= 100);
+                    }
+                })
+            })
+            .collect();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        let commands = commands_in_order(&service);
+        assert_eq!(commands.len(), 100);
+        assert_eq!(commands[0], "0-0");
+        assert_eq!(commands[99], "3-24");
+    }
+
+    #[tokio::test]
+    async fn test_legacy_rows_are_backfilled_with_epoch_millis() {
+        let dir = tempdir().unwrap();
+        {
+            // Schema from before the timestamp_ms column, with non-UTC offsets that sort
+            // wrongly as text
+            let conn = Connection::open(dir.path().join("bash_events.db")).unwrap();
+            conn.execute(
+                "CREATE TABLE bash_events (id TEXT PRIMARY KEY, timestamp TEXT NOT NULL, command_id TEXT NOT NULL, event_type TEXT NOT NULL, json_data TEXT NOT NULL)",
+                [],
+            )
+            .unwrap();
+            for (command, timestamp) in [
+                ("later", "2024-01-01T08:00:00+00:00"),
+                ("earlier", "2024-01-01T09:00:00+09:00"),
+            ] {
+                let event = command_at(command, timestamp);
+                let id = match &event {
+                    BashEvent::BashCommand(c) => c.id.simple().to_string(),
+                    _ => unreachable!(),
+                };
+                conn.execute(
+                    "INSERT INTO bash_events VALUES (?1, ?2, ?1, 'BashCommand', ?3)",
+                    params![id, timestamp, serde_json::to_string(&event).unwrap()],
+                )
+                .unwrap();
+            }
+        }
+
+        let service = BashEventService::new(dir.path().to_path_buf(), None, None);
+        assert_eq!(commands_in_order(&service), vec!["earlier", "later"]);
+    }
 }
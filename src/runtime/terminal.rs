@@ -1,7 +1,10 @@
+use crate::models::{EnvChange, EnvDiff};
 use anyhow::Result;
 use portable_pty::{Child, CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -9,18 +12,124 @@ use std::time::{Duration, Instant};
 
 const INIT_MARKER: &str = ">>INIT_DONE<<";
 const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// Overrides HANDSHAKE_TIMEOUT, for slow machines or heavy rc files
+const HANDSHAKE_TIMEOUT_ENV: &str = "CODER_MCP_HANDSHAKE_TIMEOUT_MS";
 const OSC_CMD_FINISHED_PREFIX: &str = "\x1b]133;D;";
 const OSC_PROMPT_START: &str = "\x1b]133;A\x07";
+// Minimum interval between partial output flushes in `execute_streaming`
+const STREAM_FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+// How long `interrupt` waits for the shell to return to its prompt
+const INTERRUPT_GRACE: Duration = Duration::from_secs(2);
+// Variables that change as a matter of course and are left out of `env_diff`
+const VOLATILE_ENV_VARS: &[&str] = &["_", "PWD", "OLDPWD", "SHLVL"];
+// ETX, the byte the terminal sends for Ctrl-C
+const ETX: &[u8] = b"\x03";
+
+type SharedWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Sends Ctrl-C to a session from another thread, e.g. while `execute` is blocked on a
+/// command. The interrupted `execute` returns once the shell is back at its prompt.
+#[derive(Clone)]
+pub struct Interrupter {
+    writer: SharedWriter,
+}
+
+impl Interrupter {
+    pub fn interrupt(&self) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(ETX)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Shell dialects, which differ in how the OSC 133 prompt markers are emitted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ShellKind {
+    Bash,
+    Zsh,
+    /// sh and its descendants, which expand parameters in PS1 as POSIX requires
+    Posix,
+}
+
+impl ShellKind {
+    /// Shells such as fish or csh cannot emit the prompt markers and are not supported.
+    fn detect(shell: &str) -> Option<Self> {
+        match Path::new(shell).file_name().and_then(|name| name.to_str()) {
+            Some("bash") => Some(ShellKind::Bash),
+            Some("zsh") => Some(ShellKind::Zsh),
+            Some("sh" | "dash" | "ash" | "ksh" | "mksh") => Some(ShellKind::Posix),
+            _ => None,
+        }
+    }
+
+    /// Lines written to a fresh session. Each prompt must print `OSC 133;D;<exit code>`
+    /// followed by `OSC 133;A`. The continuation prompt is emptied so that multi-line commands
+    /// don't echo it into their output.
+    fn setup_commands(self) -> &'static [&'static str] {
+        match self {
+            // PROMPT_COMMAND='printf "\033]133;D;%s\007" $?'
+            // PS1='\[\033]133;A\007\]'
+            ShellKind::Bash => &[
+                "stty -echo",
+                "bind 'set enable-bracketed-paste off'",
+                "export PROMPT_COMMAND='printf \"\\033]133;D;%s\\007\" $?'",
+                "export PS1='\\[\\033]133;A\\007\\]'",
+                "PS2=''",
+            ],
+            ShellKind::Zsh => &[
+                "stty -echo",
+                "unset zle_bracketed_paste",
+                "unsetopt PROMPT_SP",
+                "precmd() { printf '\\033]133;D;%s\\007' $? }",
+                "PS1=$'%{\\033]133;A\\007%}'",
+                "PS2=''",
+            ],
+            // The literal `$?` in PS1 is expanded each time the prompt is shown
+            ShellKind::Posix => &[
+                "stty -echo",
+                "PS1=\"$(printf '\\033]133;D;%s\\007\\033]133;A\\007' '$?')\"",
+                "PS2=''",
+            ],
+        }
+    }
+}
+
+/// The shell's working directory and exported variables at one point in time.
+#[derive(Clone, Debug)]
+struct ShellState {
+    cwd: String,
+    env: BTreeMap<String, String>,
+}
+
+impl ShellState {
+    /// Parses the output of `pwd; env -0`.
+    fn parse(output: &str) -> Self {
+        let (cwd, env) = output.split_once('\n').unwrap_or((output, ""));
+        let env = env
+            .split('\0')
+            .filter_map(|entry| entry.split_once('='))
+            .filter(|(name, _)| !VOLATILE_ENV_VARS.contains(name))
+            .map(|(name, value)| (name.to_string(), value.replace("\r\n", "\n")))
+            .collect();
+        Self {
+            cwd: cwd.trim_end_matches('\r').to_string(),
+            env,
+        }
+    }
+}
 
 /// Mimics the Agent's view of a terminal session
 pub struct TerminalSession {
-    writer: Box<dyn Write + Send>,
+    writer: SharedWriter,
     // The shared buffer contains output since last read
     output_buffer: Arc<Mutex<String>>,
     // Keep child process to kill it on drop
     child: Box<dyn Child + Send>,
     // Status of the background reader
     is_alive: Arc<AtomicBool>,
+    // Environment and directory right after initialization, for `env_diff`
+    initial_state: Option<ShellState>,
 }
 
 impl Drop for TerminalSession {
@@ -30,7 +139,37 @@ impl Drop for TerminalSession {
 }
 
 impl TerminalSession {
-    pub fn new(workdir: Option<PathBuf>) -> Result<Self> {
+    /// Starts `shell` in `workdir`. Without an explicit shell, `$SHELL` is used, then `bash`;
+    /// an unsupported shell such as fish also falls back to `bash`.
+    /// The shell gets `CODER_MCP_HANDSHAKE_TIMEOUT_MS` (default 5000) to come up.
+    pub fn new(workdir: Option<PathBuf>, shell: Option<String>) -> Result<Self> {
+        let handshake_timeout = std::env::var(HANDSHAKE_TIMEOUT_ENV)
+            .ok()
+            .and_then(|ms| ms.trim().parse().ok())
+            .map_or(HANDSHAKE_TIMEOUT, Duration::from_millis);
+        Self::with_handshake_timeout(workdir, shell, handshake_timeout)
+    }
+
+    /// Like `new`, but fails if the shell is not ready within `handshake_timeout`.
+    pub fn with_handshake_timeout(
+        workdir: Option<PathBuf>,
+        shell: Option<String>,
+        handshake_timeout: Duration,
+    ) -> Result<Self> {
+        let requested = shell
+            .or_else(|| std::env::var("SHELL").ok())
+            .filter(|s| !s.trim().is_empty());
+        let (shell, kind) = match requested {
+            Some(shell) => match ShellKind::detect(&shell) {
+                Some(kind) => (shell, kind),
+                None => {
+                    tracing::warn!("Unsupported shell {}, using bash instead", shell);
+                    ("bash".to_string(), ShellKind::Bash)
+                }
+            },
+            None => ("bash".to_string(), ShellKind::Bash),
+        };
+
         let pty_system = NativePtySystem::default();
         let pair = pty_system.openpty(PtySize {
             rows: 24,
@@ -39,7 +178,7 @@ impl TerminalSession {
             pixel_height: 0,
         })?;
 
-        let mut cmd = CommandBuilder::new("bash");
+        let mut cmd = CommandBuilder::new(&shell);
 
         // We set CWD here. We do NOT set PS1/PROMPT_COMMAND here because .bashrc
         // will likely override them. We set them via the writer below.
@@ -80,24 +219,11 @@ impl TerminalSession {
             }
         });
 
-        // Initialize shell
-        // 1. Disable echo to avoid double output
-        writeln!(writer, "stty -echo")?;
-        // 2. Disable bracketed paste
-        writeln!(writer, "bind 'set enable-bracketed-paste off'")?;
-
-        // 3. Configure OSC 133 Semantic Prompts
-        // We do this here ensures it overrides .bashrc
-        // D;<code>: Command finished with exit code
-        // A: Prompt start
-        // Note: We need careful escaping for the printf string inside the export.
-        // PROMPT_COMMAND='printf "\033]133;D;%s\007" $?'
-        // PS1='\[\033]133;A\007\]'
-        writeln!(
-            writer,
-            "export PROMPT_COMMAND='printf \"\\033]133;D;%s\\007\" $?'"
-        )?;
-        writeln!(writer, "export PS1='\\[\\033]133;A\\007\\]'")?;
+        // Initialize shell: disable echo to avoid double output, disable bracketed paste and
+        // configure OSC 133 semantic prompts. This is done here so it overrides rc files.
+        for line in kind.setup_commands() {
+            writeln!(writer, "{}", line)?;
+        }
 
         // 4. Handshake
         // We use a specific marker output that won't be confused with the command echo.
@@ -108,16 +234,25 @@ impl TerminalSession {
         // Wait for handshake
         let start = Instant::now();
         loop {
-            if start.elapsed() > HANDSHAKE_TIMEOUT {
+            if start.elapsed() > handshake_timeout {
                 let locked = output_buffer.lock().unwrap();
-                let content_sample = if locked.len() > 200 {
-                    &locked[locked.len() - 200..]
+                let mut sample_start = locked.len().saturating_sub(200);
+                while !locked.is_char_boundary(sample_start) {
+                    sample_start += 1;
+                }
+                let diagnosis = if locked.contains(INIT_MARKER) {
+                    "the handshake marker was printed, but no prompt with OSC 133 markers followed; \
+                     the shell's rc files may be overriding the prompt configuration"
                 } else {
-                    &locked
+                    "the shell never printed the handshake marker; it may still be running its rc \
+                     files or not be reading commands"
                 };
                 return Err(anyhow::anyhow!(
-                    "Failed to initialize terminal: timeout waiting for handshake. Buffer (last 200 chars): {:?}",
-                    content_sample
+                    "Failed to initialize terminal: handshake timed out after {}ms ({}; set {} to wait longer). Buffer (last 200 chars): {:?}",
+                    handshake_timeout.as_millis(),
+                    diagnosis,
+                    HANDSHAKE_TIMEOUT_ENV,
+                    &locked[sample_start..]
                 ));
             }
             if !is_alive.load(Ordering::Relaxed) {
@@ -142,29 +277,68 @@ impl TerminalSession {
             thread::sleep(Duration::from_millis(10));
         }
 
-        Ok(Self {
-            writer,
+        let mut session = Self {
+            writer: Arc::new(Mutex::new(writer)),
             output_buffer,
             child,
             is_alive,
-        })
+            initial_state: None,
+        };
+        // Without it only env_diff is unavailable, so a failure here is not fatal
+        session.initial_state = session.capture_state().ok();
+        Ok(session)
     }
 
+    /// Runs a command and returns its output and exit code. An exit code of -1 means the
+    /// command timed out and was terminated.
     pub fn execute(&mut self, cmd: &str, timeout_ms: u64) -> Result<(String, i32)> {
+        let mut output = String::new();
+        let (rest, exit_code) = self.execute_streaming(cmd, timeout_ms, |chunk| {
+            output.push_str(chunk);
+            ControlFlow::Continue(())
+        })?;
+        output.push_str(&rest);
+        Ok((output.trim_end().to_string(), exit_code))
+    }
+
+    /// Runs a command like `execute`, but hands complete lines of output to `on_output`
+    /// while the command is still running. The returned string holds only the output
+    /// that was not already passed to the callback.
+    ///
+    /// If `on_output` returns `ControlFlow::Break`, the command is interrupted with Ctrl-C
+    /// and no further output is streamed; the remaining output is returned once the shell
+    /// is back at its prompt.
+    pub fn execute_streaming<F>(
+        &mut self,
+        cmd: &str,
+        timeout_ms: u64,
+        mut on_output: F,
+    ) -> Result<(String, i32)>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
         // Check health
         if !self.is_alive.load(Ordering::Relaxed) {
             return Err(anyhow::anyhow!("Terminal session is dead"));
         }
 
+        // Discard anything printed since the last command finished, such as a prompt redrawn
+        // after a late Ctrl-C, so it isn't mistaken for this command's finish marker
+        self.drain_output();
+
         // Just write the command. bash will handle the rest via PROMPT_COMMAND.
-        writeln!(self.writer, "{}", cmd)?;
+        writeln!(self.writer.lock().unwrap(), "{}", cmd)?;
 
         let start = Instant::now();
         let duration = Duration::from_millis(timeout_ms);
+        let mut last_flush = Instant::now();
+        let mut interrupted = false;
 
         loop {
             if start.elapsed() > duration {
-                return Ok((self.drain_output(), -1));
+                let output = self.drain_output();
+                self.terminate_after_timeout();
+                return Ok((output, -1));
             }
             if !self.is_alive.load(Ordering::Relaxed) {
                 return Err(anyhow::anyhow!(
@@ -173,11 +347,25 @@ impl TerminalSession {
             }
 
             {
-                let locked = self.output_buffer.lock().unwrap();
+                let mut locked = self.output_buffer.lock().unwrap();
                 // Look for OSC 133;D;<code>\x07
                 if locked.contains(OSC_CMD_FINISHED_PREFIX) {
                     break;
                 }
+
+                // Flush complete lines only, so a partially received marker is never
+                // handed out as output.
+                if !interrupted
+                    && last_flush.elapsed() >= STREAM_FLUSH_INTERVAL
+                    && let Some(pos) = locked.rfind('\n')
+                {
+                    let chunk: String = locked.drain(..=pos).collect();
+                    last_flush = Instant::now();
+                    if on_output(&chunk).is_break() {
+                        self.interrupter().interrupt()?;
+                        interrupted = true;
+                    }
+                }
             }
 
             thread::sleep(Duration::from_millis(10));
@@ -205,6 +393,101 @@ impl TerminalSession {
         Ok((output, -1))
     }
 
+    fn capture_state(&mut self) -> Result<ShellState> {
+        let (output, exit_code) = self.execute("pwd; env -0", 5000)?;
+        if exit_code != 0 {
+            return Err(anyhow::anyhow!(
+                "Failed to read the shell environment: {}",
+                output
+            ));
+        }
+        Ok(ShellState::parse(&output))
+    }
+
+    /// Compares the current environment and working directory with those the session
+    /// started with.
+    pub fn env_diff(&mut self) -> Result<EnvDiff> {
+        let initial = self
+            .initial_state
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Initial shell state was not captured"))?;
+        let current = self.capture_state()?;
+
+        let mut diff = EnvDiff {
+            cwd_changed: current.cwd != initial.cwd,
+            initial_cwd: initial.cwd,
+            cwd: current.cwd,
+            ..Default::default()
+        };
+        for (name, value) in &current.env {
+            match initial.env.get(name) {
+                None => {
+                    diff.added.insert(name.clone(), value.clone());
+                }
+                Some(before) if before != value => {
+                    diff.changed.insert(
+                        name.clone(),
+                        EnvChange {
+                            before: before.clone(),
+                            after: value.clone(),
+                        },
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = initial
+            .env
+            .keys()
+            .filter(|name| !current.env.contains_key(*name))
+            .cloned()
+            .collect();
+        Ok(diff)
+    }
+
+    /// A handle that can interrupt this session's running command from another thread.
+    pub fn interrupter(&self) -> Interrupter {
+        Interrupter {
+            writer: self.writer.clone(),
+        }
+    }
+
+    /// Sends Ctrl-C to whatever the shell is running, e.g. a command left behind by a timed-out
+    /// `execute`, then waits for the prompt and discards the output so the next command starts
+    /// clean.
+    pub fn interrupt(&mut self) -> Result<()> {
+        self.interrupter().interrupt()?;
+        let start = Instant::now();
+        while start.elapsed() < INTERRUPT_GRACE {
+            if self
+                .output_buffer
+                .lock()
+                .unwrap()
+                .contains(OSC_CMD_FINISHED_PREFIX)
+            {
+                // Let the prompt that follows the marker arrive before discarding it
+                thread::sleep(Duration::from_millis(50));
+                self.drain_output();
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Err(anyhow::anyhow!(
+            "Shell did not return to its prompt after Ctrl-C"
+        ))
+    }
+
+    /// Interrupts a command that ran past its timeout so its later output can't leak into the
+    /// next command. A shell that doesn't return to its prompt is killed, leaving the session
+    /// dead rather than out of sync.
+    fn terminate_after_timeout(&mut self) {
+        if let Err(e) = self.interrupt() {
+            tracing::error!("Killing terminal session after timeout: {}", e);
+            let _ = self.child.kill();
+            self.is_alive.store(false, Ordering::Relaxed);
+        }
+    }
+
     fn drain_output(&mut self) -> String {
         let mut locked = self.output_buffer.lock().unwrap();
         let current_content = locked.clone();
@@ -219,15 +502,37 @@ mod tests {
 
     #[test]
     fn test_execute_simple_command() {
-        let mut session = TerminalSession::new(None).unwrap();
+        let mut session = TerminalSession::new(None, None).unwrap();
         let (output, exit_code) = session.execute("echo hello", 1000).unwrap();
         assert_eq!(exit_code, 0);
         assert!(output.contains("hello"));
     }
 
+    #[test]
+    fn test_unsupported_shell_falls_back_to_bash() {
+        assert_eq!(ShellKind::detect("/usr/bin/dash"), Some(ShellKind::Posix));
+        assert_eq!(ShellKind::detect("/usr/bin/fish"), None);
+
+        let mut session = TerminalSession::new(None, Some("/usr/bin/fish".to_string())).unwrap();
+        let (output, exit_code) = session.execute("echo $BASH_VERSION", 1000).unwrap();
+        assert_eq!(exit_code, 0);
+        assert!(!output.trim().is_empty());
+    }
+
+    #[test]
+    fn test_handshake_timeout_is_descriptive() {
+        let Err(e) = TerminalSession::with_handshake_timeout(None, None, Duration::ZERO) else {
+            panic!("a zero handshake timeout cannot succeed");
+        };
+        let message = e.to_string();
+        assert!(message.contains("timed out after 0ms"), "{}", message);
+        assert!(message.contains("handshake marker"), "{}", message);
+        assert!(message.contains(HANDSHAKE_TIMEOUT_ENV), "{}", message);
+    }
+
     #[test]
     fn test_execute_state_persistence() {
-        let mut session = TerminalSession::new(None).unwrap();
+        let mut session = TerminalSession::new(None, None).unwrap();
         session.execute("export MY_VAR=123", 1000).unwrap();
 
         let (output, exit_code) = session.execute("echo $MY_VAR", 1000).unwrap();
@@ -237,7 +542,7 @@ mod tests {
 
     #[test]
     fn test_execute_directory_persistence() {
-        let mut session = TerminalSession::new(None).unwrap();
+        let mut session = TerminalSession::new(None, None).unwrap();
         session.execute("mkdir -p /tmp/test_dir", 1000).unwrap();
         session.execute("cd /tmp/test_dir", 1000).unwrap();
 
@@ -248,14 +553,14 @@ mod tests {
 
     #[test]
     fn test_execute_timeout() {
-        let mut session = TerminalSession::new(None).unwrap();
+        let mut session = TerminalSession::new(None, None).unwrap();
         let (_output, exit_code) = session.execute("sleep 2", 500).unwrap();
         assert_eq!(exit_code, -1);
     }
 
     #[test]
     fn test_execute_exit_code() {
-        let mut session = TerminalSession::new(None).unwrap();
+        let mut session = TerminalSession::new(None, None).unwrap();
 
         let (_output, exit_code) = session.execute("false", 1000).unwrap();
         assert_eq!(exit_code, 1);
@@ -263,7 +568,7 @@ mod tests {
 
     #[test]
     fn test_execute_large_output() {
-        let mut session = TerminalSession::new(None).unwrap();
+        let mut session = TerminalSession::new(None, None).unwrap();
         // seq 1 10000 generates roughly 48KB of text
         let (output, exit_code) = session.execute("seq 1 10000", 5000).unwrap();
         assert_eq!(exit_code, 0);
@@ -279,7 +584,7 @@ mod tests {
         let mut handles = vec![];
         for i in 0..5 {
             handles.push(thread::spawn(move || {
-                let mut session = TerminalSession::new(None).unwrap();
+                let mut session = TerminalSession::new(None, None).unwrap();
                 let (output, exit_code) = session
                     .execute(&format!("echo thread {}", i), 1000)
                     .unwrap();
@@ -293,9 +598,103 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_execute_streaming_partial_output() {
+        let mut session = TerminalSession::new(None, None).unwrap();
+        let mut chunks = Vec::new();
+        let (rest, exit_code) = session
+            .execute_streaming("echo first; sleep 1; echo second", 5000, |chunk| {
+                chunks.push(chunk.to_string());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(exit_code, 0);
+        assert!(chunks.first().unwrap().contains("first"));
+        assert!(!chunks.first().unwrap().contains("second"));
+        assert!(chunks.concat().contains("second") || rest.contains("second"));
+    }
+
+    #[test]
+    fn test_execute_with_posix_sh() {
+        let mut session = TerminalSession::new(None, Some("sh".to_string())).unwrap();
+        let (output, exit_code) = session.execute("echo hello from sh", 1000).unwrap();
+        assert_eq!(exit_code, 0);
+        assert_eq!(output, "hello from sh");
+
+        let (_output, exit_code) = session.execute("sh -c 'exit 4'", 1000).unwrap();
+        assert_eq!(exit_code, 4);
+    }
+
+    #[test]
+    fn test_env_diff_reports_exports_and_cd() {
+        let mut session = TerminalSession::new(None, None).unwrap();
+        let diff = session.env_diff().unwrap();
+        assert!(diff.added.is_empty(), "{:?}", diff.added);
+        assert!(!diff.cwd_changed);
+
+        session
+            .execute("export CODER_MCP_TEST_VAR=hello; unset HOME; cd /", 1000)
+            .unwrap();
+        let diff = session.env_diff().unwrap();
+        assert_eq!(diff.added["CODER_MCP_TEST_VAR"], "hello");
+        assert_eq!(diff.removed, vec!["HOME".to_string()]);
+        assert!(diff.cwd_changed);
+        assert_eq!(diff.cwd, "/");
+        assert_ne!(diff.initial_cwd, "/");
+    }
+
+    #[test]
+    fn test_interrupt_running_command() {
+        let mut session = TerminalSession::new(None, None).unwrap();
+        let interrupter = session.interrupter();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(500));
+            interrupter.interrupt().unwrap();
+        });
+
+        let start = Instant::now();
+        let (_output, exit_code) = session.execute("sleep 30", 10000).unwrap();
+        handle.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(exit_code, 130);
+
+        let (output, exit_code) = session.execute("echo ok", 1000).unwrap();
+        assert_eq!(exit_code, 0);
+        assert_eq!(output, "ok");
+    }
+
+    #[test]
+    fn test_interrupt_after_timeout() {
+        let mut session = TerminalSession::new(None, None).unwrap();
+        let (_output, exit_code) = session.execute("sleep 30", 300).unwrap();
+        assert_eq!(exit_code, -1);
+
+        // Interrupting an idle session is harmless
+        session.interrupt().unwrap();
+        let (output, exit_code) = session.execute("echo ok", 1000).unwrap();
+        assert_eq!(exit_code, 0);
+        assert_eq!(output, "ok");
+    }
+
+    #[test]
+    fn test_timeout_terminates_command() {
+        let mut session = TerminalSession::new(None, None).unwrap();
+        let (_output, exit_code) = session.execute("sleep 5; echo late", 500).unwrap();
+        assert_eq!(exit_code, -1);
+
+        let (output, exit_code) = session.execute("echo clean", 1000).unwrap();
+        assert_eq!(exit_code, 0);
+        assert_eq!(output, "clean");
+
+        // The timed-out command never got to print
+        thread::sleep(Duration::from_secs(5));
+        let (output, _) = session.execute("echo again", 1000).unwrap();
+        assert_eq!(output, "again");
+    }
+
     #[test]
     fn test_interrupt_exit_code() {
-        let mut session = TerminalSession::new(None).unwrap();
+        let mut session = TerminalSession::new(None, None).unwrap();
         // sh -c 'kill -TERM $$' causes the subshell to die with signal 15 (TERM).
         // Bash reports this as 128 + 15 = 143.
         let (_output, exit_code) = session.execute("sh -c 'kill -TERM $$'", 1000).unwrap();
@@ -1,41 +1,216 @@
+use crate::audit::AuditLog;
+use crate::auth::{self, CapabilityTokens};
 use crate::logger;
-use crate::runtime::bash::BashEventService;
-use crate::service::CoderMcpService;
+use crate::runtime::bash::{BashEventService, ResourceLimits};
+use crate::service::{CoderMcpReadOnlyService, CoderMcpService};
+use crate::session_manager::IdleSessionManager;
+use crate::tools::fetch::FetchConfig;
 use crate::tools::file_tools::{run_tree, TreeArgs};
-use axum::{extract::Query, Router};
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use axum::{extract::Query, Json, Router};
 use rmcp::transport::{
-    StreamableHttpServerConfig,
-    streamable_http_server::{session::local::LocalSessionManager, tower::StreamableHttpService},
+    streamable_http_server::tower::StreamableHttpService, StreamableHttpServerConfig,
 };
+use serde::Deserialize;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
-pub async fn run_server(
-    workspace_path: PathBuf,
-    port: u16,
-    shutdown_rx: tokio::sync::oneshot::Receiver<()>,
-) {
-    // Set up tracing using the local logger
-    logger::init_logging();
+const DEFAULT_READONLY_PATH: &str = "/mcp-readonly";
+const DEFAULT_SESSION_IDLE_SECS: u64 = 1800;
+const DEFAULT_HISTORY_MAX_ENTRIES: usize = 1000;
+pub const DEFAULT_PORT: u16 = 3000;
 
-    let cwd = std::env::current_dir().unwrap();
-    let bash_service = BashEventService::new(cwd.join(".coder_mcp"), Some(workspace_path.clone()));
+/// Reads and parses an optional environment variable, ignoring unparsable values.
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+}
+
+/// Parses the port the server listens on, as given in `CODER_MCP_PORT` or `PORT`.
+pub fn parse_port(value: &str) -> Result<u16, String> {
+    match value.trim().parse::<u16>() {
+        Ok(0) | Err(_) => Err(format!(
+            "Invalid port '{}': expected a number from 1 to 65535",
+            value
+        )),
+        Ok(port) => Ok(port),
+    }
+}
+
+/// The time before which bash events are pruned when keeping the last `days` days of them.
+pub fn retention_cutoff(days: i64) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    Some(days)
+        .filter(|days| *days > 0)
+        .and_then(chrono::Duration::try_days)
+        .and_then(|retention| chrono::Utc::now().checked_sub_signed(retention))
+        .ok_or_else(|| {
+            format!(
+                "Invalid event retention of {} days: expected a positive number of days",
+                days
+            )
+        })
+}
+
+/// Splits a comma-separated environment variable, dropping empty items.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Lets browsers on `origins` call the server; `*` allows any origin.
+pub fn cors_layer(origins: &[String]) -> Result<CorsLayer, String> {
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|_| format!("invalid CORS origin '{}'", origin))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+    let session_id = HeaderName::from_static("mcp-session-id");
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([
+            header::ACCEPT,
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            session_id.clone(),
+            HeaderName::from_static("mcp-protocol-version"),
+            HeaderName::from_static("last-event-id"),
+        ])
+        .expose_headers([session_id]))
+}
+
+/// What `run_server` serves and where. The optional settings let embedding callers such as
+/// `CServer.start` avoid process-wide environment variables; unset ones fall back to the
+/// matching variable.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Directory the tools operate on
+    #[serde(skip)]
+    pub workspace_path: PathBuf,
+    /// Port to listen on; 0 picks a free one
+    #[serde(skip)]
+    pub port: u16,
+    /// Overrides `CODER_MCP_SHELL`
+    pub shell: Option<String>,
+    /// Overrides `CODER_MCP_EVENT_RETENTION_DAYS`
+    pub event_retention_days: Option<i64>,
+    /// Overrides `CODER_MCP_COMMAND_WRAPPER`, the template each bash command runs under
+    pub command_wrapper: Option<String>,
+    /// Overrides `CODER_MCP_AUTH_TOKEN`
+    pub auth_token: Option<String>,
+    /// Overrides `CODER_MCP_CORS_ORIGINS`
+    pub cors_origins: Option<Vec<String>>,
+}
+
+impl ServerConfig {
+    /// Creates the workspace directory if it is missing and returns its absolute path.
+    pub fn prepare_workspace(&self) -> Result<PathBuf, String> {
+        let path = &self.workspace_path;
+        std::fs::create_dir_all(path)
+            .and_then(|_| std::path::absolute(path))
+            .map_err(|e| format!("Failed to create workspace {}: {}", path.display(), e))
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            workspace_path: PathBuf::from("workspace"),
+            port: DEFAULT_PORT,
+            shell: None,
+            event_retention_days: None,
+            command_wrapper: None,
+            auth_token: None,
+            cors_origins: None,
+        }
+    }
+}
+
+/// Where and how the read-only service is served.
+#[derive(Clone, Debug)]
+pub struct ReadOnlyMount {
+    pub path: String,
+    /// Return structured JSON content instead of text
+    pub structured: bool,
+}
+
+/// Creates a session manager and starts reaping its idle sessions.
+fn session_manager(session_idle: Option<Duration>) -> Arc<IdleSessionManager> {
+    let manager = Arc::new(IdleSessionManager::new(session_idle));
+    manager.spawn_reaper();
+    manager
+}
+
+/// Builds the HTTP routes. `/health` reports the server version, the seconds since the router
+/// was built, and the workspace. The read-only service is only mounted when `readonly` is set.
+/// MCP sessions idle for longer than `session_idle` are closed. Tool calls on both services
+/// are recorded in `audit_log` when set. `fetch_url` is only offered when `fetch` is set.
+/// `history_limit` caps the undo versions kept in memory across all files. With
+/// `capability_tokens`, `/mcp` refuses tools that can modify the workspace unless the caller
+/// presents a read-write token. With `auth_token`, every route but `/health` answers 401
+/// unless the caller presents that token or one of the capability tokens.
+#[allow(clippy::too_many_arguments)]
+pub fn build_router(
+    bash_service: BashEventService,
+    workspace_path: PathBuf,
+    readonly: Option<ReadOnlyMount>,
+    session_idle: Option<Duration>,
+    audit_log: Option<Arc<AuditLog>>,
+    fetch: Option<FetchConfig>,
+    history_limit: Option<usize>,
+    capability_tokens: Option<CapabilityTokens>,
+    auth_token: Option<String>,
+) -> Router {
+    let accepted_tokens = auth_token.map(|token| {
+        let mut accepted = vec![token];
+        if let Some(tokens) = &capability_tokens {
+            accepted.extend(tokens.tokens().map(str::to_string));
+        }
+        Arc::new(accepted)
+    });
 
     // Create the MCP service
-    let coder_mcp_service = CoderMcpService::new(bash_service, workspace_path.clone());
+    let mut coder_mcp_service = CoderMcpService::new(bash_service, workspace_path.clone());
+    if let Some(audit_log) = &audit_log {
+        coder_mcp_service = coder_mcp_service.with_audit_log(audit_log.clone());
+    }
+    if let Some(fetch) = fetch {
+        coder_mcp_service = coder_mcp_service.with_fetch_config(fetch);
+    }
+    if let Some(max_entries) = history_limit {
+        coder_mcp_service = coder_mcp_service.with_history_limit(max_entries);
+    }
+    if let Some(tokens) = capability_tokens {
+        coder_mcp_service = coder_mcp_service.with_capability_tokens(tokens);
+    }
 
     // Wrap in StreamableHttpService
-    let mcp_service: StreamableHttpService<CoderMcpService, LocalSessionManager> =
+    let mcp_service: StreamableHttpService<CoderMcpService, IdleSessionManager> =
         StreamableHttpService::new(
             move || Ok(coder_mcp_service.clone()),
-            LocalSessionManager::default().into(),
+            session_manager(session_idle),
             StreamableHttpServerConfig::default(),
         );
 
     // Build our application with routes
+    let started = Instant::now();
     let tree_workspace = workspace_path.clone();
     let app = Router::new()
-        .route("/health", axum::routing::get(|| async { "OK" }))
         .route(
             "/tree",
             axum::routing::get(move |Query(args): Query<TreeArgs>| async move {
@@ -47,10 +222,294 @@ pub async fn run_server(
         )
         .nest_service("/mcp", mcp_service);
 
-    // Run it
+    let app = match readonly {
+        Some(mount) => {
+            let mut readonly_service = CoderMcpReadOnlyService::new(workspace_path)
+                .with_structured_output(mount.structured);
+            if let Some(audit_log) = audit_log {
+                readonly_service = readonly_service.with_audit_log(audit_log);
+            }
+            let readonly_mcp_service: StreamableHttpService<
+                CoderMcpReadOnlyService,
+                IdleSessionManager,
+            > = StreamableHttpService::new(
+                move || Ok(readonly_service.clone()),
+                session_manager(session_idle),
+                StreamableHttpServerConfig::default(),
+            );
+            app.nest_service(&mount.path, readonly_mcp_service)
+        }
+        None => app,
+    };
+    let app = match accepted_tokens {
+        Some(accepted) => app.layer(axum::middleware::from_fn_with_state(
+            accepted,
+            auth::require_bearer,
+        )),
+        None => app,
+    };
+    app.route(
+        "/health",
+        axum::routing::get(move || async move {
+            Json(serde_json::json!({
+                "status": "ok",
+                "version": env!("CARGO_PKG_VERSION"),
+                "uptime_secs": started.elapsed().as_secs(),
+            }))
+        }),
+    )
+}
+
+/// Receives the address the server bound, or why binding failed.
+pub type ReadySender = oneshot::Sender<Result<SocketAddr, String>>;
+
+/// Reports why the server could not start on `ready`. Without `ready` (the command-line
+/// server), the reason is printed and the process exits.
+fn report_startup_error(ready: Option<ReadySender>, message: String) {
+    let Some(ready) = ready else {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    };
+    tracing::error!("{}", message);
+    let _ = ready.send(Err(message));
+}
+
+/// Binds the listener on all interfaces and reports the outcome on `ready`. Without `ready`, a
+/// bind failure exits the process.
+async fn bind_listener(port: u16, ready: Option<ReadySender>) -> Option<TcpListener> {
     let addr = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&addr).await.unwrap();
-    tracing::info!("Listening on {}", listener.local_addr().unwrap());
+    let bound = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener.local_addr().map(|local| (listener, local)),
+        Err(e) => Err(e),
+    };
+    match bound {
+        Ok((listener, local)) => {
+            tracing::info!("Listening on {}", local);
+            if let Some(ready) = ready {
+                let _ = ready.send(Ok(local));
+            }
+            Some(listener)
+        }
+        Err(e) => {
+            report_startup_error(ready, format!("Failed to bind {}: {}", addr, e));
+            None
+        }
+    }
+}
+
+/// Serves until `shutdown_rx` fires. With `ready`, the bound address (or the bind error) is
+/// sent back as soon as the listener is up.
+pub async fn run_server(
+    config: ServerConfig,
+    shutdown_rx: oneshot::Receiver<()>,
+    ready: Option<ReadySender>,
+) {
+    // Set up tracing using the local logger
+    if let Err(message) = logger::init_logging() {
+        report_startup_error(ready, message);
+        return;
+    }
+
+    let workspace_path = match config.prepare_workspace() {
+        Ok(path) => path,
+        Err(message) => {
+            report_startup_error(ready, message);
+            return;
+        }
+    };
+    tracing::info!("Workspace: {}", workspace_path.display());
+
+    // Settings that can be invalid are checked before anything starts, so `ready` gets the reason
+    let audit_log = match std::env::var("CODER_MCP_AUDIT_LOG")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| {
+            let path = PathBuf::from(p.trim());
+            AuditLog::open(&path)
+                .map(Arc::new)
+                .map_err(|e| format!("Failed to open audit log {}: {}", path.display(), e))
+        })
+        .transpose()
+    {
+        Ok(log) => log,
+        Err(message) => {
+            report_startup_error(ready, message);
+            return;
+        }
+    };
+
+    let capability_tokens = match std::env::var("CODER_MCP_CAPABILITY_TOKENS")
+        .ok()
+        .filter(|spec| !spec.trim().is_empty())
+        .map(|spec| CapabilityTokens::parse(&spec))
+        .transpose()
+    {
+        Ok(tokens) => tokens,
+        Err(message) => {
+            report_startup_error(ready, message);
+            return;
+        }
+    };
+
+    // Browser clients are refused unless their origins are listed
+    let cors_origins = config
+        .cors_origins
+        .or_else(|| {
+            std::env::var("CODER_MCP_CORS_ORIGINS")
+                .ok()
+                .map(|origins| split_list(&origins))
+        })
+        .unwrap_or_default();
+    let cors = match Some(cors_origins)
+        .filter(|origins| !origins.is_empty())
+        .map(|origins| cors_layer(&origins))
+        .transpose()
+    {
+        Ok(cors) => cors,
+        Err(message) => {
+            report_startup_error(ready, message);
+            return;
+        }
+    };
+
+    let retention_days = match config.event_retention_days {
+        Some(days) => Some(Ok(days)),
+        None => std::env::var("CODER_MCP_EVENT_RETENTION_DAYS")
+            .ok()
+            .filter(|days| !days.trim().is_empty())
+            .map(|days| {
+                days.trim().parse::<i64>().map_err(|_| {
+                    format!(
+                        "Invalid CODER_MCP_EVENT_RETENTION_DAYS '{}': expected a positive number of days",
+                        days
+                    )
+                })
+            }),
+    };
+    let retention = match retention_days
+        .map(|days| days.and_then(|days| Ok((days, retention_cutoff(days)?))))
+        .transpose()
+    {
+        Ok(retention) => retention,
+        Err(message) => {
+            report_startup_error(ready, message);
+            return;
+        }
+    };
+
+    let cwd = std::env::current_dir().unwrap();
+    let shell = config
+        .shell
+        .or_else(|| std::env::var("CODER_MCP_SHELL").ok())
+        .filter(|s| !s.trim().is_empty());
+    let bash_service = if env_parse::<bool>("CODER_MCP_EVENTS_IN_MEMORY").unwrap_or(false) {
+        BashEventService::try_new_in_memory(Some(workspace_path.clone()), shell)
+    } else {
+        BashEventService::try_new(cwd.join(".coder_mcp"), Some(workspace_path.clone()), shell)
+    };
+    let mut bash_service = match bash_service {
+        Ok(service) => service,
+        Err(e) => {
+            report_startup_error(ready, format!("{:#}", e));
+            return;
+        }
+    };
+
+    if let Some((days, cutoff)) = retention {
+        match bash_service.prune_events(cutoff) {
+            Ok(deleted) => {
+                tracing::info!("Pruned {} bash events older than {} days", deleted, days)
+            }
+            Err(e) => tracing::error!("Failed to prune bash events: {}", e),
+        }
+    }
+
+    // Opt-in guard against an agent hammering the same command
+    if let Some(threshold) = env_parse::<usize>("CODER_MCP_REPEAT_THRESHOLD") {
+        let window_secs = env_parse::<u64>("CODER_MCP_REPEAT_WINDOW_SECS").unwrap_or(60);
+        bash_service = bash_service.with_repeat_guard(threshold, Duration::from_secs(window_secs));
+    }
+
+    if let Some(template) = config
+        .command_wrapper
+        .or_else(|| std::env::var("CODER_MCP_COMMAND_WRAPPER").ok())
+        && !template.trim().is_empty()
+    {
+        bash_service = bash_service.with_command_wrapper(template);
+    }
+
+    // Either limit puts every command in its own subshell, so `cd` and `export` stop persisting
+    let limits = ResourceLimits {
+        memory_kb: env_parse::<u64>("CODER_MCP_ULIMIT_MEMORY_KB"),
+        cpu_secs: env_parse::<u64>("CODER_MCP_ULIMIT_CPU_SECS"),
+    };
+    bash_service = bash_service.with_resource_limits(limits);
+
+    // An empty CODER_MCP_READONLY_PATH disables the read-only mount
+    let readonly_path = std::env::var("CODER_MCP_READONLY_PATH")
+        .unwrap_or_else(|_| DEFAULT_READONLY_PATH.to_string());
+    let readonly = Some(readonly_path.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| ReadOnlyMount {
+            path: format!("/{}", p.trim_start_matches('/')),
+            structured: env_parse::<bool>("CODER_MCP_READONLY_STRUCTURED").unwrap_or(false),
+        });
+
+    // CODER_MCP_SESSION_IDLE_SECS=0 keeps sessions until the client deletes them
+    let session_idle =
+        Some(env_parse::<u64>("CODER_MCP_SESSION_IDLE_SECS").unwrap_or(DEFAULT_SESSION_IDLE_SECS))
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+
+    // fetch_url stays disabled unless hosts are allowlisted explicitly
+    let fetch = std::env::var("CODER_MCP_FETCH_ALLOWED_HOSTS")
+        .ok()
+        .map(|hosts| split_list(&hosts))
+        .filter(|hosts| !hosts.is_empty())
+        .map(|hosts| {
+            let mut config = FetchConfig::new(hosts);
+            if let Ok(schemes) = std::env::var("CODER_MCP_FETCH_ALLOWED_SCHEMES") {
+                config.allowed_schemes = split_list(&schemes);
+            }
+            if let Some(max_bytes) = env_parse::<u64>("CODER_MCP_FETCH_MAX_BYTES") {
+                config.max_bytes = max_bytes;
+            }
+            config
+        });
+
+    // CODER_MCP_HISTORY_MAX_ENTRIES=0 keeps every undo version
+    let history_limit = Some(
+        env_parse::<usize>("CODER_MCP_HISTORY_MAX_ENTRIES").unwrap_or(DEFAULT_HISTORY_MAX_ENTRIES),
+    )
+    .filter(|max| *max > 0);
+
+    let auth_token = config
+        .auth_token
+        .or_else(|| std::env::var("CODER_MCP_AUTH_TOKEN").ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty());
+
+    let app = build_router(
+        bash_service,
+        workspace_path,
+        readonly,
+        session_idle,
+        audit_log,
+        fetch,
+        history_limit,
+        capability_tokens,
+        auth_token,
+    );
+
+    let app = match cors {
+        Some(cors) => app.layer(cors),
+        None => app,
+    };
+
+    // Run it
+    let Some(listener) = bind_listener(config.port, ready).await else {
+        return;
+    };
     axum::serve(listener, app)
         .with_graceful_shutdown(async {
             shutdown_rx.await.ok();
@@ -59,3 +518,592 @@ pub async fn run_server(
         .await
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_server_config_defaults() {
+        let config = ServerConfig {
+            port: 8080,
+            auth_token: Some("secret".to_string()),
+            ..ServerConfig::default()
+        };
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.workspace_path, PathBuf::from("workspace"));
+        assert_eq!(config.auth_token.as_deref(), Some("secret"));
+        assert_eq!(config.shell, None);
+        assert_eq!(config.event_retention_days, None);
+        assert_eq!(config.command_wrapper, None);
+        assert_eq!(config.cors_origins, None);
+        assert_eq!(ServerConfig::default().port, DEFAULT_PORT);
+    }
+
+    #[test]
+    fn test_prepare_workspace_creates_missing_directory() {
+        let dir = tempdir().unwrap();
+        let config = ServerConfig {
+            workspace_path: dir.path().join("missing/workspace"),
+            ..ServerConfig::default()
+        };
+        let path = config.prepare_workspace().unwrap();
+        assert!(path.is_absolute());
+        assert!(dir.path().join("missing/workspace").is_dir());
+
+        std::fs::write(dir.path().join("file"), "").unwrap();
+        let config = ServerConfig {
+            workspace_path: dir.path().join("file/workspace"),
+            ..ServerConfig::default()
+        };
+        let err = config.prepare_workspace().unwrap_err();
+        assert!(err.starts_with("Failed to create workspace"), "{}", err);
+    }
+
+    #[test]
+    fn test_server_config_from_json() {
+        let config: ServerConfig = serde_json::from_value(serde_json::json!({
+            "shell": "zsh",
+            "event_retention_days": 7,
+            "command_wrapper": "nice -n 10 {cmd}",
+            "auth_token": "secret",
+            "cors_origins": ["http://localhost:5173"],
+        }))
+        .unwrap();
+        assert_eq!(
+            config,
+            ServerConfig {
+                shell: Some("zsh".to_string()),
+                event_retention_days: Some(7),
+                command_wrapper: Some("nice -n 10 {cmd}".to_string()),
+                auth_token: Some("secret".to_string()),
+                cors_origins: Some(vec!["http://localhost:5173".to_string()]),
+                ..ServerConfig::default()
+            }
+        );
+
+        let partial: ServerConfig =
+            serde_json::from_value(serde_json::json!({ "shell": "bash" })).unwrap();
+        assert_eq!(partial.auth_token, None);
+        assert!(
+            serde_json::from_value::<ServerConfig>(serde_json::json!({ "shel": "bash" })).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_port() {
+        assert_eq!(parse_port("8080"), Ok(8080));
+        assert_eq!(parse_port(" 3001\n"), Ok(3001));
+        for invalid in ["", "0", "65536", "http"] {
+            let err = parse_port(invalid).unwrap_err();
+            assert!(err.contains("expected a number from 1 to 65535"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_retention_cutoff() {
+        let cutoff = retention_cutoff(7).unwrap();
+        assert!(cutoff < chrono::Utc::now() - chrono::Duration::days(6));
+        for invalid in [0, -1, i64::MAX] {
+            let err = retention_cutoff(invalid).unwrap_err();
+            assert!(
+                err.contains("expected a positive number of days"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    async fn spawn_app(app: Router) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_router_without_readonly_mount() {
+        let dir = tempdir().unwrap();
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let base = spawn_app(build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ))
+        .await;
+
+        let client = reqwest::Client::new();
+        let readonly = client
+            .get(format!("{}/mcp-readonly", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(readonly.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let full = client.get(format!("{}/mcp", base)).send().await.unwrap();
+        assert_ne!(full.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_router_with_custom_readonly_mount() {
+        let dir = tempdir().unwrap();
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let mount = ReadOnlyMount {
+            path: "/ro".to_string(),
+            structured: false,
+        };
+        let app = build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            Some(mount),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let base = spawn_app(app).await;
+
+        let client = reqwest::Client::new();
+        let custom = client.get(format!("{}/ro", base)).send().await.unwrap();
+        assert_ne!(custom.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let default = client
+            .get(format!("{}/mcp-readonly", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(default.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    async fn initialize(client: &reqwest::Client, base: &str) -> reqwest::Response {
+        client
+            .post(format!("{}/mcp", base))
+            .header("Accept", "application/json, text/event-stream")
+            .header("Content-Type", "application/json")
+            .body(
+                r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"0"}}}"#,
+            )
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_idle_session_is_reaped() {
+        let dir = tempdir().unwrap();
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let app = build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            None,
+            Some(Duration::from_millis(200)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let base = spawn_app(app).await;
+        let client = reqwest::Client::new();
+
+        let first = initialize(&client, &base).await;
+        assert_eq!(first.status(), reqwest::StatusCode::OK);
+        let session_id = first.headers()["mcp-session-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        first.text().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(600)).await;
+
+        let stale = client
+            .post(format!("{}/mcp", base))
+            .header("Accept", "application/json, text/event-stream")
+            .header("Content-Type", "application/json")
+            .header("mcp-session-id", &session_id)
+            .body(r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stale.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let fresh = initialize(&client, &base).await;
+        assert_eq!(fresh.status(), reqwest::StatusCode::OK);
+        assert_ne!(
+            fresh.headers()["mcp-session-id"].to_str().unwrap(),
+            session_id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_are_audited_without_contents() {
+        use rmcp::model::CallToolRequestParam;
+        use rmcp::transport::StreamableHttpClientTransport;
+        use rmcp::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let audit_path = dir.path().join("audit/calls.jsonl");
+        let audit_log = Arc::new(AuditLog::open(&audit_path).unwrap());
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let app = build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            None,
+            None,
+            Some(audit_log),
+            None,
+            None,
+            None,
+            None,
+        );
+        let base = spawn_app(app).await;
+
+        let client = ()
+            .serve(StreamableHttpClientTransport::from_uri(format!(
+                "{}/mcp",
+                base
+            )))
+            .await
+            .unwrap();
+        let call = |name: &'static str, arguments: serde_json::Value| CallToolRequestParam {
+            name: name.into(),
+            arguments: arguments.as_object().cloned(),
+        };
+        client
+            .call_tool(call(
+                "create_file",
+                serde_json::json!({ "path": "notes.txt", "content": "top secret" }),
+            ))
+            .await
+            .unwrap();
+        client
+            .call_tool(call(
+                "view_file",
+                serde_json::json!({ "path": "notes.txt" }),
+            ))
+            .await
+            .unwrap();
+        client
+            .call_tool(call(
+                "view_file",
+                serde_json::json!({ "path": "missing.txt" }),
+            ))
+            .await
+            .unwrap();
+        client
+            .call_tool(call(
+                "str_replace",
+                serde_json::json!({ "path": "notes.txt", "old_str": "classified", "new_str": "x" }),
+            ))
+            .await
+            .unwrap();
+        client.cancel().await.unwrap();
+
+        let log = std::fs::read_to_string(&audit_path).unwrap();
+        assert!(!log.contains("top secret"));
+        assert!(!log.contains("classified"));
+        let records: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0]["tool"], "create_file");
+        assert_eq!(records[0]["args"]["path"], "notes.txt");
+        assert_eq!(records[0]["args"]["content"], "<redacted 10 bytes>");
+        assert_eq!(records[0]["outcome"], "ok");
+        assert_eq!(records[1]["tool"], "view_file");
+        assert_eq!(records[1]["outcome"], "ok");
+        assert_eq!(records[2]["outcome"], "error");
+        assert!(records[2]["timestamp"].is_string());
+        assert_eq!(records[3]["outcome"], "error");
+        assert!(records[3]["error"]
+            .as_str()
+            .unwrap()
+            .contains("old_str `<redacted>`"));
+    }
+
+    #[tokio::test]
+    async fn test_capability_tokens_limit_write_tools() {
+        use crate::auth::Capability;
+        use rmcp::model::CallToolRequestParam;
+        use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
+        use rmcp::transport::StreamableHttpClientTransport;
+        use rmcp::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "draft\n").unwrap();
+        let tokens = CapabilityTokens::new()
+            .with_token("reader", Capability::ReadOnly)
+            .with_token("writer", Capability::ReadWrite);
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let app = build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(tokens),
+            None,
+        );
+        let base = spawn_app(app).await;
+
+        let call = |name: &'static str, arguments: serde_json::Value| CallToolRequestParam {
+            name: name.into(),
+            arguments: arguments.as_object().cloned(),
+        };
+        let view = || call("view_file", serde_json::json!({ "path": "notes.txt" }));
+        let replace = |old_str: &str, new_str: &str| {
+            call(
+                "str_replace",
+                serde_json::json!({ "path": "notes.txt", "old_str": old_str, "new_str": new_str }),
+            )
+        };
+        let text =
+            |result: rmcp::model::CallToolResult| result.content[0].as_text().unwrap().text.clone();
+
+        for (token, can_write) in [("reader", false), ("writer", true)] {
+            let config = StreamableHttpClientTransportConfig::with_uri(format!("{}/mcp", base))
+                .auth_header(token);
+            let client =
+                ().serve(StreamableHttpClientTransport::from_config(config))
+                    .await
+                    .unwrap();
+
+            let viewed = client.call_tool(view()).await.unwrap();
+            assert!(text(viewed).contains("draft"));
+
+            // Tools outside the read-only service still work when they modify nothing.
+            let version = client
+                .call_tool(call("get_version", serde_json::json!({})))
+                .await
+                .unwrap();
+            assert_ne!(version.is_error, Some(true));
+            assert!(text(version).contains(env!("CARGO_PKG_VERSION")));
+
+            let replaced = client.call_tool(replace("draft", "final")).await.unwrap();
+            if can_write {
+                assert_ne!(replaced.is_error, Some(true));
+                assert!(text(client.call_tool(view()).await.unwrap()).contains("final"));
+            } else {
+                assert_eq!(replaced.is_error, Some(true));
+                assert!(text(replaced).contains("this token is read-only"));
+                assert_eq!(
+                    std::fs::read_to_string(dir.path().join("notes.txt")).unwrap(),
+                    "draft\n"
+                );
+
+                // Everything not known to be read-only is refused before its arguments are read
+                for tool in client.list_all_tools().await.unwrap() {
+                    if crate::service::READ_TOOLS.contains(&tool.name.as_ref()) {
+                        continue;
+                    }
+                    let name = tool.name.to_string();
+                    let refused = client
+                        .call_tool(CallToolRequestParam {
+                            name: tool.name,
+                            arguments: None,
+                        })
+                        .await
+                        .unwrap();
+                    assert_eq!(refused.is_error, Some(true), "{}", name);
+                    assert!(
+                        text(refused).contains("this token is read-only"),
+                        "{}",
+                        name
+                    );
+                }
+            }
+            client.cancel().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_protects_mcp_routes() {
+        let dir = tempdir().unwrap();
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let mount = ReadOnlyMount {
+            path: "/mcp-readonly".to_string(),
+            structured: false,
+        };
+        let app = build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            Some(mount),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some("secret".to_string()),
+        );
+        let base = spawn_app(app).await;
+        let client = reqwest::Client::new();
+
+        let health = client.get(format!("{}/health", base)).send().await.unwrap();
+        assert_eq!(health.status(), reqwest::StatusCode::OK);
+
+        for path in ["/mcp", "/mcp-readonly"] {
+            let missing = client
+                .get(format!("{}{}", base, path))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(missing.status(), reqwest::StatusCode::UNAUTHORIZED);
+            let wrong = client
+                .get(format!("{}{}", base, path))
+                .bearer_auth("guess")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(wrong.status(), reqwest::StatusCode::UNAUTHORIZED);
+            let valid = client
+                .get(format!("{}{}", base, path))
+                .bearer_auth("secret")
+                .send()
+                .await
+                .unwrap();
+            assert_ne!(valid.status(), reqwest::StatusCode::UNAUTHORIZED);
+            assert_ne!(valid.status(), reqwest::StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cors_allows_configured_origin() {
+        let dir = tempdir().unwrap();
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let app = build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let cors = cors_layer(&["http://localhost:5173".to_string()]).unwrap();
+        let base = spawn_app(app.layer(cors)).await;
+        let client = reqwest::Client::new();
+
+        let preflight = client
+            .request(reqwest::Method::OPTIONS, format!("{}/mcp", base))
+            .header("Origin", "http://localhost:5173")
+            .header("Access-Control-Request-Method", "POST")
+            .header(
+                "Access-Control-Request-Headers",
+                "content-type,mcp-session-id",
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            preflight.headers()["access-control-allow-origin"],
+            "http://localhost:5173"
+        );
+
+        let other = client
+            .get(format!("{}/health", base))
+            .header("Origin", "http://evil.example")
+            .send()
+            .await
+            .unwrap();
+        assert!(!other.headers().contains_key("access-control-allow-origin"));
+
+        assert!(cors_layer(&["bad\norigin".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_version_and_uptime() {
+        let dir = tempdir().unwrap();
+        let bash_service = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let app = build_router(
+            bash_service,
+            dir.path().to_path_buf(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let base = spawn_app(app).await;
+
+        let health: serde_json::Value = reqwest::get(format!("{}/health", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health["status"], "ok");
+        assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
+        assert!(health["uptime_secs"].is_u64());
+        // `/health` skips authentication, so it must not reveal where the workspace lives
+        assert!(health.get("workspace").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bind_listener_reports_outcome() {
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let listener = bind_listener(0, Some(ready_tx)).await.unwrap();
+        let addr = ready_rx.await.unwrap().unwrap();
+        assert_eq!(addr, listener.local_addr().unwrap());
+        assert_ne!(addr.port(), 0);
+
+        let (ready_tx, ready_rx) = oneshot::channel();
+        assert!(bind_listener(addr.port(), Some(ready_tx)).await.is_none());
+        let err = ready_rx.await.unwrap().unwrap_err();
+        assert!(
+            err.contains(&format!("Failed to bind 0.0.0.0:{}", addr.port())),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_server_reports_invalid_settings() {
+        let dir = tempdir().unwrap();
+        // A second start in the same process must not trip over the first one's logging setup
+        for _ in 0..2 {
+            let config = ServerConfig {
+                workspace_path: dir.path().to_path_buf(),
+                cors_origins: Some(vec!["http://bad\norigin".to_string()]),
+                ..ServerConfig::default()
+            };
+            let (_shutdown_tx, shutdown_rx) = oneshot::channel();
+            let (ready_tx, ready_rx) = oneshot::channel();
+            run_server(config, shutdown_rx, Some(ready_tx)).await;
+            let err = ready_rx.await.unwrap().unwrap_err();
+            assert_eq!(err, "invalid CORS origin 'http://bad\norigin'");
+        }
+
+        let config = ServerConfig {
+            workspace_path: dir.path().to_path_buf(),
+            event_retention_days: Some(-1),
+            ..ServerConfig::default()
+        };
+        let (_shutdown_tx, shutdown_rx) = oneshot::channel();
+        let (ready_tx, ready_rx) = oneshot::channel();
+        run_server(config, shutdown_rx, Some(ready_tx)).await;
+        let err = ready_rx.await.unwrap().unwrap_err();
+        assert!(
+            err.contains("Invalid event retention of -1 days"),
+            "{}",
+            err
+        );
+    }
+}
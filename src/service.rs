@@ -1,27 +1,61 @@
-use crate::models::{BashEvent, ExecuteBashRequest};
-use crate::runtime::bash::BashEventService;
+use crate::audit::AuditLog;
+use crate::auth::{Capability, CapabilityTokens};
+use crate::logger;
+use crate::models::{BashPeek, ExecuteBashRequest, Mutation, MutationOp};
+use crate::runtime::bash::{compile_abort_on, BashEventService};
 use rmcp::{
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+    handler::server::{router::tool::ToolRouter, tool::ToolCallContext, wrapper::Parameters},
     model::*,
     schemars,
     service::RequestContext,
-    tool, tool_handler, tool_router, ErrorData as McpError, RoleServer, ServerHandler,
+    tool, tool_router, ErrorData as McpError, RoleServer, ServerHandler,
 };
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::time::{sleep, Duration};
+use uuid::Uuid;
 
+use crate::tools::checkpoint::{
+    create_checkpoint, restore_checkpoint, Checkpoint, RestoreCheckpointArgs,
+};
+use crate::tools::code_stats::{run_code_stats, CodeStatsArgs};
+use crate::tools::disk_usage::run_disk_usage;
+use crate::tools::fetch::{run_fetch_url, FetchConfig, FetchUrlArgs};
 use crate::tools::file_tools::*;
-use crate::tools::glob::{run_glob, GlobArgs};
-use crate::tools::grep::{run_grep, GrepArgs};
+use crate::tools::git::run_git_info;
+use crate::tools::glob::{glob_matches, run_glob, GlobArgs};
+use crate::tools::grep::{grep_matches, run_grep, GrepArgs};
+use crate::tools::imports::{run_resolve_import, ResolveImportArgs};
+use crate::tools::search_all::{run_search_all, SearchAllArgs};
+use crate::tools::tool_versions::{run_tool_versions, ToolVersionsArgs};
+use crate::tools::version::version_info;
+use crate::tools::watch::{run_watch_dir, WatchDirArgs};
+
+/// How long `bash` and `bash_check` wait for a command before giving up on it
+const BASH_WAIT_LIMIT: Duration = Duration::from_secs(300);
 
 #[derive(Clone)]
 pub struct CoderMcpService {
     bash: Arc<BashEventService>,
     workspace_dir: PathBuf,
-    editor_history: Arc<Mutex<HashMap<PathBuf, Vec<String>>>>,
+    editor_history: Arc<Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>>,
+    /// Contents replaced by `undo_edit`, for `redo_edit`
+    redo_history: Arc<Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>>,
+    /// Edited paths, least recently edited first
+    history_recency: Arc<Mutex<VecDeque<PathBuf>>>,
+    /// Most undo versions kept in memory across all files
+    history_limit: Option<usize>,
+    mutations: Arc<Mutex<Vec<Mutation>>>,
+    checkpoints: Arc<Mutex<HashMap<String, Checkpoint>>>,
+    audit_log: Option<Arc<AuditLog>>,
+    fetch: Option<Arc<FetchConfig>>,
+    /// Log file read by `get_logs`
+    log_file: Option<PathBuf>,
+    /// When set, only read-write tokens may call tools missing from the read-only service
+    capability_tokens: Option<Arc<CapabilityTokens>>,
+    read_tools: ReadTools,
     tool_router: ToolRouter<CoderMcpService>,
 }
 
@@ -29,34 +63,200 @@ pub struct CoderMcpService {
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct BashArgs {
     pub command: String,
+    /// Directory to run just this command in, relative to the workspace
     pub cwd: Option<String>,
+    /// Seconds before the command is interrupted and reported with exit code -1 (default 300)
     pub timeout: Option<u64>,
+    /// Regex; interrupt the command as soon as an output line matches it
+    pub abort_on: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct BashHistoryArgs {
+    /// Show only this command
+    pub command_id: Option<String>,
+    /// Number of most recent commands to show (default 20)
+    pub limit: Option<usize>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct BashCheckArgs {
+    pub command: String,
+    /// Exit code that counts as success (default 0)
+    pub expect_code: Option<i32>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct BashPeekArgs {
+    /// ID of a command started with `bash_start`
+    pub id: String,
+    /// Number of trailing output lines to return (default 20)
+    pub lines: Option<usize>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct BashCommandInfoArgs {
+    /// ID of a command, or of one of its output events
+    pub id: String,
 }
 
-// File tool arguments
 #[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct CancelBashArgs {
+    /// ID of the running command to interrupt
+    pub command_id: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct BashSandboxArgs {
+    /// Turn sandbox mode on or off; omit to report the current state
+    pub enabled: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct SlowestCommandsArgs {
+    /// Number of commands to return (default 10)
+    pub limit: Option<usize>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct GetLogsArgs {
+    /// Number of trailing log lines to return (default 100)
+    pub lines: Option<usize>,
+}
+
+// File tool arguments
+#[derive(Clone, Default, serde::Deserialize, schemars::JsonSchema)]
 pub struct ViewFileArgs {
     pub path: String,
     pub start_line: Option<u64>,
     pub end_line: Option<u64>,
+    /// Truncate displayed lines longer than this many characters
+    pub max_line_width: Option<usize>,
+    /// Regex; only matching lines are returned, with their line numbers
+    pub filter: Option<String>,
+    /// List the directory instead of failing when `path` is one (default false)
+    pub list_if_directory: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ViewWithMatchesArgs {
+    pub path: String,
+    /// Regex; matching lines are marked with `>`
+    pub pattern: String,
+    pub start_line: Option<u64>,
+    pub end_line: Option<u64>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ErrorContextArgs {
+    pub path: String,
+    /// 1-based line reported by the compiler or linter
+    pub line: u64,
+    /// Lines to show on each side of `line` (default 5)
+    pub context: Option<usize>,
 }
 
 #[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ExtractBlockArgs {
+    pub path: String,
+    /// Name of the function, struct, class, ... to extract
+    pub symbol: String,
+}
+
+#[derive(Default, serde::Deserialize, schemars::JsonSchema)]
 pub struct ListDirectoryArgs {
     pub path: String,
+    /// Maximum number of entries to return (capped by the server)
+    pub limit: Option<usize>,
+    /// List subdirectories too, as paths relative to `path` (default false)
+    pub recursive: Option<bool>,
+    /// Levels to descend when recursive; 1 lists only `path` itself (default unlimited)
+    pub max_depth: Option<usize>,
+    /// List directories before files within each directory (default false)
+    pub dirs_first: Option<bool>,
+    /// Include entries whose names start with `.` (default false)
+    pub include_hidden: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct FileStatArgs {
+    /// Workspace-relative path; a symlink is described itself rather than followed
+    pub path: String,
 }
 
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct CreateFileArgs {
     pub path: String,
     pub content: String,
+    /// Make the file executable (Unix only; default false)
+    pub executable: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct NewFile {
+    pub path: String,
+    pub content: String,
 }
 
 #[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct CreateFilesArgs {
+    pub files: Vec<NewFile>,
+}
+
+#[derive(Default, serde::Deserialize, schemars::JsonSchema)]
 pub struct StrReplaceArgs {
     pub path: String,
     pub old_str: String,
     pub new_str: String,
+    /// Replace every occurrence instead of requiring a unique match (default false)
+    pub replace_all: Option<bool>,
+    /// Replace only the Nth occurrence (1-based) instead of requiring a unique match
+    pub occurrence: Option<usize>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct EditOperation {
+    pub old_str: String,
+    pub new_str: String,
+    /// Replace every occurrence instead of requiring a unique match (default false)
+    pub replace_all: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct MultiEditArgs {
+    pub path: String,
+    /// Applied in order, each against the result of the previous ones
+    pub edits: Vec<EditOperation>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct EditBlock {
+    /// Text to find; must appear exactly once when the block is applied
+    pub search: String,
+    pub replace: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ApplyEditBlocksArgs {
+    pub path: String,
+    /// SEARCH/REPLACE blocks, applied in order, each against the result of the previous ones
+    pub blocks: Vec<EditBlock>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ApplyPatchArgs {
+    /// Unified diff, as produced by `diff -u` or `git diff`; may cover several files
+    pub patch: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ConditionalReplaceArgs {
+    /// Glob pattern relative to the workspace selecting candidate files
+    pub glob: String,
+    /// Only files whose content contains this string are edited
+    pub contains: String,
+    pub old_str: String,
+    pub new_str: String,
 }
 
 #[derive(serde::Deserialize, schemars::JsonSchema)]
@@ -66,25 +266,115 @@ pub struct InsertLinesArgs {
     pub content: String,
 }
 
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct AppendFileArgs {
+    pub path: String,
+    /// Text to add at the end of the file
+    pub content: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct InsertNearArgs {
+    pub path: String,
+    /// Text identifying exactly one line of the file
+    pub anchor: String,
+    /// "before" or "after" the anchor line
+    pub position: String,
+    pub content: String,
+}
+
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct DeleteFileArgs {
     pub path: String,
 }
 
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct SwapFilesArgs {
+    pub a: String,
+    pub b: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct MoveFileArgs {
+    pub source: String,
+    pub destination: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct MoveGlobArgs {
+    /// Glob pattern relative to the workspace, e.g. "src/**/*.spec.js"
+    pub pattern: String,
+    /// Directory the matching files are moved into; created if missing
+    pub dest_dir: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct SortFileArgs {
+    pub path: String,
+    /// Remove duplicate lines (default false)
+    pub dedup: Option<bool>,
+    /// Compare lines ignoring case (default false)
+    pub case_insensitive: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct NormalizeWhitespaceArgs {
+    pub path: String,
+    /// Remove trailing spaces and tabs from every line (default true)
+    pub trim_trailing: Option<bool>,
+    /// Expand tabs to spaces using this tab width (default: keep tabs)
+    pub tabs_to_spaces: Option<usize>,
+    /// Collapse runs of blank lines into a single blank line (default false)
+    pub collapse_blank_lines: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct ToggleCommentArgs {
+    pub path: String,
+    pub start_line: u64,
+    pub end_line: u64,
+    /// Line-comment token such as `//` or `#` (inferred from the extension if omitted)
+    pub style: Option<String>,
+}
+
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct UndoEditArgs {
     pub path: String,
 }
 
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct DiffArgs {
+    pub path: String,
+    /// Workspace path to compare against; defaults to the version before the last edit
+    pub against: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct RedoEditArgs {
+    pub path: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct RevertFileArgs {
+    pub path: String,
+}
+
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct TreeArgs {
     #[serde(default)]
     pub path: Option<String>,
+    /// Comma-separated names or glob patterns to skip, e.g. `node_modules,*.log,target/**`
     #[serde(default)]
     pub exclude: Option<String>,
     pub max_depth: Option<usize>,
     #[serde(default)]
     pub truncate: Option<usize>,
+    /// Include entries whose names start with `.` (default false)
+    #[serde(default)]
+    pub include_hidden: Option<bool>,
+    /// "text" (default) for an ASCII tree or "json" for nested `{ name, type, children }`
+    #[serde(default)]
+    pub format: Option<String>,
 }
 
 #[tool_router]
@@ -92,12 +382,141 @@ impl CoderMcpService {
     pub fn new(bash: BashEventService, workspace_dir: PathBuf) -> Self {
         Self {
             bash: Arc::new(bash),
+            read_tools: ReadTools::new(workspace_dir.clone()),
             workspace_dir,
             editor_history: Arc::new(Mutex::new(HashMap::new())),
+            redo_history: Arc::new(Mutex::new(HashMap::new())),
+            history_recency: Arc::new(Mutex::new(VecDeque::new())),
+            history_limit: None,
+            mutations: Arc::new(Mutex::new(Vec::new())),
+            checkpoints: Arc::new(Mutex::new(HashMap::new())),
+            audit_log: None,
+            fetch: None,
+            log_file: logger::log_file().map(Path::to_path_buf),
+            capability_tokens: None,
             tool_router: Self::tool_router(),
         }
     }
 
+    /// Records every tool call in `audit_log`.
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Checks the caller's bearer token before every tool that can change the workspace.
+    /// Callers without a read-write token are limited to the read-only tool set.
+    pub fn with_capability_tokens(mut self, tokens: CapabilityTokens) -> Self {
+        self.capability_tokens = Some(Arc::new(tokens));
+        self
+    }
+
+    /// Makes `get_logs` read `path` instead of the file configured by `init_logging`.
+    pub fn with_log_file(mut self, path: PathBuf) -> Self {
+        self.log_file = Some(path);
+        self
+    }
+
+    /// Caps the undo versions kept across all files at `max_entries`. Once exceeded, the
+    /// history of the least recently edited files is dropped first.
+    pub fn with_history_limit(mut self, max_entries: usize) -> Self {
+        self.history_limit = Some(max_entries);
+        self
+    }
+
+    /// Enables the `fetch_url` tool. Without this the tool is not listed at all.
+    pub fn with_fetch_config(mut self, config: FetchConfig) -> Self {
+        if self.fetch.is_none() {
+            self.tool_router.merge(Self::fetch_tool_router());
+        }
+        self.fetch = Some(Arc::new(config));
+        self
+    }
+
+    /// Name, description and input schema of every tool, sorted by name.
+    pub fn list_tools() -> Vec<Tool> {
+        let mut tools = Self::tool_router().list_all();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+    }
+
+    /// Appends to the mutation log unless the tool reported an error. A fresh edit also
+    /// discards the redo history of the files it touched.
+    async fn record_mutations(&self, output: &str, changes: &[(MutationOp, &str)]) {
+        if output.starts_with("Error") {
+            return;
+        }
+        let mut redo_history = self.redo_history.lock().await;
+        for (_, path) in changes {
+            redo_history.remove(&self.workspace_dir.join(path));
+        }
+        drop(redo_history);
+        self.log_mutations(output, changes).await;
+    }
+
+    /// Appends to the mutation log unless the tool reported an error, keeping redo history.
+    async fn log_mutations(&self, output: &str, changes: &[(MutationOp, &str)]) {
+        if output.starts_with("Error") {
+            return;
+        }
+        let mut mutations = self.mutations.lock().await;
+        for (op, path) in changes {
+            mutations.push(Mutation {
+                op: *op,
+                path: path.to_string(),
+                timestamp: chrono::Utc::now(),
+            });
+        }
+        drop(mutations);
+        self.evict_history(changes).await;
+    }
+
+    /// Marks `changes` as the most recently edited paths, then drops whole file histories (undo
+    /// and redo versions alike), least recently edited first, until the history limit is met.
+    /// If the file edited last is over the limit on its own, its oldest versions are dropped.
+    async fn evict_history(&self, changes: &[(MutationOp, &str)]) {
+        let mut recency = self.history_recency.lock().await;
+        for (_, path) in changes {
+            let path = self.workspace_dir.join(path);
+            recency.retain(|p| *p != path);
+            recency.push_back(path);
+        }
+        let Some(limit) = self.history_limit else {
+            return;
+        };
+
+        let mut history = self.editor_history.lock().await;
+        let mut redo_history = self.redo_history.lock().await;
+        let mut total: usize = history
+            .values()
+            .chain(redo_history.values())
+            .map(Vec::len)
+            .sum();
+        while total > limit && recency.len() > 1 {
+            let Some(oldest) = recency.pop_front() else {
+                break;
+            };
+            total -= history.remove(&oldest).map_or(0, |versions| versions.len());
+            total -= redo_history
+                .remove(&oldest)
+                .map_or(0, |versions| versions.len());
+        }
+        if total > limit
+            && let Some(last) = recency.back()
+        {
+            for histories in [&mut *history, &mut *redo_history] {
+                if let Some(versions) = histories.get_mut(last) {
+                    let excess = (total - limit).min(versions.len());
+                    versions.drain(..excess);
+                    total -= excess;
+                    if versions.is_empty() {
+                        histories.remove(last);
+                    }
+                }
+            }
+        }
+    }
+
     #[tool(
         name = "search_filenames",
         description = "Fast file pattern matching tool. Finds files by name patterns (e.g. '**/*.js'). Returns matching file paths."
@@ -106,71 +525,117 @@ impl CoderMcpService {
         &self,
         Parameters(args): Parameters<GlobArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_glob(&args, &self.workspace_dir)?;
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        self.read_tools.search_filenames(args).await
     }
 
     #[tool(
         name = "search_content",
-        description = "Fast content search tool. Searches file contents using regex. Returns matching file paths."
+        description = "Fast content search tool. Searches file contents using regex. Returns matching file paths, or each matching line as path:line_number: text when show_lines or a context count is set."
     )]
     async fn search_content(
         &self,
         Parameters(args): Parameters<GrepArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_grep(&args, &self.workspace_dir)?;
+        self.read_tools.search_content(args).await
+    }
+
+    #[tool(
+        name = "search_all",
+        description = "Search file names and file contents for a text in one call (case-insensitive). Returns one ranked list with file name matches before content matches."
+    )]
+    async fn search_all(
+        &self,
+        Parameters(args): Parameters<SearchAllArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_search_all(&args, &self.workspace_dir)?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
     #[tool(
         name = "bash",
-        description = "Execute a bash command in a stateful terminal session. State (environment variables, working directory) persists across calls."
+        description = "Execute a bash command in a stateful terminal session. State (environment variables, working directory) persists across calls. A command still running after 5 minutes returns its output so far; follow it with bash_peek."
     )]
     async fn bash(
         &self,
         Parameters(args): Parameters<BashArgs>,
     ) -> Result<CallToolResult, McpError> {
         tracing::info!("Executing bash command: {}", args.command);
+        if let Err(e) = compile_abort_on(args.abort_on.as_deref()) {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: {}",
+                e
+            ))]));
+        }
         let req = ExecuteBashRequest {
             command: args.command,
             cwd: args.cwd,
             timeout: args.timeout,
+            abort_on: args.abort_on,
         };
 
         let cmd = self.bash.start_bash_command(req);
         tracing::info!("Started bash command with ID: {}", cmd.id);
 
-        // Simple polling loop
-        let mut attempts = 0;
+        let progress = self.wait_for_command(cmd.id, BASH_WAIT_LIMIT).await?;
+        let mut result_str = progress.output;
+        if !result_str.is_empty() {
+            result_str.push('\n');
+        }
+        match progress.exit_code {
+            Some(exit_code) => {
+                result_str.push_str(&format!("[Command finished with exit code {}]", exit_code))
+            }
+            None => result_str.push_str(&format!(
+                "[Command is still running; follow it with bash_peek using ID {}]",
+                cmd.id
+            )),
+        }
+        Ok(CallToolResult::success(vec![Content::text(result_str)]))
+    }
+
+    #[tool(
+        name = "bash_check",
+        description = "Run a bash command in the terminal session and report whether it exited with the expected code (default 0) as structured { success, exit_code }."
+    )]
+    async fn bash_check(
+        &self,
+        Parameters(args): Parameters<BashCheckArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        tracing::info!("Checking bash command: {}", args.command);
+        let req = ExecuteBashRequest {
+            command: args.command,
+            cwd: None,
+            timeout: None,
+            abort_on: None,
+        };
+        let cmd = self.bash.start_bash_command(req);
+        let progress = self.wait_for_command(cmd.id, BASH_WAIT_LIMIT).await?;
+        let Some(exit_code) = progress.exit_code else {
+            return Err(McpError {
+                code: ErrorCode(0),
+                message: "Polling timed out".to_string().into(),
+                data: None,
+            });
+        };
+        Ok(CallToolResult::structured(serde_json::json!({
+            "success": exit_code == args.expect_code.unwrap_or(0),
+            "exit_code": exit_code,
+        })))
+    }
+
+    /// Polls a started command until it exits or `limit` passes. In the latter case the
+    /// output so far is returned with the command still marked as running.
+    async fn wait_for_command(&self, id: Uuid, limit: Duration) -> Result<BashPeek, McpError> {
+        let deadline = tokio::time::Instant::now() + limit;
         loop {
             sleep(Duration::from_millis(100)).await;
-            let page = self.bash.search_bash_events(Some(cmd.id));
-            if let Some(last_item) = page.items.last() {
-                if let BashEvent::BashOutput(out) = last_item {
-                    // Combine stdout and stderr
-                    let mut result_str = String::new();
-                    if let Some(stdout) = &out.stdout {
-                        result_str.push_str(stdout);
-                    }
-                    if let Some(stderr) = &out.stderr {
-                        if !result_str.is_empty() {
-                            result_str.push('\n');
-                        }
-                        result_str.push_str(stderr);
-                    }
-                    if let Some(exit_code) = out.exit_code {
-                        if !result_str.is_empty() {
-                            result_str.push('\n');
-                        }
-                        result_str
-                            .push_str(&format!("[Command finished with exit code {}]", exit_code));
-                    }
-                    return Ok(CallToolResult::success(vec![Content::text(result_str)]));
-                }
+            let progress = self.bash.command_progress(id);
+            if let Some(progress) = &progress
+                && (!progress.is_running || tokio::time::Instant::now() >= deadline)
+            {
+                return Ok(progress.clone());
             }
-
-            attempts += 1;
-            if attempts > 3000 {
+            if progress.is_none() && tokio::time::Instant::now() >= deadline {
                 return Err(McpError {
                     code: ErrorCode(0),
                     message: "Polling timed out".to_string().into(),
@@ -181,107 +646,1062 @@ impl CoderMcpService {
     }
 
     #[tool(
-        name = "view_file",
-        description = "Read file contents with optional line range. Returns file content with line numbers."
+        name = "bash_start",
+        description = "Start a bash command in the stateful terminal session without waiting for it to finish. Returns the command ID to use with bash_peek."
     )]
-    async fn view_file(
+    async fn bash_start(
         &self,
-        Parameters(args): Parameters<ViewFileArgs>,
+        Parameters(args): Parameters<BashArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_view_file(&args, &self.workspace_dir).await?;
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        tracing::info!("Starting bash command: {}", args.command);
+        if let Err(e) = compile_abort_on(args.abort_on.as_deref()) {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: {}",
+                e
+            ))]));
+        }
+        let req = ExecuteBashRequest {
+            command: args.command,
+            cwd: args.cwd,
+            timeout: args.timeout,
+            abort_on: args.abort_on,
+        };
+
+        let cmd = self.bash.start_bash_command(req);
+        tracing::info!("Started bash command with ID: {}", cmd.id);
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Started command with ID: {}",
+            cmd.id
+        ))]))
     }
 
     #[tool(
-        name = "list_directory",
-        description = "List contents of a directory, excluding hidden files."
+        name = "bash_peek",
+        description = "Return the last N lines of output of a command started with bash_start, without waiting. Also reports whether the command is still running."
     )]
-    async fn list_directory(
+    async fn bash_peek(
         &self,
-        Parameters(args): Parameters<ListDirectoryArgs>,
+        Parameters(args): Parameters<BashPeekArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_list_directory(&args, &self.workspace_dir).await?;
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        let Ok(command_id) = Uuid::parse_str(&args.id) else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: Invalid command ID '{}'",
+                args.id
+            ))]));
+        };
+
+        let Some(peek) = self
+            .bash
+            .peek_bash_output(command_id, args.lines.unwrap_or(20))
+        else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: No command found with ID {}",
+                args.id
+            ))]));
+        };
+
+        let mut result_str = peek.output;
+        if !result_str.is_empty() {
+            result_str.push('\n');
+        }
+        match peek.exit_code {
+            Some(exit_code) => {
+                result_str.push_str(&format!("[Command finished with exit code {}]", exit_code))
+            }
+            None => result_str.push_str("[Command is still running]"),
+        }
+        Ok(CallToolResult::success(vec![Content::text(result_str)]))
     }
 
     #[tool(
-        name = "create_file",
-        description = "Create a new file with content. Returns error if file already exists."
+        name = "cancel_bash",
+        description = "Interrupt a running bash command with Ctrl-C, e.g. one started with bash_start that hangs. The terminal session stays usable afterwards."
     )]
-    async fn create_file(
+    async fn cancel_bash(
         &self,
-        Parameters(args): Parameters<CreateFileArgs>,
+        Parameters(args): Parameters<CancelBashArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_create_file(&args, &self.workspace_dir).await?;
+        let Ok(command_id) = Uuid::parse_str(&args.command_id) else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: Invalid command ID '{}'",
+                args.command_id
+            ))]));
+        };
+        let output = match self.bash.cancel_command(command_id) {
+            Ok(true) => format!("Sent Ctrl-C to command {}.", command_id),
+            Ok(false) => format!("Error: Command {} is not running", command_id),
+            Err(e) => format!("Error: Failed to interrupt command {}: {}", command_id, e),
+        };
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
     #[tool(
-        name = "str_replace",
-        description = "Find and replace exact string in file. Returns error if string not found or multiple matches. Shows context snippet after edit."
+        name = "session_env_diff",
+        description = "Report how the bash session drifted from its startup state: environment variables added, changed or removed, and whether the working directory moved."
     )]
-    async fn str_replace(
+    async fn session_env_diff(&self) -> Result<CallToolResult, McpError> {
+        let diff = match self.bash.session_env_diff().await {
+            Ok(diff) => diff,
+            Err(e) => {
+                return Ok(CallToolResult::success(vec![Content::text(format!(
+                    "Error: Failed to compare the session environment: {}",
+                    e
+                ))]));
+            }
+        };
+        let value = serde_json::to_value(diff).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize environment diff: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(value))
+    }
+
+    #[tool(
+        name = "bash_command_info",
+        description = "Show a bash command's text, cwd, timeout and start time with a summary of its output (exit code, finish time, size and last lines). Accepts a command ID or the ID of one of its output events."
+    )]
+    async fn bash_command_info(
         &self,
-        Parameters(args): Parameters<StrReplaceArgs>,
+        Parameters(args): Parameters<BashCommandInfoArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_str_replace(&args, &self.workspace_dir, &self.editor_history).await?;
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        let Ok(id) = Uuid::parse_str(&args.id) else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: Invalid command ID '{}'",
+                args.id
+            ))]));
+        };
+        let Some(info) = self.bash.command_info(id, 20) else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: No command found with ID {}",
+                args.id
+            ))]));
+        };
+        let value = serde_json::to_value(info).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize command info: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(value))
     }
 
     #[tool(
-        name = "insert_lines",
-        description = "Insert content at a specific line number. Shows context snippet after edit."
+        name = "list_bash_history",
+        description = "List the bash commands run in this session, oldest first, with start time, command ID, exit code and the last lines of output. Pass command_id to show a single command."
     )]
-    async fn insert_lines(
+    async fn list_bash_history(
         &self,
-        Parameters(args): Parameters<InsertLinesArgs>,
+        Parameters(args): Parameters<BashHistoryArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_insert_lines(&args, &self.workspace_dir, &self.editor_history).await?;
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        let command_id = match args.command_id.as_deref().map(Uuid::parse_str) {
+            None => None,
+            Some(Ok(id)) => Some(id),
+            Some(Err(_)) => {
+                return Ok(CallToolResult::success(vec![Content::text(format!(
+                    "Error: Invalid command ID '{}'",
+                    args.command_id.unwrap_or_default()
+                ))]));
+            }
+        };
+        let history = self.bash.command_history(command_id, 5);
+        if history.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(
+                match command_id {
+                    Some(id) => format!("Error: No command found with ID {}", id),
+                    None => "No bash commands have been run yet.".to_string(),
+                },
+            )]));
+        }
+
+        let limit = args.limit.unwrap_or(20);
+        let entries: Vec<String> = history[history.len().saturating_sub(limit)..]
+            .iter()
+            .map(|info| {
+                let status = match info.exit_code {
+                    Some(code) => format!("exit {}", code),
+                    None => "running".to_string(),
+                };
+                let mut entry = format!(
+                    "[{}] {} ({})\n$ {}",
+                    info.command.timestamp.to_rfc3339(),
+                    info.command.id,
+                    status,
+                    info.command.command
+                );
+                for line in info.output_tail.lines() {
+                    entry.push_str(&format!("\n  {}", line));
+                }
+                entry
+            })
+            .collect();
+        Ok(CallToolResult::success(vec![Content::text(
+            entries.join("\n\n"),
+        )]))
     }
 
     #[tool(
-        name = "delete_file",
-        description = "Delete a file from the workspace."
+        name = "bash_sandbox",
+        description = "Switch the terminal session into a scratch directory outside the workspace (enabled: true) or back to the workspace (enabled: false), and report the sandbox path. File tools always use the workspace."
     )]
-    async fn delete_file(
+    async fn bash_sandbox(
         &self,
-        Parameters(args): Parameters<DeleteFileArgs>,
+        Parameters(args): Parameters<BashSandboxArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_delete_file(&args, &self.workspace_dir).await?;
+        if let Some(enabled) = args.enabled
+            && let Err(e) = self.bash.set_sandbox_mode(enabled).await
+        {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: Failed to switch sandbox mode: {}",
+                e
+            ))]));
+        }
+        let output = match self.bash.sandbox_dir() {
+            Some(dir) => format!(
+                "Sandbox mode is on; commands run in {}. File tools still use the workspace {}.",
+                dir.display(),
+                self.workspace_dir.display()
+            ),
+            None => format!(
+                "Sandbox mode is off. Workspace: {}",
+                self.workspace_dir.display()
+            ),
+        };
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
     #[tool(
-        name = "undo_edit",
-        description = "Revert the last edit made to a file (from str_replace or insert_lines)."
+        name = "slowest_commands",
+        description = "List the finished bash commands that took the longest, with their durations in milliseconds and exit codes."
     )]
-    async fn undo_edit(
+    async fn slowest_commands(
         &self,
-        Parameters(args): Parameters<UndoEditArgs>,
+        Parameters(args): Parameters<SlowestCommandsArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_undo_edit(&args, &self.workspace_dir, &self.editor_history).await?;
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        let commands = self.bash.slowest_commands(args.limit.unwrap_or(10));
+        let value = serde_json::to_value(commands).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize command durations: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(serde_json::json!({
+            "commands": value
+        })))
     }
-}
 
-#[tool_handler]
-impl ServerHandler for CoderMcpService {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            server_info: Implementation::from_build_env(),
+    #[tool(
+        name = "view_file",
+        description = "Read file contents with optional line range. Returns file content with line numbers. Set filter to a regex to return only matching lines in one pass over the file. .gz files are decompressed transparently."
+    )]
+    async fn view_file(
+        &self,
+        Parameters(args): Parameters<ViewFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.view_file(args).await
+    }
+
+    #[tool(
+        name = "view_with_matches",
+        description = "Read a file (or a line range) with line numbers, marking lines that match a regex with '>'."
+    )]
+    async fn view_with_matches(
+        &self,
+        Parameters(args): Parameters<ViewWithMatchesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_view_with_matches(&args, &self.workspace_dir).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "error_context",
+        description = "Show the numbered lines around a file:line reported by a compiler or linter, with the reported line marked '>'."
+    )]
+    async fn error_context(
+        &self,
+        Parameters(args): Parameters<ErrorContextArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_error_context(&args, &self.workspace_dir).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "extract_block",
+        description = "Return the full source of a named function, struct or class with line numbers. The end of the block is inferred from braces or indentation."
+    )]
+    async fn extract_block(
+        &self,
+        Parameters(args): Parameters<ExtractBlockArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_extract_block(&args, &self.workspace_dir).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "resolve_import",
+        description = "Resolve a relative import (Rust modules or TypeScript/JavaScript specifiers) in a source file to the file it refers to."
+    )]
+    async fn resolve_import(
+        &self,
+        Parameters(args): Parameters<ResolveImportArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_resolve_import(&args, &self.workspace_dir)?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "list_directory",
+        description = "List contents of a directory, excluding hidden files unless include_hidden is set. Set recursive to list nested entries as relative paths, optionally limited by max_depth."
+    )]
+    async fn list_directory(
+        &self,
+        Parameters(args): Parameters<ListDirectoryArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.list_directory(args).await
+    }
+
+    #[tool(
+        name = "list_directory_detailed",
+        description = "List contents of a directory as structured { name, type, size_bytes, line_count, modified } entries. Takes the same arguments and filtering as list_directory."
+    )]
+    async fn list_directory_detailed(
+        &self,
+        Parameters(args): Parameters<ListDirectoryArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.list_directory_detailed(args).await
+    }
+
+    #[tool(
+        name = "file_stat",
+        description = "Report metadata for one path as structured { path, type, size_bytes, line_count, modified, executable, target } without reading it into the conversation. type is file, dir or symlink; modified is RFC 3339."
+    )]
+    async fn file_stat(
+        &self,
+        Parameters(args): Parameters<FileStatArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.file_stat(args).await
+    }
+
+    #[tool(
+        name = "create_file",
+        description = "Create a new file with content. Returns error if file already exists. Set executable to make the file executable (e.g. for scripts)."
+    )]
+    async fn create_file(
+        &self,
+        Parameters(args): Parameters<CreateFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_create_file(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Create, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "create_files",
+        description = "Create several new files at once. If any file already exists or cannot be written, none of the files are created."
+    )]
+    async fn create_files(
+        &self,
+        Parameters(args): Parameters<CreateFilesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_create_files(&args, &self.workspace_dir).await?;
+        let created: Vec<_> = args
+            .files
+            .iter()
+            .map(|f| (MutationOp::Create, f.path.as_str()))
+            .collect();
+        self.record_mutations(&output, &created).await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "str_replace",
+        description = "Find and replace exact string in file. Returns error if string not found or multiple matches, unless replace_all or occurrence is set. Shows context snippet after edit."
+    )]
+    async fn str_replace(
+        &self,
+        Parameters(args): Parameters<StrReplaceArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_str_replace(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "multi_edit",
+        description = "Apply several find-and-replace edits to one file in order. Each old_str must match exactly once unless replace_all is set. If any edit fails, the file is left untouched. One undo_edit reverts the whole batch."
+    )]
+    async fn multi_edit(
+        &self,
+        Parameters(args): Parameters<MultiEditArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_multi_edit(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "apply_edit_blocks",
+        description = "Apply SEARCH/REPLACE edit blocks to one file in order. Each search text must match exactly once when its block is applied. If any block fails, the file is left untouched. Reports the lines removed and added; one undo_edit reverts all blocks."
+    )]
+    async fn apply_edit_blocks(
+        &self,
+        Parameters(args): Parameters<ApplyEditBlocksArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output =
+            run_apply_edit_blocks(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "apply_patch",
+        description = "Apply a unified diff (diff -u or git diff output) to one or more workspace files, including created and deleted files. If any hunk's context does not match, no file is changed and the failing hunk is reported. Each patched file can be restored with undo_edit."
+    )]
+    async fn apply_patch(
+        &self,
+        Parameters(args): Parameters<ApplyPatchArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (output, changes) =
+            run_apply_patch(&args, &self.workspace_dir, &self.editor_history).await?;
+        let changes: Vec<_> = changes
+            .iter()
+            .map(|(op, path)| (*op, path.as_str()))
+            .collect();
+        self.record_mutations(&output, &changes).await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "conditional_replace",
+        description = "In every file matching a glob whose content contains a given string, replace all occurrences of old_str with new_str. Reports the files changed and the files skipped with the reason. Each changed file can be restored with undo_edit."
+    )]
+    async fn conditional_replace(
+        &self,
+        Parameters(args): Parameters<ConditionalReplaceArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (output, changed) =
+            run_conditional_replace(&args, &self.workspace_dir, &self.editor_history).await?;
+        let edits: Vec<_> = changed
+            .iter()
+            .map(|path| (MutationOp::Edit, path.as_str()))
+            .collect();
+        self.record_mutations(&output, &edits).await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "insert_lines",
+        description = "Insert content at a specific line number. Shows context snippet after edit."
+    )]
+    async fn insert_lines(
+        &self,
+        Parameters(args): Parameters<InsertLinesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_insert_lines(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "append_to_file",
+        description = "Append content to the end of a file without reading it first, creating the file if it doesn't exist. Shows the last lines of the file afterwards."
+    )]
+    async fn append_to_file(
+        &self,
+        Parameters(args): Parameters<AppendFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let op = if self.workspace_dir.join(&args.path).exists() {
+            MutationOp::Edit
+        } else {
+            MutationOp::Create
+        };
+        let output = run_append_to_file(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(op, &args.path)]).await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "insert_near",
+        description = "Insert content before or after the unique line containing an anchor text. More robust than insert_lines when line numbers may have shifted. Shows context snippet after edit."
+    )]
+    async fn insert_near(
+        &self,
+        Parameters(args): Parameters<InsertNearArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_insert_near(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "delete_file",
+        description = "Delete a file from the workspace."
+    )]
+    async fn delete_file(
+        &self,
+        Parameters(args): Parameters<DeleteFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_delete_file(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Delete, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "swap_files",
+        description = "Atomically swap the contents of two existing files. Each file can be restored with undo_edit."
+    )]
+    async fn swap_files(
+        &self,
+        Parameters(args): Parameters<SwapFilesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_swap_files(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(
+            &output,
+            &[(MutationOp::Edit, &args.a), (MutationOp::Edit, &args.b)],
+        )
+        .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "move_file",
+        description = "Move or rename a file. Fails if the destination already exists; missing parent directories are created. Edit history follows the file, so undo_edit keeps working at the new path."
+    )]
+    async fn move_file(
+        &self,
+        Parameters(args): Parameters<MoveFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_move_file(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(
+            &output,
+            &[
+                (MutationOp::Delete, &args.source),
+                (MutationOp::Create, &args.destination),
+            ],
+        )
+        .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "move_glob",
+        description = "Move every file matching a glob pattern into a directory (created if needed), keeping file names. Nothing is moved if any destination already exists. Edit history follows the moved files."
+    )]
+    async fn move_glob(
+        &self,
+        Parameters(args): Parameters<MoveGlobArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (output, moves) =
+            run_move_glob(&args, &self.workspace_dir, &self.editor_history).await?;
+        let changes: Vec<_> = moves
+            .iter()
+            .flat_map(|m| {
+                [
+                    (MutationOp::Delete, m.from.as_str()),
+                    (MutationOp::Create, m.to.as_str()),
+                ]
+            })
+            .collect();
+        self.record_mutations(&output, &changes).await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "sort_file",
+        description = "Sort the lines of a file, optionally removing duplicates or ignoring case. The change can be reverted with undo_edit."
+    )]
+    async fn sort_file(
+        &self,
+        Parameters(args): Parameters<SortFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_sort_file(&args, &self.workspace_dir, &self.editor_history).await?;
+        if !output.contains("No changes were made") {
+            self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+                .await;
+        }
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "normalize_whitespace",
+        description = "Trim trailing whitespace, expand tabs to spaces and/or collapse runs of blank lines in a file. Each normalization can be toggled. The change can be reverted with undo_edit."
+    )]
+    async fn normalize_whitespace(
+        &self,
+        Parameters(args): Parameters<NormalizeWhitespaceArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output =
+            run_normalize_whitespace(&args, &self.workspace_dir, &self.editor_history).await?;
+        if !output.contains("No changes were made") {
+            self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+                .await;
+        }
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "toggle_comment",
+        description = "Comment out a range of lines, or uncomment it if every non-blank line in the range is already commented. The change can be reverted with undo_edit."
+    )]
+    async fn toggle_comment(
+        &self,
+        Parameters(args): Parameters<ToggleCommentArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_toggle_comment(&args, &self.workspace_dir, &self.editor_history).await?;
+        self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "undo_edit",
+        description = "Revert the last change made to a file by the edit tools. Undoing create_file removes the file again; undoing delete_file restores it."
+    )]
+    async fn undo_edit(
+        &self,
+        Parameters(args): Parameters<UndoEditArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let existed = self.workspace_dir.join(&args.path).exists();
+        let output = run_undo_edit(
+            &args,
+            &self.workspace_dir,
+            &self.editor_history,
+            &self.redo_history,
+        )
+        .await?;
+        let op = restored_op(existed, self.workspace_dir.join(&args.path).exists());
+        self.log_mutations(&output, &[(op, &args.path)]).await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "diff_files",
+        description = "Show a unified diff of a file against the version before its last edit, or against another workspace file when against is given."
+    )]
+    async fn diff_files(
+        &self,
+        Parameters(args): Parameters<DiffArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_diff(&args, &self.workspace_dir, &self.editor_history).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "redo_edit",
+        description = "Re-apply the edit most recently reverted by undo_edit. Any new edit to the file clears what can be redone."
+    )]
+    async fn redo_edit(
+        &self,
+        Parameters(args): Parameters<RedoEditArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let existed = self.workspace_dir.join(&args.path).exists();
+        let output = run_redo_edit(
+            &args,
+            &self.workspace_dir,
+            &self.editor_history,
+            &self.redo_history,
+        )
+        .await?;
+        let op = restored_op(existed, self.workspace_dir.join(&args.path).exists());
+        self.log_mutations(&output, &[(op, &args.path)]).await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "revert_file",
+        description = "Restore a file to its content before the first edit of this session, discarding all edits at once. Clears the file's undo history."
+    )]
+    async fn revert_file(
+        &self,
+        Parameters(args): Parameters<RevertFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_revert_file(&args, &self.workspace_dir, &self.editor_history).await?;
+        if output.starts_with("Reverted") {
+            self.record_mutations(&output, &[(MutationOp::Edit, &args.path)])
+                .await;
+        }
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "list_mutations",
+        description = "List the files created, edited or deleted through the file tools in this session, in chronological order."
+    )]
+    async fn list_mutations(&self) -> Result<CallToolResult, McpError> {
+        let mutations = self.mutations.lock().await;
+        let value = serde_json::to_value(&*mutations).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize mutation log: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(serde_json::json!({
+            "mutations": value
+        })))
+    }
+
+    #[tool(
+        name = "list_active_edits",
+        description = "List the files that have edits undo_edit could still revert, with the number of saved versions for each. Use it to avoid editing files another agent is working on."
+    )]
+    async fn list_active_edits(&self) -> Result<CallToolResult, McpError> {
+        let history = self.editor_history.lock().await;
+        let mut files: Vec<(String, usize)> = history
+            .iter()
+            .filter(|(_, versions)| !versions.is_empty())
+            .map(|(path, versions)| {
+                let rel = path.strip_prefix(&self.workspace_dir).unwrap_or(path);
+                (rel.to_string_lossy().to_string(), versions.len())
+            })
+            .collect();
+        files.sort();
+        let files: Vec<serde_json::Value> = files
+            .into_iter()
+            .map(|(path, versions)| serde_json::json!({ "path": path, "versions": versions }))
+            .collect();
+        Ok(CallToolResult::structured(serde_json::json!({
+            "files": files
+        })))
+    }
+
+    #[tool(
+        name = "create_checkpoint",
+        description = "Snapshot every file created, edited or deleted in this session. Returns a checkpoint ID for restore_checkpoint."
+    )]
+    async fn create_checkpoint(&self) -> Result<CallToolResult, McpError> {
+        let checkpoint = {
+            let mutations = self.mutations.lock().await;
+            create_checkpoint(&mutations, &self.workspace_dir)?
+        };
+        let mut checkpoints = self.checkpoints.lock().await;
+        let id = format!("checkpoint-{}", checkpoints.len() + 1);
+        let output = format!("Created {} with {} file(s).", id, checkpoint.file_count());
+        checkpoints.insert(id, checkpoint);
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "restore_checkpoint",
+        description = "Restore the files snapshotted by create_checkpoint. Files created after the checkpoint are deleted."
+    )]
+    async fn restore_checkpoint(
+        &self,
+        Parameters(args): Parameters<RestoreCheckpointArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let checkpoints = self.checkpoints.lock().await;
+        let Some(checkpoint) = checkpoints.get(&args.id) else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Error: No checkpoint with ID {}.",
+                args.id
+            ))]));
+        };
+        let mut mutations = self.mutations.lock().await;
+        let (output, changes) = restore_checkpoint(checkpoint, &mutations, &self.workspace_dir)?;
+        // A partial restore is recorded too, since those files did change
+        mutations.extend(changes.into_iter().map(|(op, path)| Mutation {
+            op,
+            path,
+            timestamp: chrono::Utc::now(),
+        }));
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "watch_dir",
+        description = "Watch a directory for the given number of seconds and report which files were created, modified or deleted. Hidden and gitignored paths are skipped."
+    )]
+    async fn watch_dir(
+        &self,
+        Parameters(args): Parameters<WatchDirArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_watch_dir(&args, &self.workspace_dir).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "tool_versions",
+        description = "Report the versions of installed toolchains (rustc, cargo, node, python, ...) by running `--version` probes in the terminal session. Tools that are not installed are listed as missing."
+    )]
+    async fn tool_versions(
+        &self,
+        Parameters(args): Parameters<ToolVersionsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let versions = run_tool_versions(&args, &self.bash).await;
+        let value = serde_json::to_value(versions).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize tool versions: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(value))
+    }
+
+    #[tool(
+        name = "git_info",
+        description = "Report the workspace's current git branch (or detached commit), ahead/behind counts against the upstream, and whether there are uncommitted changes."
+    )]
+    async fn git_info(&self) -> Result<CallToolResult, McpError> {
+        let info = run_git_info(&self.workspace_dir).await?;
+        let value = serde_json::to_value(info).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize git info: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(value))
+    }
+
+    #[tool(
+        name = "disk_usage",
+        description = "Report the total size in bytes of the workspace (skipping gitignored paths) and of the bash events directory and database."
+    )]
+    async fn disk_usage(&self) -> Result<CallToolResult, McpError> {
+        let workspace_dir = self.workspace_dir.clone();
+        let events_dir = self.bash.events_dir().to_path_buf();
+        let usage =
+            tokio::task::spawn_blocking(move || run_disk_usage(&workspace_dir, &events_dir))
+                .await
+                .map_err(|e| McpError {
+                    code: ErrorCode(-32603),
+                    message: format!("Disk usage computation failed: {}", e).into(),
+                    data: None,
+                })?;
+        let value = serde_json::to_value(usage).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize disk usage: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(value))
+    }
+
+    #[tool(
+        name = "code_stats",
+        description = "Count files and total, code, comment and blank lines per language under a directory (default: the workspace), skipping gitignored paths."
+    )]
+    async fn code_stats(
+        &self,
+        Parameters(args): Parameters<CodeStatsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let workspace_dir = self.workspace_dir.clone();
+        let result = tokio::task::spawn_blocking(move || run_code_stats(&args, &workspace_dir))
+            .await
+            .map_err(|e| McpError {
+                code: ErrorCode(-32603),
+                message: format!("Code stats computation failed: {}", e).into(),
+                data: None,
+            })?;
+        Ok(structured_result(result, |stats| serde_json::json!(stats)))
+    }
+
+    #[tool(
+        name = "get_logs",
+        description = "Return the last N lines (default 100) of the server log file. Only available when the server logs to a file (CODER_MCP_LOG_FILE)."
+    )]
+    async fn get_logs(
+        &self,
+        Parameters(args): Parameters<GetLogsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = match &self.log_file {
+            None => "File logging is not configured. Set CODER_MCP_LOG_FILE to write server logs to a file.".to_string(),
+            Some(path) => match logger::tail_log(path, args.lines.unwrap_or(100)) {
+                Ok(tail) => tail,
+                Err(e) => format!("Error: Failed to read log file {}: {}", path.display(), e),
+            },
+        };
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "get_version",
+        description = "Report the server version, git commit, build timestamp and enabled features."
+    )]
+    async fn get_version(&self) -> Result<CallToolResult, McpError> {
+        let info = serde_json::to_value(version_info()).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize version info: {}", e).into(),
+            data: None,
+        })?;
+        Ok(CallToolResult::structured(info))
+    }
+
+    #[tool(
+        name = "get_capabilities",
+        description = "Report the MCP capabilities this server advertises (tools, resources, prompts, ...)."
+    )]
+    async fn get_capabilities(&self) -> Result<CallToolResult, McpError> {
+        capabilities_result(&self.get_info())
+    }
+}
+
+// Network access is opt-in, so this tool lives in its own router
+#[tool_router(router = fetch_tool_router)]
+impl CoderMcpService {
+    #[tool(
+        name = "fetch_url",
+        description = "Download a URL into a new workspace file. Only allowlisted schemes and hosts can be fetched, downloads are size-limited, and existing files are never overwritten."
+    )]
+    async fn fetch_url(
+        &self,
+        Parameters(args): Parameters<FetchUrlArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(config) = self.fetch.clone() else {
+            return Ok(CallToolResult::success(vec![Content::text(
+                "Error: fetch_url is disabled on this server.",
+            )]));
+        };
+        let output = run_fetch_url(&args, &self.workspace_dir, &config).await?;
+        self.record_mutations(&output, &[(MutationOp::Create, &args.dest)])
+            .await;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+}
+
+/// How undoing or redoing a change affected a file, given whether it existed before and after.
+fn restored_op(existed: bool, exists: bool) -> MutationOp {
+    match (existed, exists) {
+        (false, true) => MutationOp::Create,
+        (true, false) => MutationOp::Delete,
+        _ => MutationOp::Edit,
+    }
+}
+
+/// The capabilities advertised in `info` as a structured tool result.
+fn capabilities_result(info: &ServerInfo) -> Result<CallToolResult, McpError> {
+    let capabilities = serde_json::to_value(&info.capabilities).map_err(|e| McpError {
+        code: ErrorCode(-32603),
+        message: format!("Failed to serialize capabilities: {}", e).into(),
+        data: None,
+    })?;
+    Ok(CallToolResult::structured(capabilities))
+}
+
+/// Tools that only read the workspace or report on the server. Callers with a read-only
+/// capability token are refused every other tool, including ones added later.
+pub(crate) const READ_TOOLS: &[&str] = &[
+    "search_filenames",
+    "search_content",
+    "search_all",
+    "bash_peek",
+    "bash_command_info",
+    "list_bash_history",
+    "slowest_commands",
+    "view_file",
+    "view_with_matches",
+    "error_context",
+    "extract_block",
+    "resolve_import",
+    "list_directory",
+    "list_directory_detailed",
+    "file_stat",
+    "diff_files",
+    "list_mutations",
+    "list_active_edits",
+    "watch_dir",
+    "git_info",
+    "disk_usage",
+    "code_stats",
+    "get_logs",
+    "get_version",
+    "get_capabilities",
+];
+
+/// Dispatches a tool call through the router and records it in the audit log, if any.
+async fn audited_call<S: Send + Sync + 'static>(
+    service: &S,
+    router: &ToolRouter<S>,
+    audit_log: Option<&AuditLog>,
+    request: CallToolRequestParam,
+    context: RequestContext<RoleServer>,
+) -> Result<CallToolResult, McpError> {
+    let Some(audit_log) = audit_log else {
+        return router
+            .call(ToolCallContext::new(service, request, context))
+            .await;
+    };
+    let name = request.name.clone();
+    let arguments = request.arguments.clone();
+    let result = router
+        .call(ToolCallContext::new(service, request, context))
+        .await;
+    audit_log.record(&name, arguments.as_ref(), &result);
+    result
+}
+
+/// Identifies this server to clients. `Implementation::from_build_env` expands inside rmcp,
+/// so it would report rmcp's name and version instead of ours.
+fn server_implementation() -> Implementation {
+    Implementation {
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ..Implementation::from_build_env()
+    }
+}
+
+impl ServerHandler for CoderMcpService {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: server_implementation(),
             instructions: Some("Coder MCP Server providing Bash and File tools".to_string()),
         }
     }
 
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if let Some(tokens) = &self.capability_tokens
+            && !READ_TOOLS.contains(&request.name.as_ref())
+        {
+            let capability = context
+                .extensions
+                .get::<axum::http::request::Parts>()
+                .map_or(Capability::ReadOnly, |parts| {
+                    tokens.capability(&parts.headers)
+                });
+            if capability == Capability::ReadOnly {
+                let result = Ok(CallToolResult::error(vec![Content::text(format!(
+                    "Error: this token is read-only; {} needs a read-write token.",
+                    request.name
+                ))]));
+                if let Some(audit_log) = &self.audit_log {
+                    audit_log.record(&request.name, request.arguments.as_ref(), &result);
+                }
+                return result;
+            }
+        }
+        audited_call(
+            self,
+            &self.tool_router,
+            self.audit_log.as_deref(),
+            request,
+            context,
+        )
+        .await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult {
+            tools: self.tool_router.list_all(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+
     async fn initialize(
         &self,
         _request: InitializeRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<InitializeResult, McpError> {
-        Ok(self.get_info().into())
+        Ok(self.get_info())
     }
 }
 
@@ -289,3 +1709,914 @@ impl ServerHandler for CoderMcpService {
 // Read-Only Service Implementation
 // ===================================
 
+/// The file viewing and search tools offered by both services, implemented once. The
+/// services' `#[tool]` methods delegate here.
+#[derive(Clone)]
+struct ReadTools {
+    workspace_dir: PathBuf,
+    /// Return structured JSON content instead of text where a tool supports both
+    structured_output: bool,
+}
+
+impl ReadTools {
+    fn new(workspace_dir: PathBuf) -> Self {
+        Self {
+            workspace_dir,
+            structured_output: false,
+        }
+    }
+
+    async fn search_filenames(&self, args: GlobArgs) -> Result<CallToolResult, McpError> {
+        if self.structured_output {
+            let result = glob_matches(&args, &self.workspace_dir);
+            return Ok(structured_result(result, |(base, matches, truncated)| {
+                serde_json::json!({
+                    "base_path": base,
+                    "truncated": truncated,
+                    "matches": matches,
+                })
+            }));
+        }
+        let output = run_glob(&args, &self.workspace_dir)?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    async fn search_content(&self, args: GrepArgs) -> Result<CallToolResult, McpError> {
+        if self.structured_output {
+            let result = grep_matches(&args, &self.workspace_dir);
+            return Ok(structured_result(
+                result,
+                |(base, matches, truncated, _)| {
+                    serde_json::json!({
+                        "base_path": base,
+                        "truncated": truncated,
+                        "matches": matches,
+                    })
+                },
+            ));
+        }
+        let output = run_grep(&args, &self.workspace_dir)?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    async fn view_file(&self, args: ViewFileArgs) -> Result<CallToolResult, McpError> {
+        if self.structured_output && args.filter.is_some() {
+            let result = filter_file_lines(&args, &self.workspace_dir);
+            return Ok(structured_result(result, |matches| {
+                let matches: Vec<serde_json::Value> = matches
+                    .into_iter()
+                    .map(|(line, text)| serde_json::json!({ "line": line, "text": text }))
+                    .collect();
+                serde_json::json!({
+                    "path": args.path,
+                    "filter": args.filter,
+                    "matches": matches,
+                })
+            }));
+        }
+        if self.structured_output {
+            let result = view_file_lines(&args, &self.workspace_dir);
+            return Ok(structured_result(result, |(start_line, lines)| {
+                serde_json::json!({
+                    "path": args.path,
+                    "start_line": start_line,
+                    "lines": lines,
+                })
+            }));
+        }
+        let output = run_view_file(&args, &self.workspace_dir).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    async fn list_directory(&self, args: ListDirectoryArgs) -> Result<CallToolResult, McpError> {
+        if self.structured_output {
+            let result = list_directory_entries(&args, &self.workspace_dir);
+            return Ok(structured_result(result, |(entries, truncated)| {
+                serde_json::json!({
+                    "entries": entries,
+                    "truncated": truncated,
+                })
+            }));
+        }
+        let output = run_list_directory(&args, &self.workspace_dir).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    async fn list_directory_detailed(
+        &self,
+        args: ListDirectoryArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let result = run_list_directory_detailed(&args, &self.workspace_dir);
+        Ok(structured_result(result, |(entries, truncated)| {
+            serde_json::json!({
+                "entries": entries,
+                "truncated": truncated,
+            })
+        }))
+    }
+
+    async fn file_stat(&self, args: FileStatArgs) -> Result<CallToolResult, McpError> {
+        let result = run_file_stat(&args, &self.workspace_dir);
+        Ok(structured_result(result, |stat| serde_json::json!(stat)))
+    }
+
+    async fn tree(&self, args: TreeArgs) -> Result<CallToolResult, McpError> {
+        if self.structured_output {
+            let tree = match build_tree(&args, &self.workspace_dir)? {
+                Tree::Text(text) => serde_json::json!(text),
+                Tree::Json(root) => serde_json::json!(root),
+            };
+            return Ok(CallToolResult::structured(
+                serde_json::json!({ "tree": tree }),
+            ));
+        }
+        let output = run_tree(&args, &self.workspace_dir)?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+}
+
+/// Exposes only the tools that cannot modify the workspace.
+#[derive(Clone)]
+pub struct CoderMcpReadOnlyService {
+    read_tools: ReadTools,
+    audit_log: Option<Arc<AuditLog>>,
+    tool_router: ToolRouter<CoderMcpReadOnlyService>,
+}
+
+/// Converts the result of a read helper into structured content; helper errors become
+/// `{ "error": ... }` error results.
+fn structured_result<T, F>(result: Result<T, String>, to_json: F) -> CallToolResult
+where
+    F: FnOnce(T) -> serde_json::Value,
+{
+    match result {
+        Ok(value) => CallToolResult::structured(to_json(value)),
+        Err(message) => CallToolResult::structured_error(serde_json::json!({ "error": message })),
+    }
+}
+
+#[tool_router]
+impl CoderMcpReadOnlyService {
+    pub fn new(workspace_dir: PathBuf) -> Self {
+        Self {
+            read_tools: ReadTools::new(workspace_dir),
+            audit_log: None,
+            tool_router: Self::tool_router(),
+        }
+    }
+
+    /// Makes the tools return structured JSON content instead of text.
+    pub fn with_structured_output(mut self, structured_output: bool) -> Self {
+        self.read_tools.structured_output = structured_output;
+        self
+    }
+
+    /// Records every tool call in `audit_log`.
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    #[tool(
+        name = "search_filenames",
+        description = "Fast file pattern matching tool. Finds files by name patterns (e.g. '**/*.js'). Returns matching file paths."
+    )]
+    async fn search_filenames(
+        &self,
+        Parameters(args): Parameters<GlobArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.search_filenames(args).await
+    }
+
+    #[tool(
+        name = "search_content",
+        description = "Fast content search tool. Searches file contents using regex. Returns matching file paths, or each matching line as path:line_number: text when show_lines or a context count is set."
+    )]
+    async fn search_content(
+        &self,
+        Parameters(args): Parameters<GrepArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.search_content(args).await
+    }
+
+    #[tool(
+        name = "view_file",
+        description = "Read file contents with optional line range. Returns file content with line numbers. Set filter to a regex to return only matching lines in one pass over the file. .gz files are decompressed transparently."
+    )]
+    async fn view_file(
+        &self,
+        Parameters(args): Parameters<ViewFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.view_file(args).await
+    }
+
+    #[tool(
+        name = "list_directory",
+        description = "List contents of a directory, excluding hidden files unless include_hidden is set. Set recursive to list nested entries as relative paths, optionally limited by max_depth."
+    )]
+    async fn list_directory(
+        &self,
+        Parameters(args): Parameters<ListDirectoryArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.list_directory(args).await
+    }
+
+    #[tool(
+        name = "list_directory_detailed",
+        description = "List contents of a directory as structured { name, type, size_bytes, line_count, modified } entries. Takes the same arguments and filtering as list_directory."
+    )]
+    async fn list_directory_detailed(
+        &self,
+        Parameters(args): Parameters<ListDirectoryArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.list_directory_detailed(args).await
+    }
+
+    #[tool(
+        name = "file_stat",
+        description = "Report metadata for one path as structured { path, type, size_bytes, line_count, modified, executable, target } without reading it into the conversation. type is file, dir or symlink; modified is RFC 3339."
+    )]
+    async fn file_stat(
+        &self,
+        Parameters(args): Parameters<FileStatArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.file_stat(args).await
+    }
+
+    #[tool(
+        name = "tree",
+        description = "Show the directory tree of a path, with optional depth limit and comma-separated exclude patterns. Hidden entries are skipped unless include_hidden is set. Set format to \"json\" for a nested { name, type, children } structure."
+    )]
+    async fn tree(
+        &self,
+        Parameters(args): Parameters<TreeArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        self.read_tools.tree(args).await
+    }
+
+    #[tool(
+        name = "get_capabilities",
+        description = "Report the MCP capabilities this server advertises (tools, resources, prompts, ...)."
+    )]
+    async fn get_capabilities(&self) -> Result<CallToolResult, McpError> {
+        capabilities_result(&self.get_info())
+    }
+}
+
+impl ServerHandler for CoderMcpReadOnlyService {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: server_implementation(),
+            instructions: Some("Coder MCP Server providing read-only File tools".to_string()),
+        }
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        audited_call(
+            self,
+            &self.tool_router,
+            self.audit_log.as_deref(),
+            request,
+            context,
+        )
+        .await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult {
+            tools: self.tool_router.list_all(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+
+    async fn initialize(
+        &self,
+        _request: InitializeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, McpError> {
+        Ok(self.get_info())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_list_tools_includes_schemas() {
+        let tools = CoderMcpService::list_tools();
+        let view_file = tools
+            .iter()
+            .find(|t| t.name == "view_file")
+            .expect("view_file is listed");
+        assert!(view_file.description.is_some());
+        let properties = view_file.input_schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("path"));
+        assert!(properties.contains_key("start_line"));
+        assert_eq!(
+            view_file.input_schema["required"],
+            serde_json::json!(["path"])
+        );
+
+        let mut names: Vec<_> = tools.iter().map(|t| t.name.to_string()).collect();
+        names.dedup();
+        assert_eq!(names.len(), tools.len());
+        assert!(names.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_read_tools_are_offered() {
+        let mut router = CoderMcpService::tool_router();
+        router.merge(CoderMcpService::fetch_tool_router());
+        let names: Vec<String> = router
+            .list_all()
+            .into_iter()
+            .map(|tool| tool.name.to_string())
+            .collect();
+        for tool in READ_TOOLS {
+            assert!(names.iter().any(|name| name == tool), "{}", tool);
+        }
+    }
+
+    #[test]
+    fn test_server_info_reports_crate_version() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let read_only = CoderMcpReadOnlyService::new(dir.path().to_path_buf());
+        for info in [service.get_info(), read_only.get_info()] {
+            assert_eq!(info.server_info.name, "coder-mcp");
+            assert_eq!(info.server_info.version, env!("CARGO_PKG_VERSION"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_tools_match_between_services() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let read_only = CoderMcpReadOnlyService::new(dir.path().to_path_buf());
+
+        let args = ViewFileArgs {
+            path: "a.txt".to_string(),
+            start_line: Some(2),
+            ..Default::default()
+        };
+        let full = service.view_file(Parameters(args.clone())).await.unwrap();
+        let limited = read_only.view_file(Parameters(args)).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(&full).unwrap(),
+            serde_json::to_value(&limited).unwrap()
+        );
+        assert_eq!(
+            full.content[0].as_text().unwrap().text,
+            "     2\ttwo\n     3\tthree"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_url_is_opt_in() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        assert!(!service.tool_router.has_route("fetch_url"));
+        assert!(!CoderMcpService::list_tools()
+            .iter()
+            .any(|t| t.name == "fetch_url"));
+
+        let service = service.with_fetch_config(FetchConfig::new(vec!["example.com".into()]));
+        assert!(service.tool_router.has_route("fetch_url"));
+    }
+
+    #[tokio::test]
+    async fn test_bash_check_compares_exit_code() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let check = |command: &str, expect_code: Option<i32>| {
+            Parameters(BashCheckArgs {
+                command: command.to_string(),
+                expect_code,
+            })
+        };
+
+        for (args, success, exit_code) in [
+            (check("true", None), true, 0),
+            (check("false", None), false, 1),
+            (check("(exit 3)", Some(3)), true, 3),
+            (check("true", Some(3)), false, 0),
+        ] {
+            let result = service.bash_check(args).await.unwrap();
+            assert_eq!(
+                result.structured_content.unwrap(),
+                serde_json::json!({ "success": success, "exit_code": exit_code })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_command_returns_partial_output() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let cmd = service.bash.start_bash_command(ExecuteBashRequest {
+            command: "echo first; sleep 2; echo second".to_string(),
+            cwd: None,
+            timeout: Some(10),
+            abort_on: None,
+        });
+
+        let progress = service
+            .wait_for_command(cmd.id, Duration::from_millis(1000))
+            .await
+            .unwrap();
+        assert!(progress.is_running);
+        assert!(progress.output.contains("first"), "{}", progress.output);
+        assert!(!progress.output.contains("second"));
+
+        let progress = service
+            .wait_for_command(cmd.id, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(progress.exit_code, Some(0));
+        assert!(progress.output.contains("second"), "{}", progress.output);
+    }
+
+    #[tokio::test]
+    async fn test_bash_rejects_invalid_abort_on() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let args = || BashArgs {
+            command: "echo hi".to_string(),
+            cwd: None,
+            timeout: Some(5),
+            abort_on: Some("[".to_string()),
+        };
+        for result in [
+            service.bash(Parameters(args())).await.unwrap(),
+            service.bash_start(Parameters(args())).await.unwrap(),
+        ] {
+            let text = &result.content[0].as_text().unwrap().text;
+            assert!(
+                text.starts_with("Error: Invalid abort_on regex '['"),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_returns_recent_lines() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let text = |result: CallToolResult| result.content[0].as_text().unwrap().text.clone();
+        let args = || Parameters(GetLogsArgs { lines: Some(2) });
+
+        let output = text(service.get_logs(args()).await.unwrap());
+        assert!(output.contains("not configured"), "{}", output);
+
+        let log_path = dir.path().join("logs/server.log");
+        let file = logger::open_log_file(&log_path).unwrap();
+        // Errors pass the filter even without RUST_LOG
+        tracing::subscriber::with_default(logger::subscriber(Some(file)), || {
+            for i in 1..=3 {
+                tracing::error!("log line {}", i);
+            }
+        });
+
+        let service = service.with_log_file(log_path);
+        let output = text(service.get_logs(args()).await.unwrap());
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", output);
+        assert!(lines[0].ends_with("ERROR log line 2"), "{}", output);
+        assert!(lines[1].ends_with("ERROR log line 3"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_list_bash_history_after_two_commands() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let text = |result: CallToolResult| result.content[0].as_text().unwrap().text.clone();
+        let history = |command_id: Option<String>| {
+            Parameters(BashHistoryArgs {
+                command_id,
+                limit: None,
+            })
+        };
+
+        let output = text(service.list_bash_history(history(None)).await.unwrap());
+        assert_eq!(output, "No bash commands have been run yet.");
+
+        for command in [
+            "echo first",
+            "echo second; exit_with() { return 3; }; exit_with",
+        ] {
+            service
+                .bash(Parameters(BashArgs {
+                    command: command.to_string(),
+                    cwd: None,
+                    timeout: Some(5),
+                    abort_on: None,
+                }))
+                .await
+                .unwrap();
+        }
+
+        let output = text(service.list_bash_history(history(None)).await.unwrap());
+        let entries: Vec<&str> = output.split("\n\n").collect();
+        assert_eq!(entries.len(), 2, "{}", output);
+        assert!(
+            entries[0].contains("(exit 0)\n$ echo first\n  first"),
+            "{}",
+            output
+        );
+        assert!(entries[1].contains("(exit 3)"), "{}", output);
+        assert!(entries[1].contains("  second"), "{}", output);
+
+        let first_id = entries[0].split_whitespace().nth(1).unwrap().to_string();
+        let output = text(
+            service
+                .list_bash_history(history(Some(first_id)))
+                .await
+                .unwrap(),
+        );
+        assert!(output.contains("echo first"), "{}", output);
+        assert!(!output.contains("second"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_new_edit_invalidates_redo() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        let edit = |old: &str, new: &str| {
+            Parameters(StrReplaceArgs {
+                path: "a.txt".to_string(),
+                old_str: old.to_string(),
+                new_str: new.to_string(),
+                ..Default::default()
+            })
+        };
+        let path = || "a.txt".to_string();
+
+        service.str_replace(edit("one", "two")).await.unwrap();
+        service
+            .undo_edit(Parameters(UndoEditArgs { path: path() }))
+            .await
+            .unwrap();
+        service.str_replace(edit("one", "three")).await.unwrap();
+
+        let result = service
+            .redo_edit(Parameters(RedoEditArgs { path: path() }))
+            .await
+            .unwrap();
+        let output = &result.content[0].as_text().unwrap().text;
+        assert!(output.starts_with("Error: No undone edit"), "{}", output);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "three\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_active_edits_counts_versions() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        for path in ["a.txt", "b.txt", "untouched.txt"] {
+            fs::write(dir.path().join(path), "one\n").unwrap();
+        }
+        let edit = |path: &str, old: &str, new: &str| {
+            Parameters(StrReplaceArgs {
+                path: path.to_string(),
+                old_str: old.to_string(),
+                new_str: new.to_string(),
+                ..Default::default()
+            })
+        };
+        for (path, old, new) in [
+            ("a.txt", "one", "two"),
+            ("a.txt", "two", "three"),
+            ("b.txt", "one", "two"),
+        ] {
+            service.str_replace(edit(path, old, new)).await.unwrap();
+        }
+
+        let result = service.list_active_edits().await.unwrap();
+        assert_eq!(
+            result.structured_content.unwrap(),
+            serde_json::json!({
+                "files": [
+                    { "path": "a.txt", "versions": 2 },
+                    { "path": "b.txt", "versions": 1 },
+                ]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_limit_evicts_least_recently_edited() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf()).with_history_limit(3);
+        let edit = |path: &str, old: &str, new: &str| {
+            Parameters(StrReplaceArgs {
+                path: path.to_string(),
+                old_str: old.to_string(),
+                new_str: new.to_string(),
+                ..Default::default()
+            })
+        };
+        for path in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(path), "one\n").unwrap();
+        }
+
+        service
+            .str_replace(edit("a.txt", "one", "two"))
+            .await
+            .unwrap();
+        service
+            .str_replace(edit("b.txt", "one", "two"))
+            .await
+            .unwrap();
+        service
+            .str_replace(edit("a.txt", "two", "three"))
+            .await
+            .unwrap();
+        // b.txt is now the least recently edited file
+        service
+            .str_replace(edit("c.txt", "one", "two"))
+            .await
+            .unwrap();
+
+        let history = service.editor_history.lock().await;
+        assert!(!history.contains_key(&dir.path().join("b.txt")));
+        assert_eq!(history[&dir.path().join("a.txt")].len(), 2);
+        assert_eq!(history[&dir.path().join("c.txt")].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_history_limit_trims_single_file() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf()).with_history_limit(3);
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "0\n").unwrap();
+
+        for i in 0..6 {
+            let args = Parameters(StrReplaceArgs {
+                path: "a.txt".to_string(),
+                old_str: format!("{}\n", i),
+                new_str: format!("{}\n", i + 1),
+                ..Default::default()
+            });
+            service.str_replace(args).await.unwrap();
+        }
+        assert_eq!(service.editor_history.lock().await[&path].len(), 3);
+
+        // Undo moves versions to the redo stack; together they stay within the limit
+        let undo = || {
+            Parameters(UndoEditArgs {
+                path: "a.txt".to_string(),
+            })
+        };
+        for _ in 0..3 {
+            service.undo_edit(undo()).await.unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "3\n");
+        let output = service.undo_edit(undo()).await.unwrap();
+        assert!(output.content[0]
+            .as_text()
+            .unwrap()
+            .text
+            .starts_with("Error"));
+        let history = service.editor_history.lock().await;
+        let redo_history = service.redo_history.lock().await;
+        let total = history.get(&path).map_or(0, Vec::len) + redo_history[&path].len();
+        assert!(total <= 3, "{}", total);
+    }
+
+    #[tokio::test]
+    async fn test_restore_checkpoint_rolls_back_edits() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let text = |result: CallToolResult| result.content[0].as_text().unwrap().text.clone();
+        let edit = |path: &str, old: &str, new: &str| {
+            Parameters(StrReplaceArgs {
+                path: path.to_string(),
+                old_str: old.to_string(),
+                new_str: new.to_string(),
+                ..Default::default()
+            })
+        };
+
+        for path in ["a.txt", "b.txt"] {
+            let args = CreateFileArgs {
+                path: path.to_string(),
+                content: "one\n".to_string(),
+                executable: None,
+            };
+            service.create_file(Parameters(args)).await.unwrap();
+        }
+        service
+            .str_replace(edit("a.txt", "one", "two"))
+            .await
+            .unwrap();
+        service
+            .str_replace(edit("b.txt", "one", "two"))
+            .await
+            .unwrap();
+
+        let output = text(service.create_checkpoint().await.unwrap());
+        assert_eq!(output, "Created checkpoint-1 with 2 file(s).");
+
+        service
+            .str_replace(edit("a.txt", "two", "three"))
+            .await
+            .unwrap();
+        service
+            .str_replace(edit("b.txt", "two", "three"))
+            .await
+            .unwrap();
+        let restore = |id: &str| Parameters(RestoreCheckpointArgs { id: id.to_string() });
+        let output = text(
+            service
+                .restore_checkpoint(restore("checkpoint-1"))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(output, "Restored 2 file(s).");
+        for path in ["a.txt", "b.txt"] {
+            assert_eq!(fs::read_to_string(dir.path().join(path)).unwrap(), "two\n");
+        }
+
+        let output = text(
+            service
+                .restore_checkpoint(restore("checkpoint-9"))
+                .await
+                .unwrap(),
+        );
+        assert!(output.starts_with("Error: No checkpoint"));
+    }
+
+    #[tokio::test]
+    async fn test_get_capabilities_reports_advertised_capabilities() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+        let readonly = CoderMcpReadOnlyService::new(dir.path().to_path_buf());
+
+        for (result, info) in [
+            (
+                service.get_capabilities().await.unwrap(),
+                service.get_info(),
+            ),
+            (
+                readonly.get_capabilities().await.unwrap(),
+                readonly.get_info(),
+            ),
+        ] {
+            let capabilities = result.structured_content.unwrap();
+            assert!(capabilities.get("tools").is_some());
+            assert!(capabilities.get("resources").is_none());
+            assert!(capabilities.get("prompts").is_none());
+            assert_eq!(
+                capabilities,
+                serde_json::to_value(&info.capabilities).unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readonly_structured_list_directory() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        let service =
+            CoderMcpReadOnlyService::new(dir.path().to_path_buf()).with_structured_output(true);
+
+        let result = service
+            .list_directory(Parameters(ListDirectoryArgs {
+                path: ".".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        let structured = result.structured_content.unwrap();
+        assert_eq!(
+            structured["entries"],
+            serde_json::json!([
+                { "name": "main.rs", "type": "file", "line_count": 1 },
+                { "name": "src", "type": "dir", "line_count": null },
+            ])
+        );
+        assert_eq!(structured["truncated"], false);
+
+        // Text output stays the default
+        let service = CoderMcpReadOnlyService::new(dir.path().to_path_buf());
+        let result = service
+            .list_directory(Parameters(ListDirectoryArgs {
+                path: ".".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert!(result.structured_content.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_readonly_structured_tree() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Errors/logs")).unwrap();
+        let service =
+            CoderMcpReadOnlyService::new(dir.path().to_path_buf()).with_structured_output(true);
+        let args = |format: &str| TreeArgs {
+            path: Some("Errors".to_string()),
+            exclude: None,
+            max_depth: None,
+            truncate: None,
+            include_hidden: None,
+            format: Some(format.to_string()),
+        };
+
+        // A root named like an error message is still a tree
+        let result = service.tree(Parameters(args("text"))).await.unwrap();
+        assert_ne!(result.is_error, Some(true));
+        let structured = result.structured_content.unwrap();
+        assert!(structured["tree"].as_str().unwrap().starts_with("Errors\n"));
+
+        // The JSON format is embedded as an object, not a string
+        let result = service.tree(Parameters(args("json"))).await.unwrap();
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["tree"]["name"], "Errors");
+        assert_eq!(structured["tree"]["children"][0]["name"], "logs");
+    }
+
+    #[tokio::test]
+    async fn test_list_mutations_records_write_tools_in_order() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().join(".coder_mcp"), None, None);
+        let service = CoderMcpService::new(bash, dir.path().to_path_buf());
+
+        service
+            .create_file(Parameters(CreateFileArgs {
+                path: "notes.txt".to_string(),
+                content: "hello\n".to_string(),
+                executable: None,
+            }))
+            .await
+            .unwrap();
+        service
+            .str_replace(Parameters(StrReplaceArgs {
+                path: "notes.txt".to_string(),
+                old_str: "hello".to_string(),
+                new_str: "goodbye".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        // Failed edits are not logged
+        service
+            .str_replace(Parameters(StrReplaceArgs {
+                path: "notes.txt".to_string(),
+                old_str: "missing".to_string(),
+                new_str: "x".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        service
+            .delete_file(Parameters(DeleteFileArgs {
+                path: "notes.txt".to_string(),
+            }))
+            .await
+            .unwrap();
+
+        let result = service.list_mutations().await.unwrap();
+        let mutations = result.structured_content.unwrap()["mutations"].clone();
+        let entries = mutations.as_array().unwrap();
+        let ops: Vec<&str> = entries.iter().map(|m| m["op"].as_str().unwrap()).collect();
+        assert_eq!(ops, vec!["create", "edit", "delete"]);
+        assert!(entries.iter().all(|m| m["path"] == "notes.txt"));
+        assert!(entries.iter().all(|m| m["timestamp"].is_string()));
+    }
+}
@@ -0,0 +1,248 @@
+use futures::Stream;
+use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+use rmcp::transport::common::server_side_http::ServerSseMessage;
+use rmcp::transport::streamable_http_server::session::{
+    local::{LocalSessionManager, LocalSessionManagerError},
+    SessionId, SessionManager,
+};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Activity {
+    last_seen: Instant,
+    /// Open request or SSE streams; a session with any of these is never idle
+    in_flight: usize,
+}
+
+type ActivityMap = Arc<Mutex<HashMap<SessionId, Activity>>>;
+
+/// A `LocalSessionManager` that closes sessions once they have been idle for
+/// `idle_timeout`. A long-running tool call keeps its session alive until it returns.
+/// Without a timeout sessions live until the client deletes them.
+#[derive(Debug)]
+pub struct IdleSessionManager {
+    inner: LocalSessionManager,
+    idle_timeout: Option<Duration>,
+    activity: ActivityMap,
+}
+
+impl IdleSessionManager {
+    pub fn new(idle_timeout: Option<Duration>) -> Self {
+        Self {
+            inner: LocalSessionManager::default(),
+            idle_timeout,
+            activity: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Spawns a task that periodically reaps idle sessions. The task stops once
+    /// the manager is dropped.
+    pub fn spawn_reaper(self: &Arc<Self>) {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return;
+        };
+        let manager: Weak<Self> = Arc::downgrade(self);
+        let period = (idle_timeout / 2).max(Duration::from_millis(10));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                manager.reap_idle().await;
+            }
+        });
+    }
+
+    /// Closes every session that has no open streams and has been idle for too long.
+    pub async fn reap_idle(&self) -> usize {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return 0;
+        };
+        let expired: Vec<SessionId> = {
+            let mut activity = self.activity.lock().unwrap();
+            let expired: Vec<SessionId> = activity
+                .iter()
+                .filter(|(_, a)| a.in_flight == 0 && a.last_seen.elapsed() >= idle_timeout)
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in &expired {
+                activity.remove(id);
+            }
+            expired
+        };
+        for id in &expired {
+            if let Err(e) = self.inner.close_session(id).await {
+                tracing::warn!("Failed to close idle session {}: {}", id, e);
+            } else {
+                tracing::info!("Closed idle session {}", id);
+            }
+        }
+        expired.len()
+    }
+
+    pub async fn session_count(&self) -> usize {
+        self.inner.sessions.read().await.len()
+    }
+
+    fn touch(&self, id: &SessionId) {
+        if let Some(a) = self.activity.lock().unwrap().get_mut(id) {
+            a.last_seen = Instant::now();
+        }
+    }
+
+    fn begin(&self, id: &SessionId) -> ActivityGuard {
+        if let Some(a) = self.activity.lock().unwrap().get_mut(id) {
+            a.last_seen = Instant::now();
+            a.in_flight += 1;
+        }
+        ActivityGuard {
+            id: id.clone(),
+            activity: self.activity.clone(),
+        }
+    }
+}
+
+/// Marks a session busy for as long as it is alive.
+struct ActivityGuard {
+    id: SessionId,
+    activity: ActivityMap,
+}
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        if let Some(a) = self.activity.lock().unwrap().get_mut(&self.id) {
+            a.last_seen = Instant::now();
+            a.in_flight = a.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+/// A stream that holds an `ActivityGuard` until it is dropped.
+struct TrackedStream {
+    inner: Pin<Box<dyn Stream<Item = ServerSseMessage> + Send + Sync>>,
+    _guard: ActivityGuard,
+}
+
+impl Stream for TrackedStream {
+    type Item = ServerSseMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl SessionManager for IdleSessionManager {
+    type Error = LocalSessionManagerError;
+    type Transport = <LocalSessionManager as SessionManager>::Transport;
+
+    async fn create_session(&self) -> Result<(SessionId, Self::Transport), Self::Error> {
+        let (id, transport) = self.inner.create_session().await?;
+        self.activity.lock().unwrap().insert(
+            id.clone(),
+            Activity {
+                last_seen: Instant::now(),
+                in_flight: 0,
+            },
+        );
+        Ok((id, transport))
+    }
+
+    async fn initialize_session(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<ServerJsonRpcMessage, Self::Error> {
+        let _guard = self.begin(id);
+        self.inner.initialize_session(id, message).await
+    }
+
+    async fn has_session(&self, id: &SessionId) -> Result<bool, Self::Error> {
+        if !self.activity.lock().unwrap().contains_key(id) {
+            return Ok(false);
+        }
+        self.inner.has_session(id).await
+    }
+
+    async fn close_session(&self, id: &SessionId) -> Result<(), Self::Error> {
+        self.activity.lock().unwrap().remove(id);
+        self.inner.close_session(id).await
+    }
+
+    async fn create_stream(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        let guard = self.begin(id);
+        let stream = self.inner.create_stream(id, message).await?;
+        Ok(TrackedStream {
+            inner: Box::pin(stream),
+            _guard: guard,
+        })
+    }
+
+    async fn accept_message(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<(), Self::Error> {
+        self.touch(id);
+        self.inner.accept_message(id, message).await
+    }
+
+    async fn create_standalone_stream(
+        &self,
+        id: &SessionId,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        let guard = self.begin(id);
+        let stream = self.inner.create_standalone_stream(id).await?;
+        Ok(TrackedStream {
+            inner: Box::pin(stream),
+            _guard: guard,
+        })
+    }
+
+    async fn resume(
+        &self,
+        id: &SessionId,
+        last_event_id: String,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        let guard = self.begin(id);
+        let stream = self.inner.resume(id, last_event_id).await?;
+        Ok(TrackedStream {
+            inner: Box::pin(stream),
+            _guard: guard,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reap_idle_skips_busy_sessions() {
+        let manager = IdleSessionManager::new(Some(Duration::from_millis(50)));
+        let (idle, _idle_transport) = manager.create_session().await.unwrap();
+        let (busy, _busy_transport) = manager.create_session().await.unwrap();
+        let guard = manager.begin(&busy);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.reap_idle().await, 1);
+        assert!(!manager.has_session(&idle).await.unwrap());
+        assert!(manager.has_session(&busy).await.unwrap());
+
+        // Finishing the request restarts the idle clock
+        drop(guard);
+        assert_eq!(manager.reap_idle().await, 0);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.reap_idle().await, 1);
+        assert_eq!(manager.session_count().await, 0);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::models::{Mutation, MutationOp};
+use crate::tools::utils;
+use rmcp::schemars;
+use rmcp::ErrorData as McpError;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct RestoreCheckpointArgs {
+    /// ID returned by `create_checkpoint`
+    pub id: String,
+}
+
+/// Contents of the files touched this session, taken at one point in time.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// Content per workspace-relative path; `None` if the file did not exist
+    files: BTreeMap<String, Option<Vec<u8>>>,
+    /// Length of the mutation log when the checkpoint was taken
+    mutation_count: usize,
+}
+
+impl Checkpoint {
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+}
+
+/// Snapshots every file named in the mutation log.
+pub fn create_checkpoint(
+    mutations: &[Mutation],
+    workspace_dir: &Path,
+) -> Result<Checkpoint, McpError> {
+    let paths: BTreeSet<&str> = mutations.iter().map(|m| m.path.as_str()).collect();
+    let mut files = BTreeMap::new();
+    for rel in paths {
+        let path = utils::resolve_within_workspace(workspace_dir, rel)?;
+        let content = if path.is_file() {
+            Some(fs::read(&path).map_err(|e| McpError {
+                code: rmcp::model::ErrorCode(-32603),
+                message: format!("Failed to read {}: {}", path.display(), e).into(),
+                data: None,
+            })?)
+        } else {
+            None
+        };
+        files.insert(rel.to_string(), content);
+    }
+    Ok(Checkpoint {
+        files,
+        mutation_count: mutations.len(),
+    })
+}
+
+/// Puts every checkpointed file back into its recorded state. Files first created after the
+/// checkpoint are deleted; other files first touched afterwards have no recorded state and are
+/// left alone. Returns the summary and the changes made, for the mutation log.
+pub fn restore_checkpoint(
+    checkpoint: &Checkpoint,
+    mutations: &[Mutation],
+    workspace_dir: &Path,
+) -> Result<(String, Vec<(MutationOp, String)>), McpError> {
+    let mut targets: BTreeMap<&str, Option<&[u8]>> = checkpoint
+        .files
+        .iter()
+        .map(|(rel, content)| (rel.as_str(), content.as_deref()))
+        .collect();
+    let mut untracked = Vec::new();
+    for mutation in mutations.iter().skip(checkpoint.mutation_count) {
+        let rel = mutation.path.as_str();
+        if targets.contains_key(rel) || untracked.contains(&rel) {
+            continue;
+        }
+        if mutation.op == MutationOp::Create {
+            targets.insert(rel, None);
+        } else {
+            untracked.push(rel);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (rel, content) in targets {
+        let path = utils::resolve_within_workspace(workspace_dir, rel)?;
+        let result = match content {
+            Some(content) => {
+                if fs::read(&path).is_ok_and(|current| current == content) {
+                    continue;
+                }
+                let op = if path.exists() {
+                    MutationOp::Edit
+                } else {
+                    MutationOp::Create
+                };
+                path.parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&path, content))
+                    .map(|_| op)
+            }
+            None if path.is_file() => fs::remove_file(&path).map(|_| MutationOp::Delete),
+            None => continue,
+        };
+        match result {
+            Ok(op) => changes.push((op, rel.to_string())),
+            Err(e) => {
+                let message = format!(
+                    "Error: Failed to restore {}: {}. {} file(s) were already restored.",
+                    path.display(),
+                    e,
+                    changes.len()
+                );
+                return Ok((utils::write_error(&e, message), changes));
+            }
+        }
+    }
+
+    let mut output = format!("Restored {} file(s).", changes.len());
+    if !untracked.is_empty() {
+        output.push_str(&format!(
+            " Left unchanged (first modified after the checkpoint): {}",
+            untracked.join(", ")
+        ));
+    }
+    Ok((output, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn mutation(op: MutationOp, path: &str) -> Mutation {
+        Mutation {
+            op,
+            path: path.to_string(),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_restore_checkpoint_deletes_files_created_later() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a1").unwrap();
+        let mut log = vec![mutation(MutationOp::Edit, "a.txt")];
+        let checkpoint = create_checkpoint(&log, dir.path()).unwrap();
+        assert_eq!(checkpoint.file_count(), 1);
+
+        fs::write(dir.path().join("a.txt"), "a2").unwrap();
+        fs::write(dir.path().join("new.txt"), "new").unwrap();
+        fs::write(dir.path().join("other.txt"), "other2").unwrap();
+        log.push(mutation(MutationOp::Edit, "a.txt"));
+        log.push(mutation(MutationOp::Create, "new.txt"));
+        log.push(mutation(MutationOp::Edit, "other.txt"));
+
+        let (output, changes) = restore_checkpoint(&checkpoint, &log, dir.path()).unwrap();
+        assert!(output.starts_with("Restored 2 file(s)."), "{}", output);
+        assert!(output.contains("other.txt"));
+        assert_eq!(
+            changes,
+            vec![
+                (MutationOp::Edit, "a.txt".to_string()),
+                (MutationOp::Delete, "new.txt".to_string()),
+            ]
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "a1");
+        assert!(!dir.path().join("new.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("other.txt")).unwrap(),
+            "other2"
+        );
+    }
+}
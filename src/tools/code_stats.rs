@@ -0,0 +1,213 @@
+use ignore::WalkBuilder;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::tools::utils;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct CodeStatsArgs {
+    /// Directory to analyze, relative to the workspace (default: the workspace root)
+    pub path: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    /// All lines, i.e. `code + comment + blank`
+    pub lines: usize,
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CodeStats {
+    /// Sorted by code lines, largest first
+    pub languages: Vec<LanguageStats>,
+    pub total: LanguageStats,
+}
+
+/// Maps a file extension to a language name and its line comment prefix.
+fn language_for(ext: &str) -> Option<(&'static str, Option<&'static str>)> {
+    let language = match ext {
+        "rs" => ("Rust", Some("//")),
+        "py" | "pyi" => ("Python", Some("#")),
+        "js" | "jsx" | "mjs" | "cjs" => ("JavaScript", Some("//")),
+        "ts" | "tsx" | "mts" | "cts" => ("TypeScript", Some("//")),
+        "go" => ("Go", Some("//")),
+        "java" => ("Java", Some("//")),
+        "kt" | "kts" => ("Kotlin", Some("//")),
+        "swift" => ("Swift", Some("//")),
+        "c" | "h" => ("C", Some("//")),
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => ("C++", Some("//")),
+        "cs" => ("C#", Some("//")),
+        "rb" => ("Ruby", Some("#")),
+        "php" => ("PHP", Some("//")),
+        "sh" | "bash" | "zsh" => ("Shell", Some("#")),
+        "sql" => ("SQL", Some("--")),
+        "html" | "htm" => ("HTML", None),
+        "css" | "scss" => ("CSS", None),
+        "md" => ("Markdown", None),
+        "json" => ("JSON", None),
+        "toml" => ("TOML", Some("#")),
+        "yaml" | "yml" => ("YAML", Some("#")),
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Counts lines of code per language under `path`, respecting ignore files.
+/// Lines starting with the language's line comment prefix count as comments;
+/// block comments are counted as code.
+pub fn run_code_stats(args: &CodeStatsArgs, workspace_dir: &Path) -> Result<CodeStats, String> {
+    let root = utils::search_base(workspace_dir, args.path.as_deref())?;
+    if !root.is_dir() {
+        return Err(format!("Directory not found: {}", root.display()));
+    }
+
+    let mut by_language: HashMap<&'static str, LanguageStats> = HashMap::new();
+    let walker = WalkBuilder::new(&root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|e| e.file_name() != ".git")
+        .build();
+    for entry in walker.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Some((language, comment_prefix)) = entry
+            .path()
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| language_for(&e.to_lowercase()))
+        else {
+            continue;
+        };
+        // Skip binary or otherwise unreadable files
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+
+        let stats = by_language
+            .entry(language)
+            .or_insert_with(|| LanguageStats {
+                language: language.to_string(),
+                ..Default::default()
+            });
+        stats.files += 1;
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                stats.blank += 1;
+            } else if comment_prefix.is_some_and(|p| trimmed.starts_with(p)) {
+                stats.comment += 1;
+            } else {
+                stats.code += 1;
+            }
+            stats.lines += 1;
+        }
+    }
+
+    let mut languages: Vec<LanguageStats> = by_language.into_values().collect();
+    languages.sort_by(|a, b| b.code.cmp(&a.code).then(a.language.cmp(&b.language)));
+
+    let mut total = LanguageStats {
+        language: "Total".to_string(),
+        ..Default::default()
+    };
+    for stats in &languages {
+        total.files += stats.files;
+        total.lines += stats.lines;
+        total.code += stats.code;
+        total.comment += stats.comment;
+        total.blank += stats.blank;
+    }
+
+    Ok(CodeStats { languages, total })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_code_stats_mixed_fixture() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("src/main.rs"),
+            "// entry point\nfn main() {\n\n    println!(\"hi\");\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+        fs::write(
+            dir.path().join("tool.py"),
+            "# helper\nimport os\n\n\ndef f():\n    return os.sep\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("notes.txt"), "not code\n").unwrap();
+        fs::write(dir.path().join(".gitignore"), "build/\n").unwrap();
+        fs::create_dir(dir.path().join("build")).unwrap();
+        fs::write(dir.path().join("build/gen.rs"), "fn generated() {}\n").unwrap();
+
+        let stats = run_code_stats(&CodeStatsArgs { path: None }, dir.path()).unwrap();
+        assert_eq!(
+            stats.languages,
+            vec![
+                LanguageStats {
+                    language: "Rust".to_string(),
+                    files: 2,
+                    lines: 6,
+                    code: 4,
+                    comment: 1,
+                    blank: 1,
+                },
+                LanguageStats {
+                    language: "Python".to_string(),
+                    files: 1,
+                    lines: 6,
+                    code: 3,
+                    comment: 1,
+                    blank: 2,
+                },
+            ]
+        );
+        assert_eq!(stats.total.files, 3);
+        assert_eq!(stats.total.lines, 12);
+
+        let sub = run_code_stats(
+            &CodeStatsArgs {
+                path: Some("src".to_string()),
+            },
+            dir.path(),
+        )
+        .unwrap();
+        assert_eq!(sub.total.files, 2);
+
+        let missing = CodeStatsArgs {
+            path: Some("nope".to_string()),
+        };
+        assert!(run_code_stats(&missing, dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_code_stats_stays_inside_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir_all(dir.path().join("outside")).unwrap();
+        fs::create_dir(&workspace).unwrap();
+
+        for path in ["../outside", dir.path().to_str().unwrap()] {
+            let args = CodeStatsArgs {
+                path: Some(path.to_string()),
+            };
+            let err = run_code_stats(&args, &workspace).unwrap_err();
+            assert!(err.contains("outside the workspace"), "{}", err);
+        }
+    }
+}
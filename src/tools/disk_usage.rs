@@ -0,0 +1,82 @@
+use ignore::WalkBuilder;
+use serde::Serialize;
+use std::path::Path;
+use walkdir::WalkDir;
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct DiskUsage {
+    /// Bytes of workspace files, skipping `.git` and gitignored paths
+    pub workspace_bytes: u64,
+    pub workspace_files: u64,
+    /// Bytes of everything in the bash events directory
+    pub events_bytes: u64,
+    /// Bytes of the SQLite event database, including WAL/journal files
+    pub events_db_bytes: u64,
+}
+
+/// Sums file sizes of the workspace (respecting ignore rules) and of the events directory.
+pub fn run_disk_usage(workspace_dir: &Path, events_dir: &Path) -> DiskUsage {
+    let mut usage = DiskUsage::default();
+
+    let walker = WalkBuilder::new(workspace_dir)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|e| e.file_name() != ".git")
+        .build();
+    for entry in walker.filter_map(|e| e.ok()) {
+        if entry.file_type().is_some_and(|t| t.is_file())
+            && let Ok(meta) = entry.metadata()
+        {
+            usage.workspace_bytes += meta.len();
+            usage.workspace_files += 1;
+        }
+    }
+
+    for entry in WalkDir::new(events_dir).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        usage.events_bytes += meta.len();
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with("bash_events.db")
+        {
+            usage.events_db_bytes += meta.len();
+        }
+    }
+
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_disk_usage_respects_gitignore() {
+        let workspace = tempdir().unwrap();
+        fs::write(workspace.path().join(".gitignore"), "target/\n").unwrap();
+        fs::write(workspace.path().join("a.txt"), "hello").unwrap();
+        fs::create_dir(workspace.path().join("src")).unwrap();
+        fs::write(workspace.path().join("src/lib.rs"), "x".repeat(100)).unwrap();
+        fs::create_dir(workspace.path().join("target")).unwrap();
+        fs::write(workspace.path().join("target/big.bin"), vec![0u8; 10_000]).unwrap();
+
+        let events = tempdir().unwrap();
+        fs::write(events.path().join("bash_events.db"), vec![0u8; 4096]).unwrap();
+        fs::write(events.path().join("other.log"), "log").unwrap();
+
+        let usage = run_disk_usage(workspace.path(), events.path());
+        // .gitignore (8) + a.txt (5) + src/lib.rs (100)
+        assert_eq!(usage.workspace_bytes, 113);
+        assert_eq!(usage.workspace_files, 3);
+        assert_eq!(usage.events_bytes, 4099);
+        assert_eq!(usage.events_db_bytes, 4096);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::tools::utils;
+use rmcp::schemars;
+use rmcp::ErrorData as McpError;
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path};
+use std::time::Duration;
+
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct FetchUrlArgs {
+    /// URL to download; its scheme and host must be allowed by the server configuration
+    pub url: String,
+    /// Workspace-relative path to save the file to; must not exist yet
+    pub dest: String,
+}
+
+/// Which URLs `fetch_url` may download. The tool is only available when a config is set.
+#[derive(Clone, Debug)]
+pub struct FetchConfig {
+    /// Host names that may be fetched from, matched exactly (case-insensitive)
+    pub allowed_hosts: Vec<String>,
+    pub allowed_schemes: Vec<String>,
+    pub max_bytes: u64,
+}
+
+impl FetchConfig {
+    /// Allows HTTPS downloads of up to 10 MiB from the given hosts.
+    pub fn new(allowed_hosts: Vec<String>) -> Self {
+        Self {
+            allowed_hosts,
+            allowed_schemes: vec!["https".to_string()],
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+
+    fn allows(&self, url: &reqwest::Url) -> bool {
+        let scheme_ok = self
+            .allowed_schemes
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(url.scheme()));
+        let host_ok = url.host_str().is_some_and(|host| {
+            self.allowed_hosts
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(host))
+        });
+        scheme_ok && host_ok
+    }
+}
+
+/// Downloads `url` into the workspace. Redirects are only followed to allowed URLs, and
+/// downloads larger than `max_bytes` are abandoned before anything is written.
+pub async fn run_fetch_url(
+    args: &FetchUrlArgs,
+    workspace_dir: &Path,
+    config: &FetchConfig,
+) -> Result<String, McpError> {
+    let url = match reqwest::Url::parse(&args.url) {
+        Ok(url) => url,
+        Err(e) => return Ok(format!("Error: Invalid URL '{}': {}", args.url, e)),
+    };
+    if !config.allows(&url) {
+        return Ok(format!(
+            "Error: Fetching {} is not allowed. Allowed schemes: {}; allowed hosts: {}.",
+            url,
+            config.allowed_schemes.join(", "),
+            config.allowed_hosts.join(", ")
+        ));
+    }
+
+    let dest_rel = Path::new(&args.dest);
+    if dest_rel
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Ok(format!(
+            "Error: dest must be a relative path inside the workspace: {}",
+            args.dest
+        ));
+    }
+    // Also catches a symlinked directory leading out of the workspace
+    let dest = utils::resolve_within_workspace(workspace_dir, &args.dest)?;
+    if dest.exists() {
+        return Ok(format!(
+            "Error: File already exists at: {}. Cannot overwrite files using fetch_url.",
+            dest.display()
+        ));
+    }
+
+    let redirect_config = config.clone();
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() < 5 && redirect_config.allows(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        }))
+        .build()
+        .map_err(|e| McpError {
+            code: rmcp::model::ErrorCode(-32603),
+            message: format!("Failed to build HTTP client: {}", e).into(),
+            data: None,
+        })?;
+
+    let mut response = match client.get(url.clone()).send().await {
+        Ok(r) => r,
+        Err(e) => return Ok(format!("Error: Failed to fetch {}: {}", url, e)),
+    };
+    if !response.status().is_success() {
+        return Ok(format!(
+            "Error: Fetching {} returned HTTP {}",
+            url,
+            response.status()
+        ));
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len > config.max_bytes)
+    {
+        return Ok(format!(
+            "Error: {} is larger than the {} byte limit.",
+            url, config.max_bytes
+        ));
+    }
+
+    let mut body = Vec::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                body.extend_from_slice(&chunk);
+                if body.len() as u64 > config.max_bytes {
+                    return Ok(format!(
+                        "Error: {} is larger than the {} byte limit.",
+                        url, config.max_bytes
+                    ));
+                }
+            }
+            Ok(None) => break,
+            Err(e) => return Ok(format!("Error: Failed to download {}: {}", url, e)),
+        }
+    }
+
+    if let Some(parent) = dest.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        return Ok(utils::write_error(
+            &e,
+            format!(
+                "Error: Failed to create parent directories for {}: {}",
+                dest.display(),
+                e
+            ),
+        ));
+    }
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&dest)
+        .and_then(|mut file| file.write_all(&body));
+    if let Err(e) = written {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write to {}: {}", dest.display(), e),
+        ));
+    }
+
+    Ok(format!(
+        "Downloaded {} bytes from {} to {}",
+        body.len(),
+        url,
+        dest.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn spawn_mock_server() -> String {
+        let app = axum::Router::new()
+            .route("/data.txt", axum::routing::get(|| async { "hello data\n" }))
+            .route("/big.bin", axum::routing::get(|| async { vec![0u8; 4096] }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    fn local_config() -> FetchConfig {
+        FetchConfig {
+            allowed_hosts: vec!["127.0.0.1".to_string()],
+            allowed_schemes: vec!["http".to_string()],
+            max_bytes: 1024,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_url_downloads_into_workspace() {
+        let base = spawn_mock_server().await;
+        let dir = tempdir().unwrap();
+        let args = FetchUrlArgs {
+            url: format!("{}/data.txt", base),
+            dest: "vendor/data.txt".to_string(),
+        };
+
+        let output = run_fetch_url(&args, dir.path(), &local_config())
+            .await
+            .unwrap();
+        assert!(output.starts_with("Downloaded 11 bytes"), "{}", output);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("vendor/data.txt")).unwrap(),
+            "hello data\n"
+        );
+
+        // The existing file is never overwritten
+        let output = run_fetch_url(&args, dir.path(), &local_config())
+            .await
+            .unwrap();
+        assert!(output.contains("already exists"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_url_enforces_allowlist_and_size() {
+        let base = spawn_mock_server().await;
+        let dir = tempdir().unwrap();
+
+        let https_only = FetchConfig::new(vec!["127.0.0.1".to_string()]);
+        let args = FetchUrlArgs {
+            url: format!("{}/data.txt", base),
+            dest: "data.txt".to_string(),
+        };
+        let output = run_fetch_url(&args, dir.path(), &https_only).await.unwrap();
+        assert!(output.contains("is not allowed"), "{}", output);
+
+        let args = FetchUrlArgs {
+            url: format!("{}/big.bin", base),
+            dest: "big.bin".to_string(),
+        };
+        let output = run_fetch_url(&args, dir.path(), &local_config())
+            .await
+            .unwrap();
+        assert!(output.contains("byte limit"), "{}", output);
+        assert!(!dir.path().join("big.bin").exists());
+
+        let args = FetchUrlArgs {
+            url: format!("{}/data.txt", base),
+            dest: "../outside.txt".to_string(),
+        };
+        let output = run_fetch_url(&args, dir.path(), &local_config())
+            .await
+            .unwrap();
+        assert!(output.contains("inside the workspace"), "{}", output);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fetch_url_rejects_symlinked_dest() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir_all(dir.path().join("outside")).unwrap();
+        fs::create_dir(&workspace).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("outside"), workspace.join("link")).unwrap();
+
+        let args = FetchUrlArgs {
+            url: "http://127.0.0.1:1/data.txt".to_string(),
+            dest: "link/data.txt".to_string(),
+        };
+        let err = run_fetch_url(&args, &workspace, &local_config())
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("outside the workspace"));
+        assert!(!dir.path().join("outside/data.txt").exists());
+    }
+}
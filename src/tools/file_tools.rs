@@ -2,291 +2,528 @@ use rmcp::ErrorData as McpError;
 use rmcp::model::ErrorCode;
 use std::collections::HashMap;
 use std::fs;
+use std::io::{BufRead, Read, Write};
 use std::path::{Path, PathBuf};
 use tokio::sync::Mutex;
 
+use crate::models::MutationOp;
+use crate::tools::outline;
+use crate::tools::patch;
 use crate::tools::utils;
 
 // Re-export argument types from service
 pub use crate::service::{
-    CreateFileArgs, DeleteFileArgs, InsertLinesArgs, ListDirectoryArgs, StrReplaceArgs,
-    TreeArgs, UndoEditArgs, ViewFileArgs,
+    AppendFileArgs, ApplyEditBlocksArgs, ApplyPatchArgs, ConditionalReplaceArgs, CreateFileArgs,
+    CreateFilesArgs, DeleteFileArgs, DiffArgs, EditBlock, EditOperation, ErrorContextArgs,
+    ExtractBlockArgs, FileStatArgs, InsertLinesArgs, InsertNearArgs, ListDirectoryArgs,
+    MoveFileArgs, MoveGlobArgs, MultiEditArgs, NewFile, NormalizeWhitespaceArgs, RedoEditArgs,
+    RevertFileArgs, SortFileArgs, StrReplaceArgs, SwapFilesArgs, ToggleCommentArgs, TreeArgs,
+    UndoEditArgs, ViewFileArgs, ViewWithMatchesArgs,
 };
 
 const SNIPPET_CONTEXT_WINDOW: usize = 4;
-
-fn make_output(snippet_content: &str, _snippet_description: &str, start_line: usize) -> String {
-    utils::make_numbered_output(snippet_content, start_line)
+// Hard cap on entries read by list_directory, regardless of the requested limit
+const LIST_DIRECTORY_MAX_ENTRIES: usize = 1000;
+// Number of leading lines shown after a whole-file rewrite such as sort_file
+const REWRITE_SNIPPET_LINES: usize = 20;
+// Number of trailing lines shown after append_to_file
+const APPEND_SNIPPET_LINES: usize = 10;
+// Largest decompressed size view_file accepts for a compressed file
+const MAX_DECOMPRESSED_BYTES: u64 = 16 * 1024 * 1024;
+// Lines shown on each side of the reported line by error_context
+const ERROR_CONTEXT_LINES: usize = 5;
+// Leading bytes inspected for NUL when deciding whether a file is binary
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// A file's state before an edit, as kept by the undo and redo histories.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryEntry {
+    /// The file held this content
+    Content(String),
+    /// The file did not exist
+    Absent,
 }
 
-pub async fn run_view_file(args: &ViewFileArgs, workspace_dir: &Path) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
-
-    if !path.exists() {
-        return Ok(format!(
-            "Error: The path {} does not exist. Please provide a valid path.",
-            path.display()
-        ));
+impl HistoryEntry {
+    /// Reads the current state of `path`.
+    fn capture(path: &Path) -> std::io::Result<Self> {
+        if path.exists() {
+            fs::read_to_string(path).map(HistoryEntry::Content)
+        } else {
+            Ok(HistoryEntry::Absent)
+        }
     }
 
-    match fs::read_to_string(&path) {
-        Ok(content) => {
-            let lines: Vec<&str> = content.lines().collect();
-            let num_lines = lines.len();
-
-            let (start_line, end_line) = match (args.start_line, args.end_line) {
-                (Some(s), Some(e)) => {
-                    let s = s as usize;
-                    let e = e as usize;
-                    if s < 1 || s > num_lines {
-                        return Ok(format!(
-                            "Error: start_line {} should be within the range [1, {}].",
-                            s, num_lines
-                        ));
-                    }
-                    if e < s {
-                        return Ok(format!(
-                            "Error: end_line {} should be greater than or equal to start_line {}.",
-                            e, s
-                        ));
-                    }
-                    (s, e)
-                }
-                (Some(s), None) => {
-                    let s = s as usize;
-                    if s < 1 || s > num_lines {
-                        return Ok(format!(
-                            "Error: start_line {} should be within the range [1, {}].",
-                            s, num_lines
-                        ));
-                    }
-                    (s, num_lines)
+    /// Puts `path` back into this state, recreating parent directories or removing the file.
+    fn restore(&self, path: &Path) -> std::io::Result<()> {
+        match self {
+            HistoryEntry::Content(content) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
                 }
-                (None, Some(e)) => {
-                    let e = e as usize;
-                    (1, e)
-                }
-                (None, None) => (1, num_lines),
-            };
+                fs::write(path, content)
+            }
+            HistoryEntry::Absent => match fs::remove_file(path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+        }
+    }
 
-            let end_line = std::cmp::min(end_line, num_lines);
-            let snippet_lines = lines
-                .iter()
-                .skip(start_line - 1)
-                .take(end_line - start_line + 1)
-                .cloned()
-                .collect::<Vec<&str>>()
-                .join("\n");
-
-            Ok(make_output(
-                &snippet_lines,
-                &path.to_string_lossy(),
-                start_line,
-            ))
-        }
-        Err(e) => Ok(format!(
-            "Error: Failed to read file {}: {}",
-            path.display(),
-            e
-        )),
+    /// Describes the file after this state was restored, for undo/redo output.
+    fn describe(&self, path: &Path) -> String {
+        match self {
+            HistoryEntry::Content(content) => make_output(content, &path.to_string_lossy(), 1),
+            HistoryEntry::Absent => "The file no longer exists.".to_string(),
+        }
     }
 }
 
-pub async fn run_list_directory(
-    args: &ListDirectoryArgs,
-    workspace_dir: &Path,
-) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+fn make_output(snippet_content: &str, _snippet_description: &str, start_line: usize) -> String {
+    utils::make_numbered_output(snippet_content, start_line)
+}
 
-    if !path.exists() {
-        return Ok(format!(
-            "Error: The path {} does not exist. Please provide a valid path.",
-            path.display()
-        ));
+/// Reads a file as text, decompressing `.gz` files in memory. Files with NUL bytes near
+/// the start are reported as binary.
+fn read_text(path: &Path) -> Result<String, String> {
+    let is_gzip = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+    if !is_gzip {
+        let bytes = fs::read(path)
+            .map_err(|e| format!("Error: Failed to read file {}: {}", path.display(), e))?;
+        if is_binary(&bytes) {
+            return Err(format!(
+                "Error: {} appears to be a binary file ({} bytes) and cannot be displayed.",
+                path.display(),
+                bytes.len()
+            ));
+        }
+        return String::from_utf8(bytes).map_err(|_| {
+            format!(
+                "Error: Failed to read file {}: stream did not contain valid UTF-8",
+                path.display()
+            )
+        });
     }
 
-    if !path.is_dir() {
-        return Ok(format!(
-            "Error: The path {} is not a directory.",
-            path.display()
+    let file = fs::File::open(path)
+        .map_err(|e| format!("Error: Failed to read file {}: {}", path.display(), e))?;
+    let mut bytes = Vec::new();
+    flate2::read::MultiGzDecoder::new(file)
+        .take(MAX_DECOMPRESSED_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Error: Failed to decompress {}: {}", path.display(), e))?;
+    if bytes.len() as u64 > MAX_DECOMPRESSED_BYTES {
+        return Err(format!(
+            "Error: {} decompresses to more than {} bytes.",
+            path.display(),
+            MAX_DECOMPRESSED_BYTES
         ));
     }
-
-    match fs::read_dir(&path) {
-        Ok(entries) => {
-            let mut formatted_paths = Vec::new();
-            for entry in entries.flatten() {
-                let name = entry.file_name().to_string_lossy().to_string();
-                if !name.starts_with('.') {
-                    if entry.path().is_dir() {
-                        formatted_paths.push(format!("{}/", name));
-                    } else {
-                        let line_count_str = if let Ok(content) = fs::read_to_string(entry.path()) {
-                            let count = content.lines().count();
-                            format!(" ({} line{})", count, if count == 1 { "" } else { "s" })
-                        } else {
-                            "".to_string()
-                        };
-                        formatted_paths.push(format!("{}{}", name, line_count_str));
-                    }
-                }
-            }
-            formatted_paths.sort();
-            Ok(formatted_paths.join("\n"))
-        }
-        Err(e) => Ok(format!(
-            "Error: Failed to list directory {}: {}",
+    if is_binary(&bytes) {
+        return Err(format!(
+            "Error: {} decompresses to binary content ({} bytes) that cannot be displayed.",
             path.display(),
-            e
-        )),
+            bytes.len()
+        ));
     }
+    String::from_utf8(bytes).map_err(|_| {
+        format!(
+            "Error: Decompressed content of {} is not valid UTF-8.",
+            path.display()
+        )
+    })
 }
 
-pub async fn run_create_file(
-    args: &CreateFileArgs,
-    workspace_dir: &Path,
-) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
-
-    if path.exists() {
-        return Ok(format!(
-            "Error: File already exists at: {}. Cannot overwrite files using create_file.",
-            path.display()
+/// Reads lines `start..=end` of a plain text file one line at a time, stopping after `end`, so
+/// a small range of a huge file never loads the whole file. Lines before `start` are skipped
+/// without being decoded. Only an explicit `start` past the end of the file is an error.
+fn read_line_range(
+    path: &Path,
+    start_line: Option<usize>,
+    end: Option<usize>,
+    max_line_width: Option<usize>,
+) -> Result<(usize, Vec<String>), String> {
+    let start = start_line.unwrap_or(1);
+    let read_error =
+        |e: std::io::Error| format!("Error: Failed to read file {}: {}", path.display(), e);
+    let file = fs::File::open(path).map_err(read_error)?;
+    let mut head = Vec::new();
+    (&file)
+        .take(BINARY_SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .map_err(read_error)?;
+    if is_binary(&head) {
+        let size = file.metadata().map_err(read_error)?.len();
+        return Err(format!(
+            "Error: {} appears to be a binary file ({} bytes) and cannot be displayed.",
+            path.display(),
+            size
         ));
     }
 
-    // Create parent directories if they don't exist
-    if let Some(parent) = path.parent() {
-        if let Err(e) = fs::create_dir_all(parent) {
-            return Ok(format!(
-                "Error: Failed to create parent directories for {}: {}",
-                path.display(),
-                e
-            ));
+    let mut reader = std::io::BufReader::new(head.chain(file));
+    let mut buf = Vec::new();
+    let mut num_lines = 0;
+    let mut lines = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf).map_err(read_error)? == 0 {
+            break;
+        }
+        num_lines += 1;
+        if num_lines < start {
+            continue;
         }
+        if end.is_some_and(|end| num_lines > end) {
+            break;
+        }
+        if buf.ends_with(b"\n") {
+            buf.pop();
+            if buf.ends_with(b"\r") {
+                buf.pop();
+            }
+        }
+        let line = std::str::from_utf8(&buf).map_err(|_| {
+            format!(
+                "Error: Failed to read file {}: stream did not contain valid UTF-8",
+                path.display()
+            )
+        })?;
+        lines.push(match max_line_width {
+            Some(width) => utils::truncate_line(line, width),
+            None => line.to_string(),
+        });
     }
 
-    if let Err(e) = fs::write(&path, &args.content) {
-        return Ok(format!(
-            "Error: Failed to write to {}: {}",
-            path.display(),
-            e
+    if start_line.is_some_and(|start| start > num_lines) {
+        return Err(format!(
+            "Error: start_line {} should be within the range [1, {}].",
+            start, num_lines
         ));
     }
+    Ok((start, lines))
+}
 
-    Ok(format!("File created successfully at: {}", path.display()))
+/// Treats content as binary when a NUL byte appears in its first `BINARY_SNIFF_BYTES`.
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(BINARY_SNIFF_BYTES).any(|&b| b == 0)
 }
 
-pub async fn run_str_replace(
-    args: &StrReplaceArgs,
+fn directory_error(path: &Path) -> String {
+    format!(
+        "Error: {} is a directory; use list_directory or tree to see its contents.",
+        path.display()
+    )
+}
+
+/// Reads the requested line range of a file, returning the first line number and the
+/// (possibly truncated) lines. On failure the error message to show the caller is returned.
+pub fn view_file_lines(
+    args: &ViewFileArgs,
     workspace_dir: &Path,
-    editor_history: &Mutex<HashMap<PathBuf, Vec<String>>>,
-) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+) -> Result<(usize, Vec<String>), String> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)
+        .map_err(|e| format!("Error: {}", e.message))?;
 
     if !path.exists() {
-        return Ok(format!(
-            "Error: The path {} does not exist. Please check the file path.",
+        return Err(format!(
+            "Error: The path {} does not exist. Please provide a valid path.",
             path.display()
         ));
     }
+    if path.is_dir() {
+        return Err(directory_error(&path));
+    }
 
-    if args.old_str == args.new_str {
-        return Ok(
-            "Error: No replacement was performed. new_str and old_str must be different."
-                .to_string(),
-        );
+    let is_gzip = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+    let start = args.start_line.unwrap_or(1) as usize;
+    let sane_range = start >= 1 && args.end_line.is_none_or(|e| e as usize >= start);
+    if !is_gzip && sane_range && (args.start_line.is_some() || args.end_line.is_some()) {
+        let end = args.end_line.map(|e| e as usize);
+        let start_line = args.start_line.map(|s| s as usize);
+        return read_line_range(&path, start_line, end, args.max_line_width);
     }
 
-    let content = fs::read_to_string(&path).map_err(|e| McpError {
-        code: ErrorCode(-32603),
-        message: format!("Failed to read file: {}", e).into(),
-        data: None,
-    })?;
+    let content = read_text(&path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    let num_lines = lines.len();
+
+    let (start_line, end_line) = match (args.start_line, args.end_line) {
+        (Some(s), Some(e)) => {
+            let s = s as usize;
+            let e = e as usize;
+            if s < 1 || s > num_lines {
+                return Err(format!(
+                    "Error: start_line {} should be within the range [1, {}].",
+                    s, num_lines
+                ));
+            }
+            if e < s {
+                return Err(format!(
+                    "Error: end_line {} should be greater than or equal to start_line {}.",
+                    e, s
+                ));
+            }
+            (s, e)
+        }
+        (Some(s), None) => {
+            let s = s as usize;
+            if s < 1 || s > num_lines {
+                return Err(format!(
+                    "Error: start_line {} should be within the range [1, {}].",
+                    s, num_lines
+                ));
+            }
+            (s, num_lines)
+        }
+        (None, Some(e)) => {
+            let e = e as usize;
+            (1, e)
+        }
+        (None, None) => (1, num_lines),
+    };
 
-    // Find occurrences logic
-    let occurrences: Vec<_> = content.match_indices(&args.old_str).collect();
+    let end_line = std::cmp::min(end_line, num_lines);
+    let snippet_lines = lines
+        .iter()
+        .skip(start_line - 1)
+        // An empty file has no lines at all, so `end_line` can be 0
+        .take((end_line + 1).saturating_sub(start_line))
+        .map(|line| match args.max_line_width {
+            Some(width) => utils::truncate_line(line, width),
+            None => line.to_string(),
+        })
+        .collect();
 
-    if occurrences.is_empty() {
-        return Ok(format!(
-            "Error: No replacement was performed, old_str `{}` did not appear verbatim in {}.",
-            args.old_str,
+    Ok((start_line, snippet_lines))
+}
+
+/// Returns the number and text of each line matching `args.filter` within the requested
+/// range, reading the file in a single streaming pass. On failure the error message to show
+/// the caller is returned.
+pub fn filter_file_lines(
+    args: &ViewFileArgs,
+    workspace_dir: &Path,
+) -> Result<Vec<(usize, String)>, String> {
+    let filter = args.filter.as_deref().unwrap_or_default();
+    let regex = regex::Regex::new(filter)
+        .map_err(|e| format!("Error: Invalid filter regex '{}': {}", filter, e))?;
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)
+        .map_err(|e| format!("Error: {}", e.message))?;
+
+    if !path.exists() {
+        return Err(format!(
+            "Error: The path {} does not exist. Please provide a valid path.",
             path.display()
         ));
     }
-    if occurrences.len() > 1 {
-        let line_numbers: Vec<usize> = occurrences
-            .iter()
-            .map(|(idx, _)| content[..*idx].chars().filter(|&c| c == '\n').count() + 1)
-            .collect();
-        return Ok(format!(
-            "Error: No replacement was performed. Multiple occurrences of old_str `{}` in lines {:?}. Please provide more context to make the match unique.",
-            args.old_str, line_numbers
-        ));
+    if path.is_dir() {
+        return Err(directory_error(&path));
     }
 
-    let (idx, matched_text) = occurrences[0];
-    let replacement_line = content[..idx].chars().filter(|&c| c == '\n').count() + 1;
+    let file = fs::File::open(&path)
+        .map_err(|e| format!("Error: Failed to read file {}: {}", path.display(), e))?;
+    let is_gzip = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+    let source: Box<dyn Read> = if is_gzip {
+        Box::new(flate2::read::MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut reader = std::io::BufReader::with_capacity(BINARY_SNIFF_BYTES, source);
+    let head = reader
+        .fill_buf()
+        .map_err(|e| format!("Error: Failed to read file {}: {}", path.display(), e))?;
+    if is_binary(head) {
+        return Err(format!(
+            "Error: {} appears to be a binary file and cannot be displayed.",
+            path.display()
+        ));
+    }
 
-    let new_content = format!(
-        "{}{}{}",
-        &content[..idx],
-        args.new_str,
-        &content[idx + matched_text.len()..]
-    );
+    let start_line = args.start_line.unwrap_or(1) as usize;
+    let end_line = args.end_line.map_or(usize::MAX, |e| e as usize);
+    let mut matches = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let number = i + 1;
+        if number > end_line {
+            break;
+        }
+        let line =
+            line.map_err(|e| format!("Error: Failed to read file {}: {}", path.display(), e))?;
+        if number >= start_line && regex.is_match(&line) {
+            let line = match args.max_line_width {
+                Some(width) => utils::truncate_line(&line, width),
+                None => line,
+            };
+            matches.push((number, line));
+        }
+    }
+    Ok(matches)
+}
 
-    // Save history
+pub async fn run_view_file(args: &ViewFileArgs, workspace_dir: &Path) -> Result<String, McpError> {
+    if args.list_if_directory.unwrap_or(false)
+        && utils::resolve_within_workspace(workspace_dir, &args.path)?.is_dir()
     {
-        let mut history = editor_history.lock().await;
-        history
-            .entry(path.clone())
-            .or_default()
-            .push(content.clone());
+        let list_args = ListDirectoryArgs {
+            path: args.path.clone(),
+            limit: None,
+            recursive: None,
+            max_depth: None,
+            dirs_first: Some(true),
+            include_hidden: None,
+        };
+        return run_list_directory(&list_args, workspace_dir).await;
+    }
+    // Reading and numbering a large file takes a while; keep it off the async worker threads
+    let args = args.clone();
+    let workspace_dir = workspace_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || view_file(&args, &workspace_dir))
+        .await
+        .map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to read file: {}", e).into(),
+            data: None,
+        })
+}
+
+fn view_file(args: &ViewFileArgs, workspace_dir: &Path) -> String {
+    if let Some(filter) = &args.filter {
+        return match filter_file_lines(args, workspace_dir) {
+            Ok(matches) if matches.is_empty() => {
+                format!("No lines in {} match '{}'.", args.path, filter)
+            }
+            Ok(matches) => matches
+                .iter()
+                .map(|(number, line)| utils::make_numbered_output(line, *number))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Err(message) => message,
+        };
+    }
+    match view_file_lines(args, workspace_dir) {
+        Ok((start_line, lines)) => make_output(
+            &lines.join("\n"),
+            &workspace_dir.join(&args.path).to_string_lossy(),
+            start_line,
+        ),
+        Err(message) => message,
     }
+}
 
-    fs::write(&path, &new_content).map_err(|e| McpError {
-        code: ErrorCode(-32603),
-        message: format!("Failed to write file: {}", e).into(),
-        data: None,
-    })?;
+pub async fn run_view_with_matches(
+    args: &ViewWithMatchesArgs,
+    workspace_dir: &Path,
+) -> Result<String, McpError> {
+    let re = match regex::Regex::new(&args.pattern) {
+        Ok(re) => re,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Invalid regex pattern '{}': {}",
+                args.pattern, e
+            ));
+        }
+    };
 
-    // Create snippet
-    let start_line = replacement_line.saturating_sub(SNIPPET_CONTEXT_WINDOW);
-    let end_line = replacement_line + SNIPPET_CONTEXT_WINDOW + args.new_str.matches('\n').count();
+    let view_args = ViewFileArgs {
+        path: args.path.clone(),
+        start_line: args.start_line,
+        end_line: args.end_line,
+        max_line_width: None,
+        filter: None,
+        list_if_directory: None,
+    };
+    let (start_line, lines) = match view_file_lines(&view_args, workspace_dir) {
+        Ok(found) => found,
+        Err(message) => return Ok(message),
+    };
 
-    let lines: Vec<&str> = new_content.lines().collect();
-    let snippet_display_start_line = start_line + 1;
+    let numbered = mark_lines(&lines, start_line, |_, line| re.is_match(line));
+    let match_count = numbered.iter().filter(|line| line.starts_with('>')).count();
 
-    let s_idx = start_line;
-    let output_snippet = lines
+    Ok(format!(
+        "{} of {} line(s) match '{}':\n{}",
+        match_count,
+        lines.len(),
+        args.pattern,
+        numbered.join("\n")
+    ))
+}
+
+/// Numbers `lines` from `start_line`, marking with `>` those for which `marked` holds.
+fn mark_lines(
+    lines: &[String],
+    start_line: usize,
+    marked: impl Fn(usize, &str) -> bool,
+) -> Vec<String> {
+    lines
         .iter()
-        .skip(s_idx)
-        .take(end_line - s_idx)
-        .cloned()
-        .collect::<Vec<&str>>()
-        .join("\n");
+        .enumerate()
+        .map(|(i, line)| {
+            let number = i + start_line;
+            let marker = if marked(number, line) { '>' } else { ' ' };
+            format!("{}{:6}\t{}", marker, number, line)
+        })
+        .collect()
+}
+
+/// Shows the lines around a diagnostic's location, with the reported line marked.
+pub async fn run_error_context(
+    args: &ErrorContextArgs,
+    workspace_dir: &Path,
+) -> Result<String, McpError> {
+    if args.line == 0 {
+        return Ok("Error: line numbers start at 1.".to_string());
+    }
+    let context = args.context.unwrap_or(ERROR_CONTEXT_LINES) as u64;
+    let view_args = ViewFileArgs {
+        path: args.path.clone(),
+        start_line: None,
+        end_line: Some(args.line.saturating_add(context)),
+        max_line_width: None,
+        filter: None,
+        list_if_directory: None,
+    };
+    let lines = match view_file_lines(&view_args, workspace_dir) {
+        Ok((_, lines)) => lines,
+        Err(message) => return Ok(message),
+    };
+    if args.line as usize > lines.len() {
+        return Ok(format!(
+            "Error: line {} is past the end of {} ({} lines).",
+            args.line,
+            args.path,
+            lines.len()
+        ));
+    }
 
+    let start_line = args.line.saturating_sub(context).max(1) as usize;
+    let numbered = mark_lines(&lines[start_line - 1..], start_line, |number, _| {
+        number == args.line as usize
+    });
     Ok(format!(
-        "The file {} has been edited. {}Review the changes and make sure they are as expected.",
-        path.display(),
-        make_output(
-            &output_snippet,
-            &format!("a snippet of {}", path.display()),
-            snippet_display_start_line
-        )
+        "{}:{}\n{}",
+        args.path,
+        args.line,
+        numbered.join("\n")
     ))
 }
 
-pub async fn run_insert_lines(
-    args: &InsertLinesArgs,
+pub async fn run_extract_block(
+    args: &ExtractBlockArgs,
     workspace_dir: &Path,
-    editor_history: &Mutex<HashMap<PathBuf, Vec<String>>>,
 ) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
 
     if !path.exists() {
         return Ok(format!(
-            "Error: The path {} does not exist.",
+            "Error: The path {} does not exist. Please provide a valid path.",
             path.display()
         ));
     }
@@ -302,220 +539,3911 @@ pub async fn run_insert_lines(
         }
     };
 
-    // Save history
-    {
-        let mut history = editor_history.lock().await;
-        history
-            .entry(path.clone())
-            .or_default()
-            .push(content.clone());
-    }
-
-    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
-    let idx = (args.insert_line as usize).saturating_sub(1);
-
-    if idx > lines.len() {
+    let lines: Vec<&str> = content.lines().collect();
+    let language = outline::detect_language(&path);
+    let Some(start) = outline::find_definition(&lines, &args.symbol, language) else {
         return Ok(format!(
-            "Error: insert_line {} should be within the range [0, {}]",
-            args.insert_line,
-            lines.len()
+            "Error: Symbol `{}` was not found in {}.",
+            args.symbol,
+            path.display()
         ));
-    }
+    };
 
-    let inserted_lines_count = args.content.lines().count();
+    let (first, last) = outline::block_span(&lines, start, language);
+    let snippet = lines[first..=last].join("\n");
+    Ok(make_output(&snippet, &path.to_string_lossy(), first + 1))
+}
 
-    if idx == lines.len() {
-        lines.push(args.content.clone());
-    } else {
-        lines.insert(idx, args.content.clone());
-    }
+/// An entry reported by `list_directory`.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    /// Number of lines for readable text files
+    pub line_count: Option<usize>,
+}
 
-    let new_content = lines.join("\n");
-    if let Err(e) = fs::write(&path, &new_content) {
-        return Ok(format!(
-            "Error: Failed to write file {}: {}",
-            path.display(),
-            e
-        ));
-    }
+/// An entry reported by `list_directory_detailed`.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct DetailedEntry {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    /// File size; absent for directories
+    pub size_bytes: Option<u64>,
+    /// Number of lines for readable text files
+    pub line_count: Option<usize>,
+    pub modified: Option<chrono::DateTime<chrono::Utc>>,
+}
 
-    // Snippet
-    let start_line = (args.insert_line as usize).saturating_sub(SNIPPET_CONTEXT_WINDOW);
-    let end_line = args.insert_line as usize + SNIPPET_CONTEXT_WINDOW + inserted_lines_count;
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    /// Only reported by `file_stat`; listings follow symlinks
+    Symlink,
+}
 
-    let new_lines: Vec<&str> = new_content.lines().collect();
-    let output_snippet = new_lines
-        .iter()
-        .skip(start_line)
-        .take(end_line - start_line)
-        .cloned()
-        .collect::<Vec<&str>>()
-        .join("\n");
+/// Metadata reported by `file_stat`.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct FileStat {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    pub size_bytes: u64,
+    /// Number of lines for readable text files
+    pub line_count: Option<usize>,
+    pub modified: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether any execute permission bit is set (always false off Unix)
+    pub executable: bool,
+    /// Where a symlink points
+    pub target: Option<String>,
+}
 
-    Ok(format!(
-        "The file {} has been edited. {}Review the changes and make sure they are as expected.",
-        path.display(),
-        make_output(
-            &output_snippet,
-            "a snippet of the edited file",
-            start_line + 1
+/// A file or directory in the JSON output of `tree`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct TreeNode {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    /// Contents of a directory; absent for files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<TreeChild>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TreeChild {
+    Node(TreeNode),
+    Truncated {
+        /// Number of entries left out of the listing by `truncate`
+        truncated: usize,
+    },
+}
+
+fn list_directory_limit(args: &ListDirectoryArgs) -> usize {
+    args.limit
+        .unwrap_or(LIST_DIRECTORY_MAX_ENTRIES)
+        .min(LIST_DIRECTORY_MAX_ENTRIES)
+}
+
+/// Lists the entries of a directory, sorted by name, and whether the listing was
+/// cut off at the entry limit. Recursive listings name entries by their path relative to the
+/// listed directory, each directory followed by its contents. On failure the error message to
+/// show the caller is returned.
+pub fn list_directory_entries(
+    args: &ListDirectoryArgs,
+    workspace_dir: &Path,
+) -> Result<(Vec<DirectoryEntry>, bool), String> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)
+        .map_err(|e| format!("Error: {}", e.message))?;
+
+    if !path.exists() {
+        return Err(format!(
+            "Error: The path {} does not exist. Please provide a valid path.",
+            path.display()
+        ));
+    }
+
+    if !path.is_dir() {
+        return Err(format!(
+            "Error: The path {} is not a directory.",
+            path.display()
+        ));
+    }
+
+    let max_depth = if args.recursive.unwrap_or(false) {
+        args.max_depth.unwrap_or(usize::MAX).max(1)
+    } else {
+        1
+    };
+    let mut listed = Vec::new();
+    let complete = collect_directory_entries(&path, "", max_depth, args, &mut listed)?;
+    Ok((listed, !complete))
+}
+
+/// Appends the entries of `dir` to `listed`, descending `max_depth - 1` more levels.
+/// Returns false if an entry had to be left out to stay within the entry limit.
+fn collect_directory_entries(
+    dir: &Path,
+    prefix: &str,
+    max_depth: usize,
+    args: &ListDirectoryArgs,
+    listed: &mut Vec<DirectoryEntry>,
+) -> Result<bool, String> {
+    let limit = list_directory_limit(args);
+    let include_hidden = args.include_hidden.unwrap_or(false);
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Error: Failed to list directory {}: {}", dir.display(), e))?;
+
+    // Stop reading as soon as one entry beyond the limit is seen
+    let remaining = limit.saturating_sub(listed.len());
+    let mut children: Vec<(String, PathBuf, bool)> = entries
+        .flatten()
+        .filter(|entry| include_hidden || !entry.file_name().to_string_lossy().starts_with('.'))
+        .take(remaining + 1)
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            (name, path, is_dir)
+        })
+        .collect();
+    if args.dirs_first.unwrap_or(false) {
+        children.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    } else {
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    for (name, path, is_dir) in children {
+        if listed.len() >= limit {
+            return Ok(false);
+        }
+        let name = format!("{}{}", prefix, name);
+        if is_dir {
+            listed.push(DirectoryEntry {
+                name: name.clone(),
+                kind: EntryKind::Dir,
+                line_count: None,
+            });
+            if max_depth > 1
+                && !collect_directory_entries(
+                    &path,
+                    &format!("{}/", name),
+                    max_depth - 1,
+                    args,
+                    listed,
+                )?
+            {
+                return Ok(false);
+            }
+        } else {
+            let line_count = fs::read_to_string(&path)
+                .ok()
+                .map(|content| content.lines().count());
+            listed.push(DirectoryEntry {
+                name,
+                kind: EntryKind::File,
+                line_count,
+            });
+        }
+    }
+    Ok(true)
+}
+
+pub async fn run_list_directory(
+    args: &ListDirectoryArgs,
+    workspace_dir: &Path,
+) -> Result<String, McpError> {
+    let (entries, truncated) = match list_directory_entries(args, workspace_dir) {
+        Ok(listed) => listed,
+        Err(message) => return Ok(message),
+    };
+
+    let formatted_paths: Vec<String> = entries
+        .iter()
+        .map(|entry| match (entry.kind, entry.line_count) {
+            (EntryKind::Dir, _) => format!("{}/", entry.name),
+            (_, Some(count)) => format!(
+                "{} ({} line{})",
+                entry.name,
+                count,
+                if count == 1 { "" } else { "s" }
+            ),
+            (_, None) => entry.name.clone(),
+        })
+        .collect();
+
+    let mut output = formatted_paths.join("\n");
+    if truncated {
+        let limit = list_directory_limit(args);
+        output.push_str(&format!(
+            "\n[directory has more than {} entries; showing first {}]",
+            limit, limit
+        ));
+    }
+    Ok(output)
+}
+
+/// Lists a directory like `list_directory`, adding each entry's size and modification time.
+pub fn run_list_directory_detailed(
+    args: &ListDirectoryArgs,
+    workspace_dir: &Path,
+) -> Result<(Vec<DetailedEntry>, bool), String> {
+    let (entries, truncated) = list_directory_entries(args, workspace_dir)?;
+    let dir = utils::resolve_within_workspace(workspace_dir, &args.path)
+        .map_err(|e| format!("Error: {}", e.message))?;
+    let detailed = entries
+        .into_iter()
+        .map(|entry| {
+            let metadata = fs::metadata(dir.join(&entry.name)).ok();
+            DetailedEntry {
+                size_bytes: metadata
+                    .as_ref()
+                    .filter(|_| entry.kind == EntryKind::File)
+                    .map(|m| m.len()),
+                modified: metadata
+                    .and_then(|m| m.modified().ok())
+                    .map(chrono::DateTime::from),
+                name: entry.name,
+                kind: entry.kind,
+                line_count: entry.line_count,
+            }
+        })
+        .collect();
+    Ok((detailed, truncated))
+}
+
+/// Describes a single path without following it if it is a symlink.
+pub fn run_file_stat(args: &FileStatArgs, workspace_dir: &Path) -> Result<FileStat, String> {
+    // Only the parent must stay inside the workspace; a symlink itself is described, not followed
+    let rel = Path::new(&args.path);
+    let path = match (rel.parent(), rel.file_name()) {
+        (Some(parent), Some(name)) => {
+            utils::resolve_within_workspace(workspace_dir, &parent.to_string_lossy())
+                .map(|parent| parent.join(name))
+        }
+        _ => utils::resolve_within_workspace(workspace_dir, &args.path),
+    }
+    .map_err(|e| format!("Error: {}", e.message))?;
+    let metadata = fs::symlink_metadata(&path).map_err(|_| {
+        format!(
+            "Error: The path {} does not exist. Please provide a valid path.",
+            path.display()
         )
-    ))
+    })?;
+
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_dir() {
+        EntryKind::Dir
+    } else {
+        EntryKind::File
+    };
+    let line_count = if kind == EntryKind::File {
+        fs::read_to_string(&path)
+            .ok()
+            .map(|content| content.lines().count())
+    } else {
+        None
+    };
+    Ok(FileStat {
+        path: args.path.clone(),
+        kind,
+        size_bytes: metadata.len(),
+        line_count,
+        modified: metadata.modified().ok().map(chrono::DateTime::from),
+        executable: kind == EntryKind::File && is_executable(&metadata),
+        target: fs::read_link(&path)
+            .ok()
+            .map(|target| target.to_string_lossy().to_string()),
+    })
 }
 
-pub async fn run_delete_file(
-    args: &DeleteFileArgs,
+pub async fn run_create_file(
+    args: &CreateFileArgs,
     workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
 ) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
 
-    if !path.exists() {
+    if path.exists() {
         return Ok(format!(
-            "Error: The path {} does not exist.",
+            "Error: File already exists at: {}. Cannot overwrite files using create_file.",
             path.display()
         ));
     }
 
-    if let Err(e) = fs::remove_file(&path) {
+    // Create parent directories if they don't exist
+    if let Some(parent) = path.parent()
+        && let Err(e) = tokio::fs::create_dir_all(parent).await
+    {
+        return Ok(utils::write_error(
+            &e,
+            format!(
+                "Error: Failed to create parent directories for {}: {}",
+                path.display(),
+                e
+            ),
+        ));
+    }
+
+    if let Err(e) = tokio::fs::write(&path, &args.content).await {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write to {}: {}", path.display(), e),
+        ));
+    }
+    editor_history
+        .lock()
+        .await
+        .entry(path.clone())
+        .or_default()
+        .push(HistoryEntry::Absent);
+
+    if args.executable.unwrap_or(false)
+        && let Err(e) = set_executable(&path)
+    {
         return Ok(format!(
-            "Error: Failed to delete file {}: {}",
+            "Error: File created at {} but could not be made executable: {}",
             path.display(),
             e
         ));
     }
 
-    Ok(format!("File deleted successfully: {}", path.display()))
+    Ok(format!("File created successfully at: {}", path.display()))
 }
 
-pub async fn run_undo_edit(
-    args: &UndoEditArgs,
+/// Adds execute permission wherever read permission is set, like `chmod +x`.
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(mode | ((mode & 0o444) >> 2));
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+pub async fn run_create_files(
+    args: &CreateFilesArgs,
     workspace_dir: &Path,
-    editor_history: &Mutex<HashMap<PathBuf, Vec<String>>>,
 ) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+    if args.files.is_empty() {
+        return Ok("Error: No files were given.".to_string());
+    }
 
-    let mut history = editor_history.lock().await;
-    if let Some(versions) = history.get_mut(&path) {
-        if let Some(prev_content) = versions.pop() {
-            if let Err(e) = fs::write(&path, &prev_content) {
-                return Ok(format!(
-                    "Error: Failed to restore file {}: {}",
+    // Files and top-most directories created so far, removed again if the batch fails
+    let mut created_files: Vec<PathBuf> = Vec::new();
+    let mut created_dirs: Vec<PathBuf> = Vec::new();
+    let rollback = |files: &[PathBuf], dirs: &[PathBuf]| {
+        for file in files.iter().rev() {
+            let _ = fs::remove_file(file);
+        }
+        for dir in dirs.iter().rev() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    };
+
+    // Reject the whole batch up front if any path escapes the workspace
+    let paths = args
+        .files
+        .iter()
+        .map(|file| utils::resolve_within_workspace(workspace_dir, &file.path))
+        .collect::<Result<Vec<PathBuf>, McpError>>()?;
+
+    for (file, path) in args.files.iter().zip(paths) {
+        if let Some(parent) = path.parent() {
+            let first_missing = parent
+                .ancestors()
+                .take_while(|dir| !dir.exists())
+                .last()
+                .map(Path::to_path_buf);
+            if let Err(e) = fs::create_dir_all(parent) {
+                rollback(&created_files, &created_dirs);
+                return Ok(utils::write_error(
+                    &e,
+                    format!(
+                    "Error: Failed to create parent directories for {}: {}. No files were created.",
                     path.display(),
                     e
+                ),
+                ));
+            }
+            created_dirs.extend(first_missing);
+        }
+
+        // create_new makes the existence check and the write a single step
+        let result = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut f| f.write_all(file.content.as_bytes()));
+        match result {
+            Ok(()) => created_files.push(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                rollback(&created_files, &created_dirs);
+                return Ok(format!(
+                    "Error: File already exists at: {}. No files were created.",
+                    path.display()
+                ));
+            }
+            Err(e) => {
+                // The file may have been created before the write failed
+                created_files.push(path.clone());
+                rollback(&created_files, &created_dirs);
+                return Ok(utils::write_error(
+                    &e,
+                    format!(
+                        "Error: Failed to write to {}: {}. No files were created.",
+                        path.display(),
+                        e
+                    ),
                 ));
             }
-            return Ok(format!(
-                "Last edit to {} undone successfully. {}",
-                path.display(),
-                make_output(&prev_content, &path.to_string_lossy(), 1)
-            ));
         }
     }
+
+    let listing = created_files
+        .iter()
+        .map(|p| format!("  {}", p.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
     Ok(format!(
-        "Error: No edit history found for {}",
-        path.display()
+        "Created {} files successfully:\n{}",
+        created_files.len(),
+        listing
     ))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
+pub async fn run_str_replace(
+    args: &StrReplaceArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist. Please check the file path.",
+            path.display()
+        ));
+    }
+
+    if args.old_str == args.new_str {
+        return Ok(
+            "Error: No replacement was performed. new_str and old_str must be different."
+                .to_string(),
+        );
+    }
+
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to read file: {}", e).into(),
+            data: None,
+        })?;
+
+    // Find occurrences logic
+    let occurrences: Vec<_> = content.match_indices(&args.old_str).collect();
+
+    if occurrences.is_empty() {
+        return Ok(format!(
+            "Error: No replacement was performed, old_str `{}` did not appear verbatim in {}.",
+            args.old_str,
+            path.display()
+        ));
+    }
+    let replace_all = args.replace_all.unwrap_or(false);
+    if replace_all && args.occurrence.is_some() {
+        return Ok(
+            "Error: No replacement was performed. replace_all and occurrence cannot be combined."
+                .to_string(),
+        );
+    }
+    if let Some(n) = args.occurrence
+        && (n < 1 || n > occurrences.len())
+    {
+        return Ok(format!(
+            "Error: No replacement was performed. occurrence {} is out of range; old_str `{}` appears {} time(s).",
+            n,
+            args.old_str,
+            occurrences.len()
+        ));
+    }
+    if occurrences.len() > 1 && !replace_all && args.occurrence.is_none() {
+        let line_numbers: Vec<usize> = occurrences
+            .iter()
+            .map(|(idx, _)| content[..*idx].chars().filter(|&c| c == '\n').count() + 1)
+            .collect();
+        return Ok(format!(
+            "Error: No replacement was performed. Multiple occurrences of old_str `{}` in lines {:?}. Please provide more context to make the match unique.",
+            args.old_str, line_numbers
+        ));
+    }
+
+    let (idx, matched_text) = occurrences[args.occurrence.map_or(0, |n| n - 1)];
+    let replacement_line = content[..idx].chars().filter(|&c| c == '\n').count() + 1;
+
+    let new_content = if replace_all {
+        content.replace(&args.old_str, &args.new_str)
+    } else {
+        format!(
+            "{}{}{}",
+            &content[..idx],
+            args.new_str,
+            &content[idx + matched_text.len()..]
+        )
+    };
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content.clone()));
+    }
+
+    tokio::fs::write(&path, &new_content)
+        .await
+        .map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to write file: {}", e).into(),
+            data: None,
+        })?;
+
+    // Create snippet
+    let start_line = replacement_line.saturating_sub(SNIPPET_CONTEXT_WINDOW);
+    let end_line = replacement_line + SNIPPET_CONTEXT_WINDOW + args.new_str.matches('\n').count();
+
+    let lines: Vec<&str> = new_content.lines().collect();
+    let snippet_display_start_line = start_line + 1;
+
+    let s_idx = start_line;
+    let output_snippet = lines
+        .iter()
+        .skip(s_idx)
+        .take(end_line - s_idx)
+        .cloned()
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let replaced = if replace_all {
+        format!("Replaced {} occurrence(s). ", occurrences.len())
+    } else {
+        String::new()
+    };
+    Ok(format!(
+        "The file {} has been edited. {}{}Review the changes and make sure they are as expected.",
+        path.display(),
+        replaced,
+        make_output(
+            &output_snippet,
+            &format!("a snippet of {}", path.display()),
+            snippet_display_start_line
+        )
+    ))
+}
+
+/// Applies all edits to an in-memory copy of the file and writes it only if every edit
+/// succeeds. The original content is saved once, so a single undo reverts the batch.
+pub async fn run_multi_edit(
+    args: &MultiEditArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist. Please check the file path.",
+            path.display()
+        ));
+    }
+    if args.edits.is_empty() {
+        return Ok("Error: No edits were given.".to_string());
+    }
+
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+
+    let mut buffer = content.clone();
+    let mut applied: Vec<(usize, usize)> = Vec::new();
+    for (i, edit) in args.edits.iter().enumerate() {
+        if edit.old_str.is_empty() {
+            return Ok(format!(
+                "Error: edits[{}] has an empty old_str. No changes were made.",
+                i
+            ));
+        }
+        if edit.old_str == edit.new_str {
+            return Ok(format!(
+                "Error: edits[{}] has identical old_str and new_str. No changes were made.",
+                i
+            ));
+        }
+        let occurrences: Vec<usize> = buffer
+            .match_indices(&edit.old_str)
+            .map(|(idx, _)| idx)
+            .collect();
+        let Some(&first) = occurrences.first() else {
+            return Ok(format!(
+                "Error: edits[{}] failed: old_str `{}` did not appear verbatim in {}. No changes were made.",
+                i,
+                edit.old_str,
+                path.display()
+            ));
+        };
+        let replace_all = edit.replace_all.unwrap_or(false);
+        if occurrences.len() > 1 && !replace_all {
+            return Ok(format!(
+                "Error: edits[{}] failed: old_str `{}` appears {} times. Provide more context or set replace_all. No changes were made.",
+                i,
+                edit.old_str,
+                occurrences.len()
+            ));
+        }
+
+        let line = buffer[..first].matches('\n').count() + 1;
+        buffer = if replace_all {
+            buffer.replace(&edit.old_str, &edit.new_str)
+        } else {
+            buffer.replacen(&edit.old_str, &edit.new_str, 1)
+        };
+        applied.push((line, occurrences.len()));
+    }
+
+    if let Err(e) = fs::write(&path, &buffer) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path.display(), e),
+        ));
+    }
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content));
+    }
+
+    let mut output = format!(
+        "The file {} has been edited. Applied {} edit(s):\n",
+        path.display(),
+        applied.len()
+    );
+    for (i, (line, count)) in applied.iter().enumerate() {
+        output.push_str(&format!(
+            "  edits[{}]: line {} ({} replacement(s))\n",
+            i, line, count
+        ));
+    }
+    Ok(output)
+}
+
+/// Applies SEARCH/REPLACE blocks like `run_multi_edit`, but every search text must be unique.
+/// The summary counts the lines each block removed and added.
+pub async fn run_apply_edit_blocks(
+    args: &ApplyEditBlocksArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist. Please check the file path.",
+            path.display()
+        ));
+    }
+    if args.blocks.is_empty() {
+        return Ok("Error: No edit blocks were given.".to_string());
+    }
+
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+
+    let mut buffer = content.clone();
+    let mut stats: Vec<(usize, usize, usize)> = Vec::new();
+    for (i, block) in args.blocks.iter().enumerate() {
+        if block.search.is_empty() {
+            return Ok(format!(
+                "Error: blocks[{}] has an empty search. No changes were made.",
+                i
+            ));
+        }
+        let occurrences: Vec<usize> = buffer
+            .match_indices(&block.search)
+            .map(|(idx, _)| idx)
+            .collect();
+        let idx = match occurrences.as_slice() {
+            [idx] => *idx,
+            [] => {
+                return Ok(format!(
+                    "Error: blocks[{}] failed: search `{}` did not appear verbatim in {}. No changes were made.",
+                    i,
+                    block.search,
+                    path.display()
+                ));
+            }
+            _ => {
+                return Ok(format!(
+                    "Error: blocks[{}] failed: search `{}` appears {} times. Add surrounding lines to make it unique. No changes were made.",
+                    i,
+                    block.search,
+                    occurrences.len()
+                ));
+            }
+        };
+
+        let line = buffer[..idx].matches('\n').count() + 1;
+        buffer.replace_range(idx..idx + block.search.len(), &block.replace);
+        stats.push((
+            line,
+            block.search.lines().count(),
+            block.replace.lines().count(),
+        ));
+    }
+
+    if let Err(e) = fs::write(&path, &buffer) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path.display(), e),
+        ));
+    }
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content));
+    }
+
+    let removed: usize = stats.iter().map(|(_, removed, _)| removed).sum();
+    let added: usize = stats.iter().map(|(_, _, added)| added).sum();
+    let mut output = format!(
+        "The file {} has been edited. Applied {} block(s), -{} +{} line(s):\n",
+        path.display(),
+        stats.len(),
+        removed,
+        added
+    );
+    for (i, (line, removed, added)) in stats.iter().enumerate() {
+        output.push_str(&format!(
+            "  blocks[{}]: line {} (-{} +{})\n",
+            i, line, removed, added
+        ));
+    }
+    Ok(output)
+}
+
+/// A file touched by `apply_patch`, before and after.
+struct PatchedFile {
+    rel: String,
+    path: PathBuf,
+    original: HistoryEntry,
+    patched: HistoryEntry,
+    removed: usize,
+    added: usize,
+}
+
+/// Applies a unified diff that may span several files. Every hunk is checked before anything
+/// is written, so a hunk that does not match leaves the workspace untouched. Each file's prior
+/// state goes to the edit history. Returns the output text and the changes made.
+pub async fn run_apply_patch(
+    args: &ApplyPatchArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<(String, Vec<(MutationOp, String)>), McpError> {
+    let file_patches = match patch::parse_patch(&args.patch) {
+        Ok(files) => files,
+        Err(e) => {
+            return Ok((
+                format!("Error: Invalid patch: {}. No changes were made.", e),
+                Vec::new(),
+            ));
+        }
+    };
+
+    let mut files: Vec<PatchedFile> = Vec::new();
+    for file_patch in &file_patches {
+        let rel = file_patch.path();
+        if let (Some(old), Some(new)) = (&file_patch.old_path, &file_patch.new_path)
+            && old != new
+        {
+            return Ok((
+                format!(
+                    "Error: Renaming {} to {} is not supported; use move_file. No changes were made.",
+                    old, new
+                ),
+                Vec::new(),
+            ));
+        }
+        let path = utils::resolve_within_workspace(workspace_dir, rel)?;
+        let index = match files.iter().position(|f| f.path == path) {
+            Some(index) => index,
+            None => {
+                let original = match HistoryEntry::capture(&path) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        return Ok((
+                            format!(
+                                "Error: Failed to read file {}: {}. No changes were made.",
+                                path.display(),
+                                e
+                            ),
+                            Vec::new(),
+                        ));
+                    }
+                };
+                files.push(PatchedFile {
+                    rel: rel.to_string(),
+                    path: path.clone(),
+                    patched: original.clone(),
+                    original,
+                    removed: 0,
+                    added: 0,
+                });
+                files.len() - 1
+            }
+        };
+        let file = &mut files[index];
+
+        let content = match (&file.patched, &file_patch.old_path) {
+            (HistoryEntry::Absent, None) => String::new(),
+            (HistoryEntry::Content(content), Some(_)) => content.clone(),
+            (HistoryEntry::Content(_), None) => {
+                return Ok((
+                    format!(
+                        "Error: The patch creates {}, which already exists. No changes were made.",
+                        rel
+                    ),
+                    Vec::new(),
+                ));
+            }
+            (HistoryEntry::Absent, Some(_)) => {
+                return Ok((
+                    format!(
+                        "Error: The patch edits {}, which does not exist. No changes were made.",
+                        rel
+                    ),
+                    Vec::new(),
+                ));
+            }
+        };
+        let patched = match patch::apply_hunks(&content, &file_patch.hunks) {
+            Ok(patched) => patched,
+            Err(header) => {
+                return Ok((
+                    format!(
+                        "Error: Hunk '{}' does not apply to {}: its context and removed lines do not match the file. No changes were made.",
+                        header, rel
+                    ),
+                    Vec::new(),
+                ));
+            }
+        };
+        file.patched = match file_patch.new_path {
+            Some(_) => HistoryEntry::Content(patched),
+            None => HistoryEntry::Absent,
+        };
+        for line in file_patch.hunks.iter().flat_map(|h| &h.lines) {
+            match line.kind {
+                patch::LineKind::Remove => file.removed += 1,
+                patch::LineKind::Add => file.added += 1,
+                patch::LineKind::Context => {}
+            }
+        }
+    }
+
+    for (written, file) in files.iter().enumerate() {
+        if let Err(e) = file.patched.restore(&file.path) {
+            // Put back the files already written so the patch applies all or nothing
+            for done in files[..written].iter().rev() {
+                let _ = done.original.restore(&done.path);
+            }
+            return Ok((
+                utils::write_error(
+                    &e,
+                    format!(
+                        "Error: Failed to write file {}: {}. No changes were made.",
+                        file.path.display(),
+                        e
+                    ),
+                ),
+                Vec::new(),
+            ));
+        }
+    }
+
+    let mut history = editor_history.lock().await;
+    let mut output = format!("Applied patch to {} file(s):\n", files.len());
+    let mut changes = Vec::new();
+    for file in files {
+        let op = match (&file.original, &file.patched) {
+            (HistoryEntry::Absent, _) => MutationOp::Create,
+            (_, HistoryEntry::Absent) => MutationOp::Delete,
+            _ => MutationOp::Edit,
+        };
+        let note = match op {
+            MutationOp::Create => " (created)",
+            MutationOp::Delete => " (deleted)",
+            MutationOp::Edit => "",
+        };
+        output.push_str(&format!(
+            "  {}: -{} +{}{}\n",
+            file.rel, file.removed, file.added, note
+        ));
+        history.entry(file.path).or_default().push(file.original);
+        changes.push((op, file.rel));
+    }
+    Ok((output, changes))
+}
+
+/// Replaces every occurrence of `old_str` in the files matching the glob that contain
+/// `contains`. Returns the output text and the workspace-relative paths changed.
+pub async fn run_conditional_replace(
+    args: &ConditionalReplaceArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<(String, Vec<String>), McpError> {
+    if args.old_str.is_empty() {
+        return Ok(("Error: old_str must not be empty.".to_string(), Vec::new()));
+    }
+    if args.old_str == args.new_str {
+        return Ok((
+            "Error: No replacement was performed. new_str and old_str must be different."
+                .to_string(),
+            Vec::new(),
+        ));
+    }
+
+    let pattern = utils::workspace_glob(workspace_dir, &args.glob)?;
+    let paths = match glob::glob(&pattern) {
+        Ok(paths) => paths,
+        Err(e) => {
+            return Ok((
+                format!("Error: Invalid glob pattern '{}': {}", args.glob, e),
+                Vec::new(),
+            ));
+        }
+    };
+
+    let mut history = editor_history.lock().await;
+    let mut changed: Vec<(String, usize)> = Vec::new();
+    let mut skipped: Vec<(String, String)> = Vec::new();
+    for path in paths.filter_map(|p| p.ok()).filter(|p| p.is_file()) {
+        let rel_path = workspace_relative(&path, workspace_dir);
+        if utils::resolve_within_workspace(workspace_dir, &rel_path).is_err() {
+            skipped.push((rel_path, "resolves outside the workspace".to_string()));
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&path) else {
+            skipped.push((rel_path, "not a readable text file".to_string()));
+            continue;
+        };
+        if !content.contains(&args.contains) {
+            skipped.push((
+                rel_path,
+                "does not contain the precondition string".to_string(),
+            ));
+            continue;
+        }
+        let count = content.matches(&args.old_str).count();
+        if count == 0 {
+            skipped.push((rel_path, "old_str not found".to_string()));
+            continue;
+        }
+
+        let new_content = content.replace(&args.old_str, &args.new_str);
+        if let Err(e) = fs::write(&path, &new_content) {
+            let reason = utils::write_error(&e, format!("failed to write file: {}", e));
+            // A read-only filesystem is reported alone only while nothing has been written;
+            // files already changed keep their history and stay in the summary below
+            if reason.starts_with("Error") && changed.is_empty() {
+                return Ok((reason, Vec::new()));
+            }
+            skipped.push((rel_path, reason.trim_start_matches("Error: ").to_string()));
+            continue;
+        }
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content));
+        changed.push((rel_path, count));
+    }
+    drop(history);
+
+    if changed.is_empty() && skipped.is_empty() {
+        return Ok((
+            format!("Error: No files match pattern '{}'.", args.glob),
+            Vec::new(),
+        ));
+    }
+
+    let mut output = format!("Changed {} file(s):\n", changed.len());
+    for (path, count) in &changed {
+        output.push_str(&format!("  {} ({} replacement(s))\n", path, count));
+    }
+    if !skipped.is_empty() {
+        output.push_str(&format!("Skipped {} file(s):\n", skipped.len()));
+        for (path, reason) in &skipped {
+            output.push_str(&format!("  {}: {}\n", path, reason));
+        }
+    }
+    Ok((output, changed.into_iter().map(|(path, _)| path).collect()))
+}
+
+pub async fn run_insert_lines(
+    args: &InsertLinesArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist.",
+            path.display()
+        ));
+    }
+
+    let content = match tokio::fs::read_to_string(&path).await {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content.clone()));
+    }
+
+    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+    let idx = (args.insert_line as usize).saturating_sub(1);
+
+    if idx > lines.len() {
+        return Ok(format!(
+            "Error: insert_line {} should be within the range [0, {}]",
+            args.insert_line,
+            lines.len()
+        ));
+    }
+
+    // Each line of the content becomes its own line so later positions stay accurate
+    let inserted: Vec<String> = args.content.split('\n').map(|s| s.to_string()).collect();
+    let inserted_lines_count = inserted.len();
+    lines.splice(idx..idx, inserted);
+
+    let new_content = lines.join("\n");
+    if let Err(e) = tokio::fs::write(&path, &new_content).await {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path.display(), e),
+        ));
+    }
+
+    // Snippet
+    let start_line = (args.insert_line as usize).saturating_sub(SNIPPET_CONTEXT_WINDOW);
+    let end_line = args.insert_line as usize + SNIPPET_CONTEXT_WINDOW + inserted_lines_count;
+
+    let new_lines: Vec<&str> = new_content.lines().collect();
+    let output_snippet = new_lines
+        .iter()
+        .skip(start_line)
+        .take(end_line - start_line)
+        .cloned()
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    Ok(format!(
+        "The file {} has been edited. {}Review the changes and make sure they are as expected.",
+        path.display(),
+        make_output(
+            &output_snippet,
+            "a snippet of the edited file",
+            start_line + 1
+        )
+    ))
+}
+
+/// Appends `content` to the end of a file, creating it if needed. A newline is added first when
+/// the existing file doesn't end with one, so the appended text always starts on its own line.
+pub async fn run_append_to_file(
+    args: &AppendFileArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    let previous = match HistoryEntry::capture(&path) {
+        Ok(previous) => previous,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+    let mut new_content = match &previous {
+        HistoryEntry::Content(content) => content.clone(),
+        HistoryEntry::Absent => {
+            if let Some(parent) = path.parent()
+                && let Err(e) = fs::create_dir_all(parent)
+            {
+                return Ok(utils::write_error(
+                    &e,
+                    format!(
+                        "Error: Failed to create parent directories for {}: {}",
+                        path.display(),
+                        e
+                    ),
+                ));
+            }
+            String::new()
+        }
+    };
+    if !new_content.is_empty() && !new_content.ends_with('\n') {
+        new_content.push('\n');
+    }
+    new_content.push_str(&args.content);
+
+    if let Err(e) = fs::write(&path, &new_content) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path.display(), e),
+        ));
+    }
+    editor_history
+        .lock()
+        .await
+        .entry(path.clone())
+        .or_default()
+        .push(previous);
+
+    let lines: Vec<&str> = new_content.lines().collect();
+    let start_line = lines.len().saturating_sub(APPEND_SNIPPET_LINES);
+    Ok(format!(
+        "Appended to {}. The end of the file now reads:\n{}",
+        path.display(),
+        make_output(
+            &lines[start_line..].join("\n"),
+            "the end of the file",
+            start_line + 1
+        )
+    ))
+}
+
+pub async fn run_insert_near(
+    args: &InsertNearArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist.",
+            path.display()
+        ));
+    }
+
+    let after = match args.position.as_str() {
+        "before" => false,
+        "after" => true,
+        other => {
+            return Ok(format!(
+                "Error: position must be \"before\" or \"after\", got \"{}\".",
+                other
+            ));
+        }
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+
+    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+    let anchor_lines: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.contains(&args.anchor))
+        .map(|(i, _)| i + 1)
+        .collect();
+
+    let anchor_line = match anchor_lines.as_slice() {
+        [line] => *line,
+        [] => {
+            return Ok(format!(
+                "Error: No insertion was performed, anchor `{}` did not appear in {}.",
+                args.anchor,
+                path.display()
+            ));
+        }
+        _ => {
+            return Ok(format!(
+                "Error: No insertion was performed. Anchor `{}` appears in lines {:?}. Please provide a more specific anchor.",
+                args.anchor, anchor_lines
+            ));
+        }
+    };
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content.clone()));
+    }
+
+    // 0-based index the new content starts at
+    let idx = if after { anchor_line } else { anchor_line - 1 };
+    let inserted: Vec<String> = args.content.lines().map(|s| s.to_string()).collect();
+    let inserted_lines_count = inserted.len();
+    lines.splice(idx..idx, inserted);
+
+    let mut new_content = lines.join("\n");
+    if content.ends_with('\n') {
+        new_content.push('\n');
+    }
+    if let Err(e) = fs::write(&path, &new_content) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path.display(), e),
+        ));
+    }
+
+    // Snippet
+    let start_line = idx.saturating_sub(SNIPPET_CONTEXT_WINDOW);
+    let end_line = (idx + inserted_lines_count + SNIPPET_CONTEXT_WINDOW).min(lines.len());
+    let output_snippet = lines[start_line..end_line].join("\n");
+
+    Ok(format!(
+        "The file {} has been edited. {}Review the changes and make sure they are as expected.",
+        path.display(),
+        make_output(
+            &output_snippet,
+            "a snippet of the edited file",
+            start_line + 1
+        )
+    ))
+}
+
+pub async fn run_delete_file(
+    args: &DeleteFileArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist.",
+            path.display()
+        ));
+    }
+
+    // Keep the content so the deletion can be undone; files that aren't text can't be restored
+    let previous = fs::read_to_string(&path).ok();
+    if let Err(e) = fs::remove_file(&path) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to delete file {}: {}", path.display(), e),
+        ));
+    }
+    if let Some(content) = previous {
+        editor_history
+            .lock()
+            .await
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content));
+    }
+
+    Ok(format!("File deleted successfully: {}", path.display()))
+}
+
+/// Replaces `path` with `content` by writing a sibling temp file and renaming it into place,
+/// so the path always refers to either the old or the new content.
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+    fs::write(&tmp_path, content)?;
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+pub async fn run_swap_files(
+    args: &SwapFilesArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path_a = utils::resolve_within_workspace(workspace_dir, &args.a)?;
+    let path_b = utils::resolve_within_workspace(workspace_dir, &args.b)?;
+
+    for path in [&path_a, &path_b] {
+        if !path.exists() {
+            return Ok(format!(
+                "Error: The path {} does not exist.",
+                path.display()
+            ));
+        }
+        if path.is_dir() {
+            return Ok(format!(
+                "Error: The path {} is a directory. Only files can be swapped.",
+                path.display()
+            ));
+        }
+    }
+
+    let content_a = match fs::read_to_string(&path_a) {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path_a.display(),
+                e
+            ));
+        }
+    };
+    let content_b = match fs::read_to_string(&path_b) {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path_b.display(),
+                e
+            ));
+        }
+    };
+
+    if let Err(e) = write_atomic(&path_a, &content_b) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path_a.display(), e),
+        ));
+    }
+    if let Err(e) = write_atomic(&path_b, &content_a) {
+        // Put the first file back so the swap is all-or-nothing
+        let _ = write_atomic(&path_a, &content_a);
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path_b.display(), e),
+        ));
+    }
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path_a.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content_a));
+        history
+            .entry(path_b.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content_b));
+    }
+
+    Ok(format!(
+        "Swapped contents of {} and {}. Use undo_edit on each file to revert.",
+        path_a.display(),
+        path_b.display()
+    ))
+}
+
+/// Moves a single file, carrying its edit history over to the new path. A text file's
+/// content at the time of the move is pushed onto the destination's history; binary
+/// files are moved as-is and only carry their existing history.
+pub async fn run_move_file(
+    args: &MoveFileArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let source = utils::resolve_within_workspace(workspace_dir, &args.source)?;
+    let destination = utils::resolve_within_workspace(workspace_dir, &args.destination)?;
+
+    if !source.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist.",
+            source.display()
+        ));
+    }
+    if source.is_dir() {
+        return Ok(format!(
+            "Error: The path {} is a directory. Only files can be moved.",
+            source.display()
+        ));
+    }
+
+    let same_path = source == destination
+        || (destination.exists()
+            && fs::canonicalize(&source).ok() == fs::canonicalize(&destination).ok());
+    if same_path {
+        return Ok(format!(
+            "Error: Source and destination are the same path: {}",
+            source.display()
+        ));
+    }
+
+    if destination.exists() {
+        return Ok(format!(
+            "Error: File already exists at: {}. Cannot overwrite files using move_file.",
+            destination.display()
+        ));
+    }
+
+    let content = match fs::read(&source) {
+        Ok(bytes) => String::from_utf8(bytes).ok(),
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                source.display(),
+                e
+            ));
+        }
+    };
+
+    if let Some(parent) = destination.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        return Ok(utils::write_error(
+            &e,
+            format!(
+                "Error: Failed to create parent directories for {}: {}",
+                destination.display(),
+                e
+            ),
+        ));
+    }
+
+    if let Err(e) = move_path(&source, &destination) {
+        return Ok(utils::write_error(
+            &e,
+            format!(
+                "Error: Failed to move {} to {}: {}",
+                source.display(),
+                destination.display(),
+                e
+            ),
+        ));
+    }
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        let mut versions = history.remove(&source).unwrap_or_default();
+        if let Some(content) = content {
+            versions.push(HistoryEntry::Content(content));
+        }
+        if !versions.is_empty() {
+            history.insert(destination.clone(), versions);
+        }
+    }
+
+    Ok(format!(
+        "Moved {} to {}",
+        source.display(),
+        destination.display()
+    ))
+}
+
+/// Renames `source` to `destination`, copying the bytes and removing the source when
+/// they sit on different filesystems.
+fn move_path(source: &Path, destination: &Path) -> std::io::Result<()> {
+    match fs::rename(source, destination) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            fs::copy(source, destination)?;
+            fs::remove_file(source)
+        }
+        result => result,
+    }
+}
+
+/// A file moved by `run_move_glob`, as workspace-relative paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMove {
+    pub from: String,
+    pub to: String,
+}
+
+fn workspace_relative(path: &Path, workspace_dir: &Path) -> String {
+    path.strip_prefix(workspace_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Moves every file matching the pattern into `dest_dir`, keeping basenames. All
+/// destinations are checked before anything moves, and a failed rename rolls back the
+/// moves already made. Returns the output text and the moves performed.
+pub async fn run_move_glob(
+    args: &MoveGlobArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<(String, Vec<FileMove>), McpError> {
+    let dest_dir = utils::resolve_within_workspace(workspace_dir, &args.dest_dir)?;
+    if dest_dir.is_file() {
+        return Ok((
+            format!(
+                "Error: The path {} is a file, not a directory.",
+                dest_dir.display()
+            ),
+            Vec::new(),
+        ));
+    }
+
+    let pattern = utils::workspace_glob(workspace_dir, &args.pattern)?;
+    let paths = match glob::glob(&pattern) {
+        Ok(paths) => paths,
+        Err(e) => {
+            return Ok((
+                format!("Error: Invalid glob pattern '{}': {}", args.pattern, e),
+                Vec::new(),
+            ));
+        }
+    };
+
+    let mut planned: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for source in paths.filter_map(|p| p.ok()).filter(|p| p.is_file()) {
+        utils::resolve_within_workspace(
+            workspace_dir,
+            &workspace_relative(&source, workspace_dir),
+        )?;
+        let Some(file_name) = source.file_name() else {
+            continue;
+        };
+        let target = dest_dir.join(file_name);
+        // Already in place
+        if target == source {
+            continue;
+        }
+        if target.exists() {
+            return Ok((
+                format!(
+                    "Error: Destination {} already exists. No files were moved.",
+                    target.display()
+                ),
+                Vec::new(),
+            ));
+        }
+        if let Some((other, _)) = planned.iter().find(|(_, t)| *t == target) {
+            return Ok((
+                format!(
+                    "Error: {} and {} would both be moved to {}. No files were moved.",
+                    other.display(),
+                    source.display(),
+                    target.display()
+                ),
+                Vec::new(),
+            ));
+        }
+        planned.push((source, target));
+    }
+
+    if planned.is_empty() {
+        return Ok((
+            format!("Error: No files to move match pattern '{}'.", args.pattern),
+            Vec::new(),
+        ));
+    }
+
+    if let Err(e) = fs::create_dir_all(&dest_dir) {
+        return Ok((
+            utils::write_error(
+                &e,
+                format!(
+                    "Error: Failed to create directory {}: {}",
+                    dest_dir.display(),
+                    e
+                ),
+            ),
+            Vec::new(),
+        ));
+    }
+
+    for (i, (source, target)) in planned.iter().enumerate() {
+        if let Err(e) = fs::rename(source, target) {
+            for (moved_source, moved_target) in planned[..i].iter().rev() {
+                let _ = fs::rename(moved_target, moved_source);
+            }
+            return Ok((
+                utils::write_error(
+                    &e,
+                    format!(
+                        "Error: Failed to move {}: {}. No files were moved.",
+                        source.display(),
+                        e
+                    ),
+                ),
+                Vec::new(),
+            ));
+        }
+    }
+
+    // Edit history follows the files
+    {
+        let mut history = editor_history.lock().await;
+        for (source, target) in &planned {
+            if let Some(versions) = history.remove(source) {
+                history.insert(target.clone(), versions);
+            }
+        }
+    }
+
+    let moves: Vec<FileMove> = planned
+        .iter()
+        .map(|(source, target)| FileMove {
+            from: workspace_relative(source, workspace_dir),
+            to: workspace_relative(target, workspace_dir),
+        })
+        .collect();
+    let mut output = format!(
+        "Moved {} file(s) into {}:\n",
+        moves.len(),
+        dest_dir.display()
+    );
+    for m in &moves {
+        output.push_str(&format!("{} -> {}\n", m.from, m.to));
+    }
+    Ok((output, moves))
+}
+
+pub async fn run_sort_file(
+    args: &SortFileArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist. Please check the file path.",
+            path.display()
+        ));
+    }
+
+    let content = fs::read_to_string(&path).map_err(|e| McpError {
+        code: ErrorCode(-32603),
+        message: format!("Failed to read file: {}", e).into(),
+        data: None,
+    })?;
+
+    let case_insensitive = args.case_insensitive.unwrap_or(false);
+    let key = |line: &str| {
+        if case_insensitive {
+            line.to_lowercase()
+        } else {
+            line.to_string()
+        }
+    };
+
+    let mut lines: Vec<&str> = content.lines().collect();
+    lines.sort_by_key(|line| key(line));
+    if args.dedup.unwrap_or(false) {
+        lines.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    let mut new_content = lines.join("\n");
+    if content.ends_with('\n') && !new_content.is_empty() {
+        new_content.push('\n');
+    }
+
+    if new_content == content {
+        return Ok(format!(
+            "The file {} is already sorted. No changes were made.",
+            path.display()
+        ));
+    }
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content.clone()));
+    }
+
+    fs::write(&path, &new_content).map_err(|e| McpError {
+        code: ErrorCode(-32603),
+        message: format!("Failed to write file: {}", e).into(),
+        data: None,
+    })?;
+
+    let snippet = lines
+        .iter()
+        .take(REWRITE_SNIPPET_LINES)
+        .cloned()
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    Ok(format!(
+        "The file {} has been sorted ({} lines). {}Review the changes and make sure they are as expected.",
+        path.display(),
+        lines.len(),
+        make_output(&snippet, &format!("a snippet of {}", path.display()), 1)
+    ))
+}
+
+/// Expands tabs to spaces, advancing to the next multiple of `width` columns.
+fn expand_tabs(line: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.push_str(&" ".repeat(spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+pub async fn run_normalize_whitespace(
+    args: &NormalizeWhitespaceArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist. Please check the file path.",
+            path.display()
+        ));
+    }
+    if args.tabs_to_spaces == Some(0) {
+        return Ok("Error: tabs_to_spaces must be at least 1.".to_string());
+    }
+
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+
+    let trim_trailing = args.trim_trailing.unwrap_or(true);
+    let collapse_blank_lines = args.collapse_blank_lines.unwrap_or(false);
+    let ends_with_newline = content.ends_with('\n');
+    let body = content.strip_suffix('\n').unwrap_or(&content);
+
+    let mut trimmed = 0;
+    let mut expanded = 0;
+    let mut removed = 0;
+    let mut previous_blank = false;
+    let mut lines: Vec<String> = Vec::new();
+    for raw in body.split('\n') {
+        // Keep CRLF line endings as they are
+        let (mut line, cr) = match raw.strip_suffix('\r') {
+            Some(l) => (l.to_string(), "\r"),
+            None => (raw.to_string(), ""),
+        };
+        if trim_trailing {
+            let kept = line.trim_end_matches([' ', '\t']).len();
+            if kept < line.len() {
+                line.truncate(kept);
+                trimmed += 1;
+            }
+        }
+        if let Some(width) = args.tabs_to_spaces
+            && line.contains('\t')
+        {
+            line = expand_tabs(&line, width);
+            expanded += 1;
+        }
+        let is_blank = line.trim().is_empty();
+        if collapse_blank_lines && is_blank && previous_blank {
+            removed += 1;
+            continue;
+        }
+        previous_blank = is_blank;
+        lines.push(format!("{}{}", line, cr));
+    }
+
+    let mut new_content = lines.join("\n");
+    if ends_with_newline {
+        new_content.push('\n');
+    }
+
+    if new_content == content {
+        return Ok(format!(
+            "The whitespace in {} is already normalized. No changes were made.",
+            path.display()
+        ));
+    }
+
+    if let Err(e) = fs::write(&path, &new_content) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path.display(), e),
+        ));
+    }
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content));
+    }
+
+    Ok(format!(
+        "Normalized whitespace in {}: trimmed trailing whitespace on {} line(s), expanded tabs on {} line(s), removed {} blank line(s).",
+        path.display(),
+        trimmed,
+        expanded,
+        removed
+    ))
+}
+
+/// Line-comment token for a file, based on its extension.
+fn comment_token_for(path: &Path) -> &'static str {
+    if path
+        .file_name()
+        .is_some_and(|n| n == "Makefile" || n == "Dockerfile")
+    {
+        return "#";
+    }
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "py" | "pyi" | "sh" | "bash" | "zsh" | "rb" | "pl" | "toml" | "yaml" | "yml" | "r"
+        | "cfg" | "conf" | "ini" | "mk" => "#",
+        "sql" | "lua" | "hs" => "--",
+        _ => "//",
+    }
+}
+
+pub async fn run_toggle_comment(
+    args: &ToggleCommentArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.exists() {
+        return Ok(format!(
+            "Error: The path {} does not exist.",
+            path.display()
+        ));
+    }
+
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+
+    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+    let start = args.start_line as usize;
+    let end = args.end_line as usize;
+    if start == 0 || start > end || end > lines.len() {
+        return Ok(format!(
+            "Error: Invalid line range [{}, {}]. Lines must be within [1, {}].",
+            args.start_line,
+            args.end_line,
+            lines.len()
+        ));
+    }
+
+    let token = args
+        .style
+        .as_deref()
+        .unwrap_or_else(|| comment_token_for(&path));
+    let range = &mut lines[start - 1..end];
+    let is_blank = |line: &str| line.trim().is_empty();
+
+    let commented = range
+        .iter()
+        .filter(|l| !is_blank(l))
+        .all(|l| l.trim_start().starts_with(token));
+    if range.iter().all(|l| is_blank(l)) {
+        return Ok("Error: The selected range only contains blank lines.".to_string());
+    }
+
+    if commented {
+        for line in range.iter_mut().filter(|l| !is_blank(l)) {
+            let indent = line.len() - line.trim_start().len();
+            let rest = &line[indent + token.len()..];
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            *line = format!("{}{}", &line[..indent], rest);
+        }
+    } else {
+        // Comment at the shallowest indentation so the block stays aligned
+        let indent = range
+            .iter()
+            .filter(|l| !is_blank(l))
+            .map(|l| l.len() - l.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        for line in range.iter_mut().filter(|l| !is_blank(l)) {
+            *line = format!("{}{} {}", &line[..indent], token, &line[indent..]);
+        }
+    }
+
+    let mut new_content = lines.join("\n");
+    if content.ends_with('\n') {
+        new_content.push('\n');
+    }
+
+    // Save history
+    {
+        let mut history = editor_history.lock().await;
+        history
+            .entry(path.clone())
+            .or_default()
+            .push(HistoryEntry::Content(content.clone()));
+    }
+
+    if let Err(e) = fs::write(&path, &new_content) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write file {}: {}", path.display(), e),
+        ));
+    }
+
+    let action = if commented {
+        "uncommented"
+    } else {
+        "commented out"
+    };
+
+    // Snippet
+    let start_line = start.saturating_sub(SNIPPET_CONTEXT_WINDOW + 1);
+    let end_line = (end + SNIPPET_CONTEXT_WINDOW).min(lines.len());
+    let output_snippet = lines[start_line..end_line].join("\n");
+
+    Ok(format!(
+        "Lines {}-{} of {} have been {}. {}Review the changes and make sure they are as expected.",
+        start,
+        end,
+        path.display(),
+        action,
+        make_output(
+            &output_snippet,
+            "a snippet of the edited file",
+            start_line + 1
+        )
+    ))
+}
+
+pub async fn run_undo_edit(
+    args: &UndoEditArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+    redo_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    let mut history = editor_history.lock().await;
+    if let Some(versions) = history.get_mut(&path)
+        && let Some(previous) = versions.pop()
+    {
+        let current = HistoryEntry::capture(&path);
+        if let Err(e) = previous.restore(&path) {
+            versions.push(previous);
+            return Ok(utils::write_error(
+                &e,
+                format!("Error: Failed to restore file {}: {}", path.display(), e),
+            ));
+        }
+        if let Ok(current) = current {
+            redo_history
+                .lock()
+                .await
+                .entry(path.clone())
+                .or_default()
+                .push(current);
+        }
+        return Ok(format!(
+            "Last edit to {} undone successfully. {}",
+            path.display(),
+            previous.describe(&path)
+        ));
+    }
+    Ok(format!(
+        "Error: No edit history found for {}",
+        path.display()
+    ))
+}
+
+/// Diffs the current content of `args.path` against `args.against`, or against the version
+/// saved before its last edit. A file that did not exist before the edit diffs as empty.
+pub async fn run_diff(
+    args: &DiffArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+    let previous = editor_history
+        .lock()
+        .await
+        .get(&path)
+        .and_then(|versions| versions.last().cloned());
+    let (old, old_label) = match &args.against {
+        Some(against) => {
+            let against_path = utils::resolve_within_workspace(workspace_dir, against)?;
+            match tokio::fs::read_to_string(&against_path).await {
+                Ok(content) => (content, against.clone()),
+                Err(e) => {
+                    return Ok(format!(
+                        "Error: Failed to read file {}: {}",
+                        against_path.display(),
+                        e
+                    ));
+                }
+            }
+        }
+        None => match previous {
+            Some(HistoryEntry::Content(content)) => {
+                (content, format!("{} (before last edit)", args.path))
+            }
+            Some(HistoryEntry::Absent) => (String::new(), "/dev/null".to_string()),
+            None => {
+                return Ok(format!(
+                    "Error: No edit history found for {}",
+                    path.display()
+                ));
+            }
+        },
+    };
+    let new = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && args.against.is_none() => {
+            String::new()
+        }
+        Err(e) => {
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+
+    if old == new {
+        return Ok(format!(
+            "No differences between {} and {}.",
+            old_label, args.path
+        ));
+    }
+    Ok(similar::TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .header(&old_label, &args.path)
+        .to_string())
+}
+
+/// Re-applies the edit most recently reverted by `undo_edit`, which can then be undone again.
+pub async fn run_redo_edit(
+    args: &RedoEditArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+    redo_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    // Same lock order as undo_edit: editor history first, then redo history
+    let mut history = editor_history.lock().await;
+    let mut redo = redo_history.lock().await;
+    let Some(next) = redo.get_mut(&path).and_then(|versions| versions.pop()) else {
+        return Ok(format!(
+            "Error: No undone edit to redo for {}",
+            path.display()
+        ));
+    };
+
+    let current = match HistoryEntry::capture(&path) {
+        Ok(current) => current,
+        Err(e) => {
+            redo.entry(path.clone()).or_default().push(next);
+            return Ok(format!(
+                "Error: Failed to read file {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    };
+    if let Err(e) = next.restore(&path) {
+        redo.entry(path.clone()).or_default().push(next);
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to write to {}: {}", path.display(), e),
+        ));
+    }
+    history.entry(path.clone()).or_default().push(current);
+
+    Ok(format!(
+        "Last undone edit to {} redone successfully. {}",
+        path.display(),
+        next.describe(&path)
+    ))
+}
+
+pub async fn run_revert_file(
+    args: &RevertFileArgs,
+    workspace_dir: &Path,
+    editor_history: &Mutex<HashMap<PathBuf, Vec<HistoryEntry>>>,
+) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    let mut history = editor_history.lock().await;
+    let Some(original) = history.get(&path).and_then(|versions| versions.first()) else {
+        return Ok(format!("No edits to revert for {}", path.display()));
+    };
+
+    if let Err(e) = original.restore(&path) {
+        return Ok(utils::write_error(
+            &e,
+            format!("Error: Failed to restore file {}: {}", path.display(), e),
+        ));
+    }
+    let edits = history.remove(&path).map_or(0, |versions| versions.len());
+
+    Ok(format!(
+        "Reverted {} edit(s) to {}; the file is back to its original content.",
+        edits,
+        path.display()
+    ))
+}
+
+pub fn run_tree(
+    args: &TreeArgs,
+    workspace_dir: &Path,
+) -> Result<String, McpError> {
+    match build_tree(args, workspace_dir)? {
+        Tree::Text(text) => Ok(text),
+        Tree::Json(root) => serde_json::to_string(&root).map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to serialize tree: {}", e).into(),
+            data: None,
+        }),
+    }
+}
+
+/// A rendered `tree`: indented text, or the nested structure for `format: "json"`.
+pub enum Tree {
+    Text(String),
+    Json(TreeNode),
+}
+
+/// Builds the tree `run_tree` renders.
+pub fn build_tree(args: &TreeArgs, workspace_dir: &Path) -> Result<Tree, McpError> {
+    let rel_path = args.path.as_deref().unwrap_or(".");
+    let root_path = utils::resolve_within_workspace(workspace_dir, rel_path)?;
+
+    if !root_path.exists() {
+        return Err(McpError {
+            code: ErrorCode(-32602),
+            message: format!("Path does not exist: {}", root_path.display()).into(),
+            data: None,
+        });
+    }
+
+    let max_depth = args.max_depth.unwrap_or(usize::MAX);
+    let truncate = args.truncate.unwrap_or(10);
+
+    let exclude = tree_exclude_patterns(args.exclude.as_deref().unwrap_or(""))?;
+
+    let options = TreeOptions {
+        max_depth,
+        truncate,
+        root: &root_path,
+        exclude: &exclude,
+        include_hidden: args.include_hidden.unwrap_or(false),
+    };
+
+    match args.format.as_deref().unwrap_or("text") {
+        "text" => {
+            let mut output = String::new();
+            // Add root
+            output.push_str(&format!("{}\n", rel_path));
+            visit_dirs(&root_path, &mut output, "", 0, &options)?;
+            Ok(Tree::Text(output))
+        }
+        "json" => Ok(Tree::Json(TreeNode {
+            name: rel_path.to_string(),
+            kind: EntryKind::Dir,
+            children: Some(tree_children(&root_path, 0, &options)?),
+        })),
+        other => Err(McpError {
+            code: ErrorCode(-32602),
+            message: format!("Unknown tree format '{}'; expected text or json", other).into(),
+            data: None,
+        }),
+    }
+}
+
+/// Settings shared by every level of a `tree` walk.
+struct TreeOptions<'a> {
+    max_depth: usize,
+    truncate: usize,
+    root: &'a Path,
+    exclude: &'a [glob::Pattern],
+    include_hidden: bool,
+}
+
+impl TreeOptions<'_> {
+    /// Whether an exclude pattern matches the entry's name or its path relative to the root.
+    fn is_excluded(&self, name: &str, path: &Path) -> bool {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let relative = path.strip_prefix(self.root).unwrap_or(path);
+        self.exclude.iter().any(|pattern| {
+            pattern.matches_with(name, options) || pattern.matches_path_with(relative, options)
+        })
+    }
+}
+
+/// Parses comma-separated exclude patterns. A `dir/**` pattern also excludes `dir` itself.
+fn tree_exclude_patterns(exclude: &str) -> Result<Vec<glob::Pattern>, McpError> {
+    let mut patterns = Vec::new();
+    for item in exclude.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut sources = vec![item];
+        if let Some(dir) = item.strip_suffix("/**") {
+            sources.push(dir);
+        }
+        for source in sources {
+            let pattern = glob::Pattern::new(source).map_err(|e| McpError {
+                code: ErrorCode(-32602),
+                message: format!("Invalid exclude pattern '{}': {}", item, e).into(),
+                data: None,
+            })?;
+            patterns.push(pattern);
+        }
+    }
+    Ok(patterns)
+}
+
+/// The entries of `dir` shown by `tree`, sorted by name and cut to `options.truncate`, along
+/// with the number of entries left out.
+fn tree_entries(
+    dir: &Path,
+    options: &TreeOptions,
+) -> Result<(Vec<(String, PathBuf)>, usize), McpError> {
+    let entries = fs::read_dir(dir).map_err(|e| McpError {
+        code: ErrorCode(-32603),
+        message: format!("Failed to read directory: {}", e).into(),
+        data: None,
+    })?;
+
+    let mut entries_vec = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to read entry: {}", e).into(),
+            data: None,
+        })?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // Filter excludes and hidden files
+        let path = entry.path();
+        let hidden = name.starts_with('.') && !options.include_hidden;
+        if !hidden && !options.is_excluded(&name, &path) {
+            entries_vec.push((name, path));
+        }
+    }
+
+    entries_vec.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let total_count = entries_vec.len();
+    let mut display_entries = entries_vec;
+    let mut remaining = 0;
+
+    if total_count > options.truncate {
+        remaining = total_count - options.truncate;
+        display_entries.truncate(options.truncate);
+    }
+
+    Ok((display_entries, remaining))
+}
+
+/// Builds the nested JSON form of `tree`.
+fn tree_children(
+    dir: &Path,
+    current_depth: usize,
+    options: &TreeOptions,
+) -> Result<Vec<TreeChild>, McpError> {
+    if current_depth >= options.max_depth {
+        return Ok(Vec::new());
+    }
+
+    let (entries, remaining) = tree_entries(dir, options)?;
+    let mut children = Vec::with_capacity(entries.len() + 1);
+    for (name, path) in entries {
+        let node = if path.is_dir() {
+            TreeNode {
+                name,
+                kind: EntryKind::Dir,
+                children: Some(tree_children(&path, current_depth + 1, options)?),
+            }
+        } else {
+            TreeNode {
+                name,
+                kind: EntryKind::File,
+                children: None,
+            }
+        };
+        children.push(TreeChild::Node(node));
+    }
+    if remaining > 0 {
+        children.push(TreeChild::Truncated {
+            truncated: remaining,
+        });
+    }
+    Ok(children)
+}
+
+fn visit_dirs(
+    dir: &Path,
+    output: &mut String,
+    prefix: &str,
+    current_depth: usize,
+    options: &TreeOptions,
+) -> Result<(), McpError> {
+    if current_depth >= options.max_depth {
+        return Ok(());
+    }
+
+    let (display_entries, remaining) = tree_entries(dir, options)?;
+
+    for (i, (name, path)) in display_entries.iter().enumerate() {
+        let is_last_entry = i == display_entries.len() - 1;
+        let show_more = is_last_entry && remaining > 0;
+
+        // connector depends on whether this is logically the last thing printed.
+        // If we show more, this is NOT the last thing strings-wise.
+        let connector = if !show_more && is_last_entry {
+            "└── "
+        } else {
+            "├── "
+        };
+
+        output.push_str(&format!("{}{}{}\n", prefix, connector, name));
+
+        if path.is_dir() {
+            let new_prefix = if !show_more && is_last_entry {
+                format!("{}    ", prefix)
+            } else {
+                format!("{}│   ", prefix)
+            };
+            visit_dirs(path, output, &new_prefix, current_depth + 1, options)?;
+        }
+
+        if show_more {
+            output.push_str(&format!("{}{}... ({} more)\n", prefix, "└── ", remaining));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // ========== str_replace tests ==========
+
+    #[tokio::test]
+    async fn test_str_replace_basic() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        let args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "world".to_string(),
+            new_str: "rust".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_str_replace(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "hello rust");
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_not_found() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        let args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "nonexistent".to_string(),
+            new_str: "replacement".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_str_replace(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("did not appear verbatim"));
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_multiple_occurrences() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "hello hello hello").unwrap();
+
+        let args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "hello".to_string(),
+            new_str: "world".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_str_replace(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("Multiple occurrences"));
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_replace_all() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.py");
+        fs::write(&file_path, "count = 0\ncount += 1\nprint(count)\n").unwrap();
+
+        let args = StrReplaceArgs {
+            path: "test.py".to_string(),
+            old_str: "count".to_string(),
+            new_str: "total".to_string(),
+            replace_all: Some(true),
+            ..Default::default()
+        };
+        let output = run_str_replace(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("Replaced 3 occurrence(s)"), "{}", output);
+        assert!(output.contains("     1\ttotal = 0"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "total = 0\ntotal += 1\nprint(total)\n"
+        );
+
+        // A single undo restores every occurrence
+        let undo_args = UndoEditArgs {
+            path: "test.py".to_string(),
+        };
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "count = 0\ncount += 1\nprint(count)\n"
+        );
+        assert!(history.lock().await[&file_path].is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_selected_occurrence() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "item\nitem\nitem\n").unwrap();
+
+        let args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "item".to_string(),
+            new_str: "second".to_string(),
+            occurrence: Some(2),
+            ..Default::default()
+        };
+        let output = run_str_replace(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("has been edited"), "{}", output);
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "item\nsecond\nitem\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_occurrence_out_of_range() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "item\nitem\nitem\n").unwrap();
+
+        for occurrence in [0, 4] {
+            let args = StrReplaceArgs {
+                path: "test.txt".to_string(),
+                old_str: "item".to_string(),
+                new_str: "other".to_string(),
+                occurrence: Some(occurrence),
+                ..Default::default()
+            };
+            let output = run_str_replace(&args, dir.path(), &history).await.unwrap();
+            assert!(output.contains("out of range"), "{}", output);
+            assert!(output.contains("appears 3 time(s)"));
+        }
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "item\nitem\nitem\n"
+        );
+        assert!(history.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_same_string() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        let args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "world".to_string(),
+            new_str: "world".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_str_replace(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("must be different"));
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_file_not_found() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = StrReplaceArgs {
+            path: "nonexistent.txt".to_string(),
+            old_str: "old".to_string(),
+            new_str: "new".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_str_replace(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("does not exist"));
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_multiline() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2\nline3").unwrap();
+
+        let args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "line2".to_string(),
+            new_str: "modified".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_str_replace(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "line1\nmodified\nline3");
+    }
+
+    // ========== view_file tests ==========
+
+    #[tokio::test]
+    async fn test_view_file_basic() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2\nline3\nline4\nline5").unwrap();
+
+        let args = ViewFileArgs {
+            path: "test.txt".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_view_file(&args, dir.path()).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("line1"));
+        assert!(output.contains("line5"));
+    }
+
+    #[tokio::test]
+    async fn test_view_file_with_range() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2\nline3\nline4\nline5").unwrap();
+
+        let args = ViewFileArgs {
+            path: "test.txt".to_string(),
+            start_line: Some(2),
+            end_line: Some(4),
+            ..Default::default()
+        };
+
+        let result = run_view_file(&args, dir.path()).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(!output.contains("line1"));
+        assert!(output.contains("line2"));
+        assert!(output.contains("line4"));
+        assert!(!output.contains("line5"));
+    }
+
+    #[tokio::test]
+    async fn test_view_file_invalid_start_line() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2\nline3").unwrap();
+
+        let args = ViewFileArgs {
+            path: "test.txt".to_string(),
+            start_line: Some(10),
+            ..Default::default()
+        };
+
+        let result = run_view_file(&args, dir.path()).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("start_line"));
+    }
+
+    #[tokio::test]
+    async fn test_view_file_empty_file_with_end_line() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("empty.txt"), "").unwrap();
+
+        let args = ViewFileArgs {
+            path: "empty.txt".to_string(),
+            end_line: Some(5),
+            ..Default::default()
+        };
+        let ranged = run_view_file(&args, dir.path()).await.unwrap();
+        assert!(!ranged.contains("Error"), "{}", ranged);
+
+        let args = ViewFileArgs {
+            end_line: None,
+            ..args
+        };
+        assert_eq!(run_view_file(&args, dir.path()).await.unwrap(), ranged);
+    }
+
+    #[tokio::test]
+    async fn test_view_file_invalid_range() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2\nline3").unwrap();
+
+        let args = ViewFileArgs {
+            path: "test.txt".to_string(),
+            start_line: Some(3),
+            end_line: Some(1),
+            ..Default::default()
+        };
+
+        let result = run_view_file(&args, dir.path()).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("greater than or equal to"));
+    }
+
+    #[tokio::test]
+    async fn test_view_file_not_found() {
+        let dir = tempdir().unwrap();
+
+        let args = ViewFileArgs {
+            path: "nonexistent.txt".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_view_file(&args, dir.path()).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("does not exist"));
+    }
+
+    #[tokio::test]
+    async fn test_view_file_range_of_large_file() {
+        let dir = tempdir().unwrap();
+        let content: String = (1..=200_000).map(|i| format!("line {}\n", i)).collect();
+        fs::write(dir.path().join("big.log"), content).unwrap();
+        let args = ViewFileArgs {
+            path: "big.log".to_string(),
+            start_line: Some(100_000),
+            end_line: Some(100_002),
+            ..Default::default()
+        };
+
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert_eq!(
+            output,
+            utils::make_numbered_output("line 100000\nline 100001\nline 100002", 100_000)
+        );
+
+        let args = ViewFileArgs {
+            start_line: Some(200_001),
+            end_line: Some(200_005),
+            ..args
+        };
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert!(output.contains("range [1, 200000]"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_view_file_does_not_block_runtime() {
+        let dir = tempdir().unwrap();
+        let line = format!("{}\n", "x".repeat(99));
+        fs::write(dir.path().join("big.txt"), line.repeat(200_000)).unwrap();
+        let args = ViewFileArgs {
+            path: "big.txt".to_string(),
+            ..Default::default()
+        };
+
+        // On the single-threaded test runtime the timer only fires first if the read yields
+        let order = Mutex::new(Vec::new());
+        tokio::join!(
+            async {
+                run_view_file(&args, dir.path()).await.unwrap();
+                order.lock().await.push("view");
+            },
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+                order.lock().await.push("timer");
+            }
+        );
+        assert_eq!(*order.lock().await, vec!["timer", "view"]);
+    }
+
+    #[tokio::test]
+    async fn test_view_file_on_directory() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+
+        let mut args = ViewFileArgs {
+            path: "src".to_string(),
+            ..Default::default()
+        };
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert!(output.contains("is a directory"), "{}", output);
+        assert!(output.contains("list_directory"));
+
+        args.filter = Some("fn".to_string());
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert!(output.contains("is a directory"), "{}", output);
+
+        args.list_if_directory = Some(true);
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert_eq!(output, "lib.rs (1 line)");
+    }
+
+    #[tokio::test]
+    async fn test_view_file_max_line_width() {
+        let dir = tempdir().unwrap();
+        let long_line = "x".repeat(5000);
+        fs::write(
+            dir.path().join("min.js"),
+            format!("short\n{}\nend", long_line),
+        )
+        .unwrap();
+
+        let args = ViewFileArgs {
+            path: "min.js".to_string(),
+            max_line_width: Some(80),
+            ..Default::default()
+        };
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("short"));
+        assert!(lines[1].ends_with(&format!("{}…(+4920 chars)", "x".repeat(80))));
+        assert!(lines[2].ends_with("end"));
+    }
+
+    #[tokio::test]
+    async fn test_view_file_binary() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("image.bin"),
+            b"\x89PNG\r\n\x00\x00\x00\rIHDR",
+        )
+        .unwrap();
+
+        let args = ViewFileArgs {
+            path: "image.bin".to_string(),
+            ..Default::default()
+        };
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert!(output.starts_with("Error: "), "{}", output);
+        assert!(output.contains("appears to be a binary file (14 bytes) and cannot be displayed."));
+    }
+
+    #[tokio::test]
+    async fn test_view_file_gzip() {
+        let dir = tempdir().unwrap();
+        let file = fs::File::create(dir.path().join("app.log.gz")).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        encoder
+            .write_all(b"first entry\nsecond entry\nthird entry\n")
+            .unwrap();
+        encoder.finish().unwrap();
+
+        let args = ViewFileArgs {
+            path: "app.log.gz".to_string(),
+            start_line: Some(2),
+            ..Default::default()
+        };
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert_eq!(output, "     2\tsecond entry\n     3\tthird entry");
+    }
+
+    // ========== create_file tests ==========
+
+    #[tokio::test]
+    async fn test_create_file_basic() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = CreateFileArgs {
+            path: "new_file.txt".to_string(),
+            content: "hello world".to_string(),
+            executable: None,
+        };
+
+        let result = run_create_file(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let file_path = dir.path().join("new_file.txt");
+        assert!(file_path.exists());
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "hello world");
+    }
+
+    #[tokio::test]
+    async fn test_create_file_already_exists() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("existing.txt");
+        fs::write(&file_path, "existing content").unwrap();
+
+        let args = CreateFileArgs {
+            path: "existing.txt".to_string(),
+            content: "new content".to_string(),
+            executable: None,
+        };
+
+        let result = run_create_file(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("already exists"));
+    }
+
+    #[tokio::test]
+    async fn test_create_file_with_parent_dirs() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = CreateFileArgs {
+            path: "subdir/nested/file.txt".to_string(),
+            content: "nested content".to_string(),
+            executable: None,
+        };
+
+        let result = run_create_file(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let file_path = dir.path().join("subdir/nested/file.txt");
+        assert!(file_path.exists());
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "nested content");
+    }
+
+    #[tokio::test]
+    async fn test_create_file_empty_content() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = CreateFileArgs {
+            path: "empty.txt".to_string(),
+            content: "".to_string(),
+            executable: None,
+        };
+
+        let result = run_create_file(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let file_path = dir.path().join("empty.txt");
+        assert!(file_path.exists());
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_create_file_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let args = CreateFileArgs {
+            path: "run.sh".to_string(),
+            content: "#!/bin/sh\necho ok\n".to_string(),
+            executable: Some(true),
+        };
+        let output = run_create_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("created successfully"), "{}", output);
+        let mode = fs::metadata(dir.path().join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o100, 0o100);
+
+        // Files stay non-executable by default
+        let args = CreateFileArgs {
+            path: "plain.txt".to_string(),
+            content: "text".to_string(),
+            executable: None,
+        };
+        run_create_file(&args, dir.path(), &history).await.unwrap();
+        let mode = fs::metadata(dir.path().join("plain.txt"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o111, 0);
+    }
+
+    // ========== create_files tests ==========
+
+    #[tokio::test]
+    async fn test_create_files_batch() {
+        let dir = tempdir().unwrap();
+        let args = CreateFilesArgs {
+            files: vec![
+                NewFile {
+                    path: "pkg/mod.rs".to_string(),
+                    content: "pub mod a;\n".to_string(),
+                },
+                NewFile {
+                    path: "pkg/a.rs".to_string(),
+                    content: "pub fn a() {}\n".to_string(),
+                },
+                NewFile {
+                    path: "README.md".to_string(),
+                    content: "# pkg\n".to_string(),
+                },
+            ],
+        };
+
+        let output = run_create_files(&args, dir.path()).await.unwrap();
+        assert!(output.contains("Created 3 files"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pkg/mod.rs")).unwrap(),
+            "pub mod a;\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pkg/a.rs")).unwrap(),
+            "pub fn a() {}\n"
+        );
+        assert!(dir.path().join("README.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_create_files_rolls_back_when_one_exists() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("existing.txt"), "keep me").unwrap();
+
+        let args = CreateFilesArgs {
+            files: vec![
+                NewFile {
+                    path: "new/one.txt".to_string(),
+                    content: "1".to_string(),
+                },
+                NewFile {
+                    path: "two.txt".to_string(),
+                    content: "2".to_string(),
+                },
+                NewFile {
+                    path: "existing.txt".to_string(),
+                    content: "overwritten".to_string(),
+                },
+            ],
+        };
+
+        let output = run_create_files(&args, dir.path()).await.unwrap();
+        assert!(output.contains("Error: File already exists"));
+        assert!(output.contains("No files were created"));
+        assert!(!dir.path().join("new").exists());
+        assert!(!dir.path().join("two.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("existing.txt")).unwrap(),
+            "keep me"
+        );
+    }
+
+    // ========== insert_lines tests ==========
+
+    #[tokio::test]
+    async fn test_insert_lines_basic() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2\nline3").unwrap();
+
+        let args = InsertLinesArgs {
+            path: "test.txt".to_string(),
+            insert_line: 2,
+            content: "inserted".to_string(),
+        };
+
+        let result = run_insert_lines(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[0], "line1");
+        assert_eq!(lines[1], "inserted");
+        assert_eq!(lines[2], "line2");
+    }
+
+    #[tokio::test]
+    async fn test_insert_lines_at_beginning() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2").unwrap();
+
+        let args = InsertLinesArgs {
+            path: "test.txt".to_string(),
+            insert_line: 1,
+            content: "first".to_string(),
+        };
+
+        let result = run_insert_lines(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[0], "first");
+        assert_eq!(lines[1], "line1");
+    }
+
+    #[tokio::test]
+    async fn test_insert_lines_at_end() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2").unwrap();
+
+        let args = InsertLinesArgs {
+            path: "test.txt".to_string(),
+            insert_line: 3,
+            content: "last".to_string(),
+        };
+
+        let result = run_insert_lines(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[2], "last");
+    }
+
+    #[tokio::test]
+    async fn test_insert_lines_multi_line_block() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2\nline3\nline4").unwrap();
+
+        let args = InsertLinesArgs {
+            path: "test.txt".to_string(),
+            insert_line: 3,
+            content: "a\nb\nc".to_string(),
+        };
+        let output = run_insert_lines(&args, dir.path(), &history).await.unwrap();
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            vec!["line1", "line2", "a", "b", "c", "line3", "line4"]
+        );
+        // Snippet line numbers match the new file
+        assert!(output.contains("     3\ta\n     4\tb\n     5\tc\n     6\tline3"));
+        assert!(output.contains("     7\tline4"));
+
+        // A following insert lands where its line number says
+        let args = InsertLinesArgs {
+            path: "test.txt".to_string(),
+            insert_line: 6,
+            content: "before line3".to_string(),
+        };
+        run_insert_lines(&args, dir.path(), &history).await.unwrap();
+        let content = fs::read_to_string(&file_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[5], "before line3");
+        assert_eq!(lines[6], "line3");
+    }
+
+    #[tokio::test]
+    async fn test_insert_lines_invalid_line() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2").unwrap();
+
+        let args = InsertLinesArgs {
+            path: "test.txt".to_string(),
+            insert_line: 100,
+            content: "invalid".to_string(),
+        };
+
+        let result = run_insert_lines(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("insert_line"));
+    }
+
+    #[tokio::test]
+    async fn test_insert_lines_file_not_found() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = InsertLinesArgs {
+            path: "nonexistent.txt".to_string(),
+            insert_line: 1,
+            content: "content".to_string(),
+        };
+
+        let result = run_insert_lines(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("does not exist"));
+    }
+
+    // ========== insert_near tests ==========
+
+    #[tokio::test]
+    async fn test_insert_near_before_and_after_anchor() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("lib.rs");
+        fs::write(&file_path, "use a;\n\nfn main() {\n    run();\n}\n").unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = InsertNearArgs {
+            path: "lib.rs".to_string(),
+            anchor: "fn main".to_string(),
+            position: "before".to_string(),
+            content: "use b;\nuse c;".to_string(),
+        };
+        let output = run_insert_near(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("has been edited"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "use a;\n\nuse b;\nuse c;\nfn main() {\n    run();\n}\n"
+        );
+
+        let args = InsertNearArgs {
+            path: "lib.rs".to_string(),
+            anchor: "run();".to_string(),
+            position: "after".to_string(),
+            content: "    stop();".to_string(),
+        };
+        run_insert_near(&args, dir.path(), &history).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "use a;\n\nuse b;\nuse c;\nfn main() {\n    run();\n    stop();\n}\n"
+        );
+        assert_eq!(history.lock().await.get(&file_path).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_insert_near_ambiguous_anchor() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "item\nother\nitem\n").unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = InsertNearArgs {
+            path: "test.txt".to_string(),
+            anchor: "item".to_string(),
+            position: "after".to_string(),
+            content: "new".to_string(),
+        };
+        let output = run_insert_near(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("[1, 3]"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "item\nother\nitem\n"
+        );
+        assert!(history.lock().await.is_empty());
+    }
+
+    // ========== append_to_file tests ==========
+
+    #[tokio::test]
+    async fn test_append_to_file() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("notes.txt");
+        fs::write(&file_path, "first").unwrap();
+
+        let args = AppendFileArgs {
+            path: "notes.txt".to_string(),
+            content: "second\n".to_string(),
+        };
+        let output = run_append_to_file(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert!(output.contains("     2\tsecond"), "{}", output);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "first\nsecond\n");
+        assert_eq!(
+            history.lock().await[&file_path],
+            vec![HistoryEntry::Content("first".to_string())]
+        );
+
+        // Missing files are created
+        let args = AppendFileArgs {
+            path: "logs/run.log".to_string(),
+            content: "started\n".to_string(),
+        };
+        run_append_to_file(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("logs/run.log")).unwrap(),
+            "started\n"
+        );
+        assert_eq!(
+            history.lock().await[&dir.path().join("logs/run.log")],
+            vec![HistoryEntry::Absent]
+        );
+    }
+
+    // ========== delete_file tests ==========
+
+    #[tokio::test]
+    async fn test_delete_file_basic() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("to_delete.txt");
+        fs::write(&file_path, "content").unwrap();
+        assert!(file_path.exists());
+
+        let args = DeleteFileArgs {
+            path: "to_delete.txt".to_string(),
+        };
+
+        let result = run_delete_file(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        assert!(!file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_file_not_found() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+
+        let args = DeleteFileArgs {
+            path: "nonexistent.txt".to_string(),
+        };
+
+        let result = run_delete_file(&args, dir.path(), &history).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("does not exist"));
+    }
+
+    // ========== undo_edit tests ==========
+
+    #[tokio::test]
+    async fn test_undo_edit_after_str_replace() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        // First, do a str_replace
+        let replace_args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "world".to_string(),
+            new_str: "rust".to_string(),
+            ..Default::default()
+        };
+        run_str_replace(&replace_args, dir.path(), &history)
+            .await
+            .unwrap();
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "hello rust");
+
+        // Now undo
+        let undo_args = UndoEditArgs {
+            path: "test.txt".to_string(),
+        };
+        let result = run_undo_edit(&undo_args, dir.path(), &history, &redo).await;
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "hello world");
+    }
+
+    #[tokio::test]
+    async fn test_diff_against_history() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        fs::write(dir.path().join("test.txt"), content).unwrap();
+
+        let args = DiffArgs {
+            path: "test.txt".to_string(),
+            against: None,
+        };
+        let output = run_diff(&args, dir.path(), &history).await.unwrap();
+        assert!(output.starts_with("Error: No edit history"), "{}", output);
+
+        let replace_args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "line 8\n".to_string(),
+            new_str: "eight\n".to_string(),
+            ..Default::default()
+        };
+        run_str_replace(&replace_args, dir.path(), &history)
+            .await
+            .unwrap();
+
+        let output = run_diff(&args, dir.path(), &history).await.unwrap();
+        assert_eq!(
+            output,
+            "--- test.txt (before last edit)\n+++ test.txt\n@@ -5,6 +5,6 @@\n line 5\n line 6\n line 7\n-line 8\n+eight\n line 9\n line 10\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diff_against_other_file() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        fs::write(dir.path().join("a.txt"), "same\nold\n").unwrap();
+        fs::write(dir.path().join("b.txt"), "same\nnew\n").unwrap();
+
+        let args = DiffArgs {
+            path: "b.txt".to_string(),
+            against: Some("a.txt".to_string()),
+        };
+        let output = run_diff(&args, dir.path(), &history).await.unwrap();
+        assert_eq!(
+            output,
+            "--- a.txt\n+++ b.txt\n@@ -1,2 +1,2 @@\n same\n-old\n+new\n"
+        );
+
+        let args = DiffArgs {
+            path: "a.txt".to_string(),
+            against: Some("a.txt".to_string()),
+        };
+        let output = run_diff(&args, dir.path(), &history).await.unwrap();
+        assert_eq!(output, "No differences between a.txt and a.txt.");
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_to_several_files() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        fs::write(dir.path().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(dir.path().join("gone.txt"), "bye\n").unwrap();
+
+        let args = ApplyPatchArgs {
+            patch: "--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,4 @@\n one\n-two\n+2\n+2.5\n three\n\
+                    --- /dev/null\n+++ b/new/b.txt\n@@ -0,0 +1 @@\n+hello\n\
+                    --- a/gone.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n"
+                .to_string(),
+        };
+        let (output, changes) = run_apply_patch(&args, dir.path(), &history).await.unwrap();
+        assert_eq!(
+            output,
+            "Applied patch to 3 file(s):\n  a.txt: -1 +2\n  new/b.txt: -0 +1 (created)\n  gone.txt: -1 +0 (deleted)\n"
+        );
+        assert_eq!(
+            changes,
+            vec![
+                (MutationOp::Edit, "a.txt".to_string()),
+                (MutationOp::Create, "new/b.txt".to_string()),
+                (MutationOp::Delete, "gone.txt".to_string()),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "one\n2\n2.5\nthree\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("new/b.txt")).unwrap(),
+            "hello\n"
+        );
+        assert!(!dir.path().join("gone.txt").exists());
+
+        // Each file is reverted with undo_edit
+        for path in ["a.txt", "new/b.txt", "gone.txt"] {
+            let undo_args = UndoEditArgs {
+                path: path.to_string(),
+            };
+            run_undo_edit(&undo_args, dir.path(), &history, &redo)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "one\ntwo\nthree\n"
+        );
+        assert!(!dir.path().join("new/b.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("gone.txt")).unwrap(),
+            "bye\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_rejects_context_mismatch() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.path().join("b.txt"), "alpha\nbeta\n").unwrap();
+
+        // The first file would patch cleanly, but the second hunk's context is stale
+        let args = ApplyPatchArgs {
+            patch: "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+TWO\n\
+                    --- a/b.txt\n+++ b/b.txt\n@@ -1,2 +1,2 @@\n gamma\n-beta\n+BETA\n"
+                .to_string(),
+        };
+        let (output, changes) = run_apply_patch(&args, dir.path(), &history).await.unwrap();
+        assert!(
+            output.starts_with("Error: Hunk '@@ -1,2 +1,2 @@' does not apply to b.txt"),
+            "{}",
+            output
+        );
+        assert!(changes.is_empty());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+            "alpha\nbeta\n"
+        );
+        assert!(history.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_undo_edit_after_delete() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("nested/keep.txt");
+        fs::create_dir_all(dir.path().join("nested")).unwrap();
+        fs::write(&file_path, "precious").unwrap();
+
+        let args = DeleteFileArgs {
+            path: "nested/keep.txt".to_string(),
+        };
+        run_delete_file(&args, dir.path(), &history).await.unwrap();
+        fs::remove_dir(dir.path().join("nested")).unwrap();
+
+        let undo_args = UndoEditArgs {
+            path: "nested/keep.txt".to_string(),
+        };
+        let output = run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert!(output.contains("undone successfully"), "{}", output);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "precious");
+
+        // Redoing deletes the file again
+        let redo_args = RedoEditArgs {
+            path: "nested/keep.txt".to_string(),
+        };
+        run_redo_edit(&redo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert!(!file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_undo_edit_after_create() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("new.txt");
+
+        let args = CreateFileArgs {
+            path: "new.txt".to_string(),
+            content: "draft".to_string(),
+            executable: None,
+        };
+        run_create_file(&args, dir.path(), &history).await.unwrap();
+        assert_eq!(history.lock().await[&file_path], vec![HistoryEntry::Absent]);
+
+        let undo_args = UndoEditArgs {
+            path: "new.txt".to_string(),
+        };
+        let output = run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert!(output.contains("no longer exists"), "{}", output);
+        assert!(!file_path.exists());
+
+        let redo_args = RedoEditArgs {
+            path: "new.txt".to_string(),
+        };
+        run_redo_edit(&redo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "draft");
+    }
+
+    #[tokio::test]
+    async fn test_redo_edit_after_undo() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        let replace_args = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "world".to_string(),
+            new_str: "rust".to_string(),
+            ..Default::default()
+        };
+        run_str_replace(&replace_args, dir.path(), &history)
+            .await
+            .unwrap();
+        let undo_args = UndoEditArgs {
+            path: "test.txt".to_string(),
+        };
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "hello world");
+
+        let redo_args = RedoEditArgs {
+            path: "test.txt".to_string(),
+        };
+        let output = run_redo_edit(&redo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert!(output.contains("redone successfully"), "{}", output);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "hello rust");
+
+        // Nothing left to redo, but the redone edit can be undone again
+        let output = run_redo_edit(&redo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert!(output.starts_with("Error: No undone edit"));
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "hello world");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_undo_and_redo_do_not_deadlock() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "current").unwrap();
+        let versions = vec![HistoryEntry::Content("older".to_string()); 50];
+        let history = std::sync::Arc::new(Mutex::new(HashMap::from([(
+            file_path.clone(),
+            versions.clone(),
+        )])));
+        let redo = std::sync::Arc::new(Mutex::new(HashMap::from([(file_path, versions)])));
+
+        let mut tasks = Vec::new();
+        for i in 0..100 {
+            let (history, redo) = (history.clone(), redo.clone());
+            let workspace = dir.path().to_path_buf();
+            tasks.push(tokio::spawn(async move {
+                let path = "test.txt".to_string();
+                if i % 2 == 0 {
+                    run_undo_edit(&UndoEditArgs { path }, &workspace, &history, &redo).await
+                } else {
+                    run_redo_edit(&RedoEditArgs { path }, &workspace, &history, &redo).await
+                }
+            }));
+        }
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            for task in tasks {
+                task.await.unwrap().unwrap();
+            }
+        })
+        .await;
+        assert!(finished.is_ok(), "undo and redo deadlocked");
+    }
+
+    #[tokio::test]
+    async fn test_undo_edit_after_insert_lines() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "line1\nline2").unwrap();
+
+        // Insert a line
+        let insert_args = InsertLinesArgs {
+            path: "test.txt".to_string(),
+            insert_line: 2,
+            content: "inserted".to_string(),
+        };
+        run_insert_lines(&insert_args, dir.path(), &history)
+            .await
+            .unwrap();
+
+        // Undo
+        let undo_args = UndoEditArgs {
+            path: "test.txt".to_string(),
+        };
+        let result = run_undo_edit(&undo_args, dir.path(), &history, &redo).await;
+        assert!(result.is_ok());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "line1\nline2");
+    }
 
-    // ========== str_replace tests ==========
+    #[tokio::test]
+    async fn test_undo_edit_no_history() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "content").unwrap();
+
+        let undo_args = UndoEditArgs {
+            path: "test.txt".to_string(),
+        };
+        let result = run_undo_edit(&undo_args, dir.path(), &history, &redo).await;
+        assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("No edit history"));
+    }
 
     #[tokio::test]
-    async fn test_str_replace_basic() {
+    async fn test_undo_edit_multiple_times() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
         let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "hello world").unwrap();
+        fs::write(&file_path, "original").unwrap();
 
-        let args = StrReplaceArgs {
+        // First edit
+        let replace_args1 = StrReplaceArgs {
             path: "test.txt".to_string(),
-            old_str: "world".to_string(),
-            new_str: "rust".to_string(),
+            old_str: "original".to_string(),
+            new_str: "edit1".to_string(),
+            ..Default::default()
         };
+        run_str_replace(&replace_args1, dir.path(), &history)
+            .await
+            .unwrap();
 
-        let result = run_str_replace(&args, dir.path(), &history).await;
-        assert!(result.is_ok());
+        // Second edit
+        let replace_args2 = StrReplaceArgs {
+            path: "test.txt".to_string(),
+            old_str: "edit1".to_string(),
+            new_str: "edit2".to_string(),
+            ..Default::default()
+        };
+        run_str_replace(&replace_args2, dir.path(), &history)
+            .await
+            .unwrap();
 
+        // Undo once
+        let undo_args = UndoEditArgs {
+            path: "test.txt".to_string(),
+        };
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
         let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "hello rust");
+        assert_eq!(content, "edit1");
+
+        // Undo again
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "original");
     }
 
+    // ========== revert_file tests ==========
+
     #[tokio::test]
-    async fn test_str_replace_not_found() {
+    async fn test_revert_file_after_three_edits() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
         let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "hello world").unwrap();
+        fs::write(&file_path, "alpha\nbeta\n").unwrap();
+        let history = Mutex::new(HashMap::new());
 
-        let args = StrReplaceArgs {
+        for (old, new) in [("alpha", "one"), ("beta", "two"), ("one", "three")] {
+            let args = StrReplaceArgs {
+                path: "test.txt".to_string(),
+                old_str: old.to_string(),
+                new_str: new.to_string(),
+                ..Default::default()
+            };
+            run_str_replace(&args, dir.path(), &history).await.unwrap();
+        }
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "three\ntwo\n");
+
+        let args = RevertFileArgs {
             path: "test.txt".to_string(),
-            old_str: "nonexistent".to_string(),
-            new_str: "replacement".to_string(),
         };
+        let output = run_revert_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("Reverted 3 edit(s)"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "alpha\nbeta\n");
+        assert!(!history.lock().await.contains_key(&file_path));
 
-        let result = run_str_replace(&args, dir.path(), &history).await;
+        let output = run_revert_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("No edits to revert"));
+    }
+
+    // ========== list_directory tests ==========
+
+    #[tokio::test]
+    async fn test_list_directory_basic() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("file1.txt"), "content1").unwrap();
+        fs::write(dir.path().join("file2.txt"), "content2").unwrap();
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+
+        let args = ListDirectoryArgs {
+            path: ".".to_string(),
+            ..Default::default()
+        };
+
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
         let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("did not appear verbatim"));
+        assert!(output.contains("file1.txt"));
+        assert!(output.contains("file2.txt"));
+        assert!(output.contains("subdir/"));
     }
 
     #[tokio::test]
-    async fn test_str_replace_multiple_occurrences() {
+    async fn test_list_directory_empty() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "hello hello hello").unwrap();
 
-        let args = StrReplaceArgs {
-            path: "test.txt".to_string(),
-            old_str: "hello".to_string(),
-            new_str: "world".to_string(),
+        let args = ListDirectoryArgs {
+            path: ".".to_string(),
+            ..Default::default()
         };
 
-        let result = run_str_replace(&args, dir.path(), &history).await;
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
         let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("Multiple occurrences"));
+        assert!(output.is_empty());
     }
 
     #[tokio::test]
-    async fn test_str_replace_same_string() {
+    async fn test_list_directory_hidden_files_excluded() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "hello world").unwrap();
+        fs::write(dir.path().join("visible.txt"), "content").unwrap();
+        fs::write(dir.path().join(".hidden"), "secret").unwrap();
 
-        let args = StrReplaceArgs {
-            path: "test.txt".to_string(),
-            old_str: "world".to_string(),
-            new_str: "world".to_string(),
+        let args = ListDirectoryArgs {
+            path: ".".to_string(),
+            ..Default::default()
         };
 
-        let result = run_str_replace(&args, dir.path(), &history).await;
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
         let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("must be different"));
+        assert!(output.contains("visible.txt"));
+        assert!(!output.contains(".hidden"));
     }
 
     #[tokio::test]
-    async fn test_str_replace_file_not_found() {
+    async fn test_list_directory_not_found() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
 
-        let args = StrReplaceArgs {
-            path: "nonexistent.txt".to_string(),
-            old_str: "old".to_string(),
-            new_str: "new".to_string(),
+        let args = ListDirectoryArgs {
+            path: "nonexistent".to_string(),
+            ..Default::default()
         };
 
-        let result = run_str_replace(&args, dir.path(), &history).await;
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
         let output = result.unwrap();
         assert!(output.contains("Error"));
@@ -523,683 +4451,1342 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_str_replace_multiline() {
+    async fn test_list_directory_on_file() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2\nline3").unwrap();
+        fs::write(dir.path().join("file.txt"), "content").unwrap();
 
-        let args = StrReplaceArgs {
-            path: "test.txt".to_string(),
-            old_str: "line2".to_string(),
-            new_str: "modified".to_string(),
+        let args = ListDirectoryArgs {
+            path: "file.txt".to_string(),
+            ..Default::default()
         };
 
-        let result = run_str_replace(&args, dir.path(), &history).await;
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
+        let output = result.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("not a directory"));
+    }
 
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "line1\nmodified\nline3");
+    #[test]
+    fn test_list_directory_detailed_reports_metadata() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join(".hidden"), "x").unwrap();
+
+        let args = ListDirectoryArgs {
+            path: ".".to_string(),
+            ..Default::default()
+        };
+        let (entries, truncated) = run_list_directory_detailed(&args, dir.path()).unwrap();
+        assert!(!truncated);
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].name, "a.txt");
+        assert_eq!(entries[0].kind, EntryKind::File);
+        assert_eq!(entries[0].size_bytes, Some(8));
+        assert_eq!(entries[0].line_count, Some(2));
+        assert!(entries[0].modified.is_some());
+
+        assert_eq!(entries[1].name, "sub");
+        assert_eq!(entries[1].kind, EntryKind::Dir);
+        assert_eq!(entries[1].size_bytes, None);
+        assert_eq!(entries[1].line_count, None);
     }
 
-    // ========== view_file tests ==========
+    #[test]
+    fn test_file_stat_regular_file() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("run.sh"), "#!/bin/sh\necho hi\n").unwrap();
+        #[cfg(unix)]
+        set_executable(&dir.path().join("run.sh")).unwrap();
+
+        let args = FileStatArgs {
+            path: "run.sh".to_string(),
+        };
+        let stat = run_file_stat(&args, dir.path()).unwrap();
+        assert_eq!(stat.kind, EntryKind::File);
+        assert_eq!(stat.size_bytes, 18);
+        assert_eq!(stat.line_count, Some(2));
+        assert!(stat.modified.is_some());
+        assert_eq!(stat.executable, cfg!(unix));
+        assert_eq!(stat.target, None);
+
+        let json = serde_json::to_value(&stat).unwrap();
+        assert_eq!(json["type"], "file");
+        let modified = json["modified"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(modified).is_ok());
+
+        let args = FileStatArgs {
+            path: "missing.txt".to_string(),
+        };
+        let err = run_file_stat(&args, dir.path()).unwrap_err();
+        assert!(err.contains("does not exist"), "{}", err);
+    }
+
+    #[test]
+    fn test_file_stat_directory() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+
+        let args = FileStatArgs {
+            path: "sub".to_string(),
+        };
+        let stat = run_file_stat(&args, dir.path()).unwrap();
+        assert_eq!(stat.kind, EntryKind::Dir);
+        assert_eq!(stat.line_count, None);
+        assert!(!stat.executable);
+        assert_eq!(serde_json::to_value(&stat).unwrap()["type"], "dir");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_stat_symlink() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir_all(workspace.join("sub")).unwrap();
+        fs::write(workspace.join("sub/real.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.path().join("outside.txt"), "secret\n").unwrap();
+        std::os::unix::fs::symlink("real.txt", workspace.join("sub/link.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("outside.txt"), workspace.join("out")).unwrap();
+
+        for (path, target) in [
+            ("sub/link.txt", "real.txt".to_string()),
+            ("out", dir.path().join("outside.txt").display().to_string()),
+        ] {
+            let args = FileStatArgs {
+                path: path.to_string(),
+            };
+            let stat = run_file_stat(&args, &workspace).unwrap();
+            assert_eq!(stat.kind, EntryKind::Symlink);
+            assert_eq!(stat.target, Some(target));
+            assert_eq!(stat.line_count, None);
+            assert_eq!(serde_json::to_value(&stat).unwrap()["type"], "symlink");
+        }
+
+        let args = FileStatArgs {
+            path: "../outside.txt".to_string(),
+        };
+        let err = run_file_stat(&args, &workspace).unwrap_err();
+        assert!(err.contains("outside the workspace"), "{}", err);
+    }
 
     #[tokio::test]
-    async fn test_view_file_basic() {
+    async fn test_list_directory_with_line_counts() {
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2\nline3\nline4\nline5").unwrap();
+        fs::write(dir.path().join("file1.txt"), "line1\nline2\nline3").unwrap();
+        fs::write(dir.path().join("file2.txt"), "hello").unwrap();
+        fs::create_dir(dir.path().join("subdir")).unwrap();
 
-        let args = ViewFileArgs {
-            path: "test.txt".to_string(),
-            start_line: None,
-            end_line: None,
+        let args = ListDirectoryArgs {
+            path: ".".to_string(),
+            ..Default::default()
         };
 
-        let result = run_view_file(&args, dir.path()).await;
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
         let output = result.unwrap();
-        assert!(output.contains("line1"));
-        assert!(output.contains("line5"));
+        assert!(output.contains("file1.txt (3 lines)"));
+        assert!(output.contains("file2.txt (1 line)"));
+        assert!(output.contains("subdir/"));
     }
 
     #[tokio::test]
-    async fn test_view_file_with_range() {
+    async fn test_list_directory_limit() {
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2\nline3\nline4\nline5").unwrap();
+        for i in 0..10 {
+            fs::write(dir.path().join(format!("file{}.txt", i)), "content").unwrap();
+        }
 
-        let args = ViewFileArgs {
-            path: "test.txt".to_string(),
-            start_line: Some(2),
-            end_line: Some(4),
+        let args = ListDirectoryArgs {
+            path: ".".to_string(),
+            limit: Some(5),
+            ..Default::default()
         };
 
-        let result = run_view_file(&args, dir.path()).await;
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
         let output = result.unwrap();
-        assert!(!output.contains("line1"));
-        assert!(output.contains("line2"));
-        assert!(output.contains("line4"));
-        assert!(!output.contains("line5"));
+        assert_eq!(output.matches(".txt").count(), 5);
+        assert!(output.contains("[directory has more than 5 entries; showing first 5]"));
     }
 
     #[tokio::test]
-    async fn test_view_file_invalid_start_line() {
+    async fn test_list_directory_limit_not_reached() {
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2\nline3").unwrap();
+        fs::write(dir.path().join("file1.txt"), "content").unwrap();
+        fs::write(dir.path().join("file2.txt"), "content").unwrap();
 
-        let args = ViewFileArgs {
-            path: "test.txt".to_string(),
-            start_line: Some(10),
-            end_line: None,
+        let args = ListDirectoryArgs {
+            path: ".".to_string(),
+            limit: Some(2),
+            ..Default::default()
         };
 
-        let result = run_view_file(&args, dir.path()).await;
+        let result = run_list_directory(&args, dir.path()).await;
         assert!(result.is_ok());
         let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("start_line"));
+        assert!(output.contains("file2.txt"));
+        assert!(!output.contains("more than"));
     }
 
     #[tokio::test]
-    async fn test_view_file_invalid_range() {
+    async fn test_list_directory_recursive() {
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2\nline3").unwrap();
+        fs::create_dir_all(dir.path().join("src/tools")).unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("README.md"), "# readme\n").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "mod tools;\n").unwrap();
+        fs::write(
+            dir.path().join("src/tools/grep.rs"),
+            "fn a() {}\nfn b() {}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/tools/.hidden"), "x").unwrap();
 
-        let args = ViewFileArgs {
-            path: "test.txt".to_string(),
-            start_line: Some(3),
-            end_line: Some(1),
+        let mut args = ListDirectoryArgs {
+            path: ".".to_string(),
+            recursive: Some(true),
+            ..Default::default()
         };
+        let output = run_list_directory(&args, dir.path()).await.unwrap();
+        assert_eq!(
+            output,
+            "README.md (1 line)\nsrc/\nsrc/lib.rs (1 line)\nsrc/tools/\nsrc/tools/grep.rs (2 lines)"
+        );
 
-        let result = run_view_file(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("greater than or equal to"));
+        args.dirs_first = Some(true);
+        let output = run_list_directory(&args, dir.path()).await.unwrap();
+        assert_eq!(
+            output,
+            "src/\nsrc/tools/\nsrc/tools/grep.rs (2 lines)\nsrc/lib.rs (1 line)\nREADME.md (1 line)"
+        );
+
+        args.dirs_first = None;
+        args.max_depth = Some(2);
+        let output = run_list_directory(&args, dir.path()).await.unwrap();
+        assert_eq!(
+            output,
+            "README.md (1 line)\nsrc/\nsrc/lib.rs (1 line)\nsrc/tools/"
+        );
+
+        args.max_depth = None;
+        args.limit = Some(3);
+        let output = run_list_directory(&args, dir.path()).await.unwrap();
+        assert!(output.starts_with("README.md (1 line)\nsrc/\nsrc/lib.rs (1 line)\n["));
+        assert!(output.contains("more than 3 entries"));
+    }
+
+    #[tokio::test]
+    async fn test_list_directory_include_hidden() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("visible.txt"), "content").unwrap();
+        fs::write(dir.path().join(".hidden"), "secret").unwrap();
+
+        let mut args = ListDirectoryArgs {
+            path: ".".to_string(),
+            ..Default::default()
+        };
+        let output = run_list_directory(&args, dir.path()).await.unwrap();
+        assert!(!output.contains(".hidden"));
+
+        args.include_hidden = Some(true);
+        let output = run_list_directory(&args, dir.path()).await.unwrap();
+        assert!(output.contains(".hidden (1 line)"));
+        assert!(output.contains("visible.txt"));
     }
 
-    #[tokio::test]
-    async fn test_view_file_not_found() {
+    #[test]
+    fn test_tree_include_hidden() {
         let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join(".github")).unwrap();
+        fs::write(dir.path().join(".github/ci.yml"), "on: push").unwrap();
+        fs::write(dir.path().join(".hidden"), "secret").unwrap();
+        fs::write(dir.path().join("visible.txt"), "content").unwrap();
 
-        let args = ViewFileArgs {
-            path: "nonexistent.txt".to_string(),
-            start_line: None,
-            end_line: None,
+        let mut args = TreeArgs {
+            path: None,
+            exclude: None,
+            max_depth: None,
+            truncate: None,
+            include_hidden: None,
+            format: None,
         };
-
-        let result = run_view_file(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("does not exist"));
+        let output = run_tree(&args, dir.path()).unwrap();
+        assert_eq!(output, ".\n└── visible.txt\n");
+
+        args.include_hidden = Some(true);
+        let output = run_tree(&args, dir.path()).unwrap();
+        assert_eq!(
+            output,
+            ".\n├── .github\n│   └── ci.yml\n├── .hidden\n└── visible.txt\n"
+        );
     }
 
-    // ========== create_file tests ==========
-
-    #[tokio::test]
-    async fn test_create_file_basic() {
+    #[test]
+    fn test_tree_exclude_glob_patterns() {
         let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::create_dir(dir.path().join("node_modules")).unwrap();
+        for file in [
+            "a.tmp",
+            "keep.txt",
+            "src/b.tmp",
+            "src/main.rs",
+            "src/nested/c.tmp",
+            "target/debug/app",
+        ] {
+            fs::write(dir.path().join(file), "x").unwrap();
+        }
 
-        let args = CreateFileArgs {
-            path: "new_file.txt".to_string(),
-            content: "hello world".to_string(),
+        let args = TreeArgs {
+            path: None,
+            exclude: Some("*.tmp, target/**, node_modules".to_string()),
+            max_depth: None,
+            truncate: None,
+            include_hidden: None,
+            format: None,
         };
+        let output = run_tree(&args, dir.path()).unwrap();
+        assert_eq!(
+            output,
+            ".\n├── keep.txt\n└── src\n    ├── main.rs\n    └── nested\n"
+        );
 
-        let result = run_create_file(&args, dir.path()).await;
-        assert!(result.is_ok());
-
-        let file_path = dir.path().join("new_file.txt");
-        assert!(file_path.exists());
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "hello world");
+        let args = TreeArgs {
+            path: None,
+            exclude: Some("src/*.tmp".to_string()),
+            max_depth: None,
+            truncate: None,
+            include_hidden: None,
+            format: None,
+        };
+        let output = run_tree(&args, dir.path()).unwrap();
+        assert!(!output.contains("b.tmp"));
+        assert!(output.contains("a.tmp"));
+        assert!(output.contains("c.tmp"));
     }
 
-    #[tokio::test]
-    async fn test_create_file_already_exists() {
+    #[test]
+    fn test_tree_json_format() {
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("existing.txt");
-        fs::write(&file_path, "existing content").unwrap();
+        fs::create_dir_all(dir.path().join("src/deep")).unwrap();
+        fs::write(dir.path().join("src/deep/x.rs"), "x").unwrap();
+        for file in ["a.txt", "b.txt", "c.log"] {
+            fs::write(dir.path().join(file), "x").unwrap();
+        }
 
-        let args = CreateFileArgs {
-            path: "existing.txt".to_string(),
-            content: "new content".to_string(),
+        let args = TreeArgs {
+            path: None,
+            exclude: Some("*.log".to_string()),
+            max_depth: Some(2),
+            truncate: Some(2),
+            include_hidden: None,
+            format: Some("json".to_string()),
+        };
+        let output = run_tree(&args, dir.path()).unwrap();
+        let tree: TreeNode = serde_json::from_str(&output).unwrap();
+        let file = |name: &str| {
+            TreeChild::Node(TreeNode {
+                name: name.to_string(),
+                kind: EntryKind::File,
+                children: None,
+            })
         };
+        assert_eq!(
+            tree,
+            TreeNode {
+                name: ".".to_string(),
+                kind: EntryKind::Dir,
+                children: Some(vec![
+                    file("a.txt"),
+                    file("b.txt"),
+                    TreeChild::Truncated { truncated: 1 },
+                ]),
+            }
+        );
 
-        let result = run_create_file(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("already exists"));
+        let args = TreeArgs {
+            path: Some("src".to_string()),
+            truncate: None,
+            ..args
+        };
+        let tree: TreeNode = serde_json::from_str(&run_tree(&args, dir.path()).unwrap()).unwrap();
+        let Some(children) = tree.children else {
+            panic!("root has no children");
+        };
+        assert_eq!(
+            children,
+            vec![TreeChild::Node(TreeNode {
+                name: "deep".to_string(),
+                kind: EntryKind::Dir,
+                children: Some(vec![file("x.rs")]),
+            })]
+        );
+
+        let args = TreeArgs {
+            format: Some("yaml".to_string()),
+            ..args
+        };
+        assert!(run_tree(&args, dir.path()).is_err());
     }
 
+    // ========== swap_files tests ==========
+
     #[tokio::test]
-    async fn test_create_file_with_parent_dirs() {
+    async fn test_swap_files_basic() {
         let dir = tempdir().unwrap();
-
-        let args = CreateFileArgs {
-            path: "subdir/nested/file.txt".to_string(),
-            content: "nested content".to_string(),
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        let file_a = dir.path().join("a.txt");
+        let file_b = dir.path().join("b.txt");
+        fs::write(&file_a, "content a").unwrap();
+        fs::write(&file_b, "content b").unwrap();
+
+        let args = SwapFilesArgs {
+            a: "a.txt".to_string(),
+            b: "b.txt".to_string(),
         };
-
-        let result = run_create_file(&args, dir.path()).await;
+        let result = run_swap_files(&args, dir.path(), &history).await;
         assert!(result.is_ok());
+        assert!(result.unwrap().contains("Swapped"));
 
-        let file_path = dir.path().join("subdir/nested/file.txt");
-        assert!(file_path.exists());
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "nested content");
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "content b");
+        assert_eq!(fs::read_to_string(&file_b).unwrap(), "content a");
+
+        // Both sides of the swap can be undone
+        for path in ["a.txt", "b.txt"] {
+            let undo_args = UndoEditArgs {
+                path: path.to_string(),
+            };
+            run_undo_edit(&undo_args, dir.path(), &history, &redo)
+                .await
+                .unwrap();
+        }
+        assert_eq!(fs::read_to_string(&file_a).unwrap(), "content a");
+        assert_eq!(fs::read_to_string(&file_b).unwrap(), "content b");
     }
 
     #[tokio::test]
-    async fn test_create_file_empty_content() {
+    async fn test_swap_files_missing_or_directory() {
         let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        fs::write(dir.path().join("a.txt"), "content a").unwrap();
+        fs::create_dir(dir.path().join("subdir")).unwrap();
 
-        let args = CreateFileArgs {
-            path: "empty.txt".to_string(),
-            content: "".to_string(),
+        let args = SwapFilesArgs {
+            a: "a.txt".to_string(),
+            b: "missing.txt".to_string(),
         };
+        let output = run_swap_files(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("does not exist"));
 
-        let result = run_create_file(&args, dir.path()).await;
-        assert!(result.is_ok());
-
-        let file_path = dir.path().join("empty.txt");
-        assert!(file_path.exists());
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "");
+        let args = SwapFilesArgs {
+            a: "a.txt".to_string(),
+            b: "subdir".to_string(),
+        };
+        let output = run_swap_files(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("is a directory"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "content a"
+        );
     }
 
-    // ========== insert_lines tests ==========
+    // ========== multi_edit tests ==========
+
+    fn edit(old_str: &str, new_str: &str, replace_all: Option<bool>) -> EditOperation {
+        EditOperation {
+            old_str: old_str.to_string(),
+            new_str: new_str.to_string(),
+            replace_all,
+        }
+    }
 
     #[tokio::test]
-    async fn test_insert_lines_basic() {
+    async fn test_multi_edit_applies_all_and_undoes_at_once() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2\nline3").unwrap();
-
-        let args = InsertLinesArgs {
-            path: "test.txt".to_string(),
-            insert_line: 2,
-            content: "inserted".to_string(),
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("lib.rs");
+        let original = "fn old_name() {}\nfn caller() {\n    old_name();\n    let x = 1;\n}\n";
+        fs::write(&file_path, original).unwrap();
+
+        let args = MultiEditArgs {
+            path: "lib.rs".to_string(),
+            edits: vec![
+                edit("old_name", "new_name", Some(true)),
+                edit("let x = 1;", "let x = 2;", None),
+                // Sees the result of the first edit
+                edit("fn new_name() {}", "pub fn new_name() {}", None),
+            ],
         };
+        let output = run_multi_edit(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("Applied 3 edit(s)"), "{}", output);
+        assert!(output.contains("edits[0]: line 1 (2 replacement(s))"));
+        assert!(output.contains("edits[1]: line 4 (1 replacement(s))"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "pub fn new_name() {}\nfn caller() {\n    new_name();\n    let x = 2;\n}\n"
+        );
 
-        let result = run_insert_lines(&args, dir.path(), &history).await;
-        assert!(result.is_ok());
-
-        let content = fs::read_to_string(&file_path).unwrap();
-        let lines: Vec<&str> = content.lines().collect();
-        assert_eq!(lines[0], "line1");
-        assert_eq!(lines[1], "inserted");
-        assert_eq!(lines[2], "line2");
+        let undo_args = UndoEditArgs {
+            path: "lib.rs".to_string(),
+        };
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
     }
 
     #[tokio::test]
-    async fn test_insert_lines_at_beginning() {
+    async fn test_multi_edit_failure_touches_nothing() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
         let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2").unwrap();
-
-        let args = InsertLinesArgs {
-            path: "test.txt".to_string(),
-            insert_line: 1,
-            content: "first".to_string(),
-        };
+        fs::write(&file_path, "alpha beta beta").unwrap();
+
+        for (edits, expected) in [
+            (
+                vec![edit("alpha", "ALPHA", None), edit("gamma", "GAMMA", None)],
+                "edits[1] failed: old_str `gamma` did not appear",
+            ),
+            (
+                vec![edit("alpha", "ALPHA", None), edit("beta", "BETA", None)],
+                "edits[1] failed: old_str `beta` appears 2 times",
+            ),
+        ] {
+            let args = MultiEditArgs {
+                path: "test.txt".to_string(),
+                edits,
+            };
+            let output = run_multi_edit(&args, dir.path(), &history).await.unwrap();
+            assert!(output.contains(expected), "{}", output);
+            assert!(output.contains("No changes were made"));
+        }
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "alpha beta beta");
+        assert!(history.lock().await.is_empty());
+    }
 
-        let result = run_insert_lines(&args, dir.path(), &history).await;
-        assert!(result.is_ok());
+    // ========== apply_edit_blocks tests ==========
 
-        let content = fs::read_to_string(&file_path).unwrap();
-        let lines: Vec<&str> = content.lines().collect();
-        assert_eq!(lines[0], "first");
-        assert_eq!(lines[1], "line1");
+    fn block(search: &str, replace: &str) -> EditBlock {
+        EditBlock {
+            search: search.to_string(),
+            replace: replace.to_string(),
+        }
     }
 
     #[tokio::test]
-    async fn test_insert_lines_at_end() {
+    async fn test_apply_edit_blocks_in_order() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2").unwrap();
-
-        let args = InsertLinesArgs {
-            path: "test.txt".to_string(),
-            insert_line: 3,
-            content: "last".to_string(),
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("app.py");
+        let original = "import os\n\ndef main():\n    print('hi')\n";
+        fs::write(&file_path, original).unwrap();
+
+        let args = ApplyEditBlocksArgs {
+            path: "app.py".to_string(),
+            blocks: vec![
+                block("import os\n", "import os\nimport sys\n"),
+                block("    print('hi')\n", "    print('hi')\n    sys.exit(0)\n"),
+                // Sees the result of the second block
+                block("    sys.exit(0)\n", "    sys.exit(1)\n"),
+            ],
         };
+        let output = run_apply_edit_blocks(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert!(
+            output.contains("Applied 3 block(s), -3 +5 line(s)"),
+            "{}",
+            output
+        );
+        assert!(output.contains("blocks[1]: line 5 (-1 +2)"), "{}", output);
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "import os\nimport sys\n\ndef main():\n    print('hi')\n    sys.exit(1)\n"
+        );
 
-        let result = run_insert_lines(&args, dir.path(), &history).await;
-        assert!(result.is_ok());
-
-        let content = fs::read_to_string(&file_path).unwrap();
-        let lines: Vec<&str> = content.lines().collect();
-        assert_eq!(lines[2], "last");
+        let undo_args = UndoEditArgs {
+            path: "app.py".to_string(),
+        };
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
     }
 
     #[tokio::test]
-    async fn test_insert_lines_invalid_line() {
+    async fn test_apply_edit_blocks_failure_touches_nothing() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
         let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2").unwrap();
+        fs::write(&file_path, "alpha beta beta").unwrap();
+
+        for (blocks, expected) in [
+            (
+                vec![block("alpha", "ALPHA"), block("gamma", "GAMMA")],
+                "blocks[1] failed: search `gamma` did not appear",
+            ),
+            (
+                vec![block("alpha", "ALPHA"), block("beta", "BETA")],
+                "blocks[1] failed: search `beta` appears 2 times",
+            ),
+        ] {
+            let args = ApplyEditBlocksArgs {
+                path: "test.txt".to_string(),
+                blocks,
+            };
+            let output = run_apply_edit_blocks(&args, dir.path(), &history)
+                .await
+                .unwrap();
+            assert!(output.contains(expected), "{}", output);
+            assert!(output.contains("No changes were made"));
+        }
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "alpha beta beta");
+        assert!(history.lock().await.is_empty());
+    }
 
-        let args = InsertLinesArgs {
-            path: "test.txt".to_string(),
-            insert_line: 100,
-            content: "invalid".to_string(),
+    // ========== conditional_replace tests ==========
+
+    #[tokio::test]
+    async fn test_conditional_replace_only_edits_matching_files() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+        fs::write(
+            dir.path().join("a.py"),
+            "import legacy\nlegacy.run()\nlegacy.stop()\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("b.py"), "legacy.run()\n").unwrap();
+        fs::write(dir.path().join("c.py"), "import legacy\n# nothing else\n").unwrap();
+        fs::write(dir.path().join("d.txt"), "import legacy\nlegacy.run()\n").unwrap();
+
+        let args = ConditionalReplaceArgs {
+            glob: "*.py".to_string(),
+            contains: "import legacy".to_string(),
+            old_str: "legacy.".to_string(),
+            new_str: "modern.".to_string(),
         };
+        let (output, changed) = run_conditional_replace(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert_eq!(changed, vec!["a.py".to_string()]);
+        assert!(output.contains("a.py (2 replacement(s))"), "{}", output);
+        assert!(output.contains("b.py: does not contain the precondition string"));
+        assert!(output.contains("c.py: old_str not found"));
+        assert!(!output.contains("d.txt"));
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.py")).unwrap(),
+            "import legacy\nmodern.run()\nmodern.stop()\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("b.py")).unwrap(),
+            "legacy.run()\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("d.txt")).unwrap(),
+            "import legacy\nlegacy.run()\n"
+        );
 
-        let result = run_insert_lines(&args, dir.path(), &history).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("insert_line"));
+        let undo_args = UndoEditArgs {
+            path: "a.py".to_string(),
+        };
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.py")).unwrap(),
+            "import legacy\nlegacy.run()\nlegacy.stop()\n"
+        );
     }
 
+    // ========== move_file tests ==========
+
     #[tokio::test]
-    async fn test_insert_lines_file_not_found() {
+    async fn test_move_file_basic() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
+        fs::write(dir.path().join("a.txt"), "original").unwrap();
+        let edit_args = StrReplaceArgs {
+            path: "a.txt".to_string(),
+            old_str: "original".to_string(),
+            new_str: "edited".to_string(),
+            ..Default::default()
+        };
+        run_str_replace(&edit_args, dir.path(), &history)
+            .await
+            .unwrap();
 
-        let args = InsertLinesArgs {
-            path: "nonexistent.txt".to_string(),
-            insert_line: 1,
-            content: "content".to_string(),
+        let args = MoveFileArgs {
+            source: "a.txt".to_string(),
+            destination: "nested/dir/b.txt".to_string(),
         };
+        let output = run_move_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.starts_with("Moved"), "{}", output);
+        assert!(!dir.path().join("a.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("nested/dir/b.txt")).unwrap(),
+            "edited"
+        );
 
-        let result = run_insert_lines(&args, dir.path(), &history).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("does not exist"));
+        let history_guard = history.lock().await;
+        assert!(!history_guard.contains_key(&dir.path().join("a.txt")));
+        assert_eq!(
+            history_guard[&dir.path().join("nested/dir/b.txt")],
+            vec![
+                HistoryEntry::Content("original".to_string()),
+                HistoryEntry::Content("edited".to_string()),
+            ]
+        );
     }
 
-    // ========== delete_file tests ==========
-
     #[tokio::test]
-    async fn test_delete_file_basic() {
+    async fn test_move_file_binary() {
         let dir = tempdir().unwrap();
-        let file_path = dir.path().join("to_delete.txt");
-        fs::write(&file_path, "content").unwrap();
-        assert!(file_path.exists());
+        let history = Mutex::new(HashMap::new());
+        let bytes = vec![0xff, 0xfe, 0x00, 0x80, 0x01];
+        fs::write(dir.path().join("image.bin"), &bytes).unwrap();
 
-        let args = DeleteFileArgs {
-            path: "to_delete.txt".to_string(),
+        let args = MoveFileArgs {
+            source: "image.bin".to_string(),
+            destination: "assets/image.bin".to_string(),
         };
-
-        let result = run_delete_file(&args, dir.path()).await;
-        assert!(result.is_ok());
-        assert!(!file_path.exists());
+        let output = run_move_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.starts_with("Moved"), "{}", output);
+        assert!(!dir.path().join("image.bin").exists());
+        assert_eq!(
+            fs::read(dir.path().join("assets/image.bin")).unwrap(),
+            bytes
+        );
+        assert!(history.lock().await.is_empty());
     }
 
     #[tokio::test]
-    async fn test_delete_file_not_found() {
+    async fn test_move_file_rejects_existing_and_same_path() {
         let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("b.txt"), "b").unwrap();
 
-        let args = DeleteFileArgs {
-            path: "nonexistent.txt".to_string(),
+        let args = MoveFileArgs {
+            source: "a.txt".to_string(),
+            destination: "b.txt".to_string(),
         };
+        let output = run_move_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("already exists"), "{}", output);
+        assert_eq!(fs::read_to_string(dir.path().join("b.txt")).unwrap(), "b");
 
-        let result = run_delete_file(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("does not exist"));
+        let args = MoveFileArgs {
+            source: "a.txt".to_string(),
+            destination: "./a.txt".to_string(),
+        };
+        let output = run_move_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("same path"), "{}", output);
+        assert!(dir.path().join("a.txt").exists());
+        assert!(history.lock().await.is_empty());
     }
 
-    // ========== undo_edit tests ==========
+    // ========== move_glob tests ==========
 
     #[tokio::test]
-    async fn test_undo_edit_after_str_replace() {
+    async fn test_move_glob_moves_files_and_history() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "hello world").unwrap();
+        let redo = Mutex::new(HashMap::new());
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::write(dir.path().join("src/a.spec.js"), "a").unwrap();
+        fs::write(dir.path().join("src/nested/b.spec.js"), "b").unwrap();
+        fs::write(dir.path().join("src/c.js"), "c").unwrap();
+        history.lock().await.insert(
+            dir.path().join("src/a.spec.js"),
+            vec![HistoryEntry::Content("old a".to_string())],
+        );
 
-        // First, do a str_replace
-        let replace_args = StrReplaceArgs {
-            path: "test.txt".to_string(),
-            old_str: "world".to_string(),
-            new_str: "rust".to_string(),
+        let args = MoveGlobArgs {
+            pattern: "src/**/*.spec.js".to_string(),
+            dest_dir: "__tests__".to_string(),
         };
-        run_str_replace(&replace_args, dir.path(), &history)
-            .await
-            .unwrap();
-
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "hello rust");
+        let (output, moves) = run_move_glob(&args, dir.path(), &history).await.unwrap();
+        assert!(output.starts_with("Moved 2 file(s)"), "{}", output);
+        assert_eq!(
+            moves,
+            vec![
+                FileMove {
+                    from: "src/a.spec.js".to_string(),
+                    to: "__tests__/a.spec.js".to_string(),
+                },
+                FileMove {
+                    from: "src/nested/b.spec.js".to_string(),
+                    to: "__tests__/b.spec.js".to_string(),
+                },
+            ]
+        );
+        assert!(!dir.path().join("src/a.spec.js").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("__tests__/b.spec.js")).unwrap(),
+            "b"
+        );
+        assert!(dir.path().join("src/c.js").exists());
 
-        // Now undo
+        // History moved with the file, so undo works at the new location
         let undo_args = UndoEditArgs {
-            path: "test.txt".to_string(),
+            path: "__tests__/a.spec.js".to_string(),
         };
-        let result = run_undo_edit(&undo_args, dir.path(), &history).await;
-        assert!(result.is_ok());
-
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "hello world");
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("__tests__/a.spec.js")).unwrap(),
+            "old a"
+        );
     }
 
     #[tokio::test]
-    async fn test_undo_edit_after_insert_lines() {
+    async fn test_move_glob_refuses_to_overwrite() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "line1\nline2").unwrap();
+        fs::create_dir(dir.path().join("dest")).unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("b.txt"), "b").unwrap();
+        fs::write(dir.path().join("dest/b.txt"), "existing").unwrap();
+
+        let args = MoveGlobArgs {
+            pattern: "*.txt".to_string(),
+            dest_dir: "dest".to_string(),
+        };
+        let (output, moves) = run_move_glob(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("already exists"), "{}", output);
+        assert!(moves.is_empty());
+        assert!(dir.path().join("a.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("dest/b.txt")).unwrap(),
+            "existing"
+        );
+    }
 
-        // Insert a line
-        let insert_args = InsertLinesArgs {
-            path: "test.txt".to_string(),
-            insert_line: 2,
-            content: "inserted".to_string(),
+    // ========== sort_file tests ==========
+
+    #[tokio::test]
+    async fn test_sort_file_dedup_and_undo() {
+        let dir = tempdir().unwrap();
+        let original = "target/\n*.log\nnode_modules/\n*.log\n.env\n";
+        let file_path = dir.path().join(".gitignore");
+        fs::write(&file_path, original).unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
+
+        let args = SortFileArgs {
+            path: ".gitignore".to_string(),
+            dedup: Some(true),
+            case_insensitive: None,
         };
-        run_insert_lines(&insert_args, dir.path(), &history)
-            .await
-            .unwrap();
+        let output = run_sort_file(&args, dir.path(), &history).await.unwrap();
+        assert!(output.contains("has been sorted"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "*.log\n.env\nnode_modules/\ntarget/\n"
+        );
 
-        // Undo
         let undo_args = UndoEditArgs {
-            path: "test.txt".to_string(),
+            path: ".gitignore".to_string(),
         };
-        let result = run_undo_edit(&undo_args, dir.path(), &history).await;
-        assert!(result.is_ok());
-
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "line1\nline2");
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
     }
 
     #[tokio::test]
-    async fn test_undo_edit_no_history() {
+    async fn test_sort_file_case_insensitive_without_trailing_newline() {
         let dir = tempdir().unwrap();
+        let file_path = dir.path().join("allow.txt");
+        fs::write(&file_path, "beta\nAlpha\nalpha").unwrap();
         let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "content").unwrap();
 
-        let undo_args = UndoEditArgs {
-            path: "test.txt".to_string(),
+        let args = SortFileArgs {
+            path: "allow.txt".to_string(),
+            dedup: Some(true),
+            case_insensitive: Some(true),
         };
-        let result = run_undo_edit(&undo_args, dir.path(), &history).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("No edit history"));
+        run_sort_file(&args, dir.path(), &history).await.unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "Alpha\nbeta");
     }
 
+    // ========== normalize_whitespace tests ==========
+
     #[tokio::test]
-    async fn test_undo_edit_multiple_times() {
+    async fn test_normalize_whitespace_trims_and_expands_tabs() {
         let dir = tempdir().unwrap();
         let history = Mutex::new(HashMap::new());
-        let file_path = dir.path().join("test.txt");
-        fs::write(&file_path, "original").unwrap();
-
-        // First edit
-        let replace_args1 = StrReplaceArgs {
-            path: "test.txt".to_string(),
-            old_str: "original".to_string(),
-            new_str: "edit1".to_string(),
+        let redo = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("main.py");
+        let original = "def f():  \n\treturn 1\t\n\n\n\nx = 1\n";
+        fs::write(&file_path, original).unwrap();
+
+        let args = NormalizeWhitespaceArgs {
+            path: "main.py".to_string(),
+            trim_trailing: None,
+            tabs_to_spaces: Some(4),
+            collapse_blank_lines: Some(true),
         };
-        run_str_replace(&replace_args1, dir.path(), &history)
+        let output = run_normalize_whitespace(&args, dir.path(), &history)
             .await
             .unwrap();
+        assert!(
+            output.contains("trimmed trailing whitespace on 2 line(s)"),
+            "{}",
+            output
+        );
+        assert!(output.contains("expanded tabs on 1 line(s)"));
+        assert!(output.contains("removed 2 blank line(s)"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "def f():\n    return 1\n\nx = 1\n"
+        );
 
-        // Second edit
-        let replace_args2 = StrReplaceArgs {
-            path: "test.txt".to_string(),
-            old_str: "edit1".to_string(),
-            new_str: "edit2".to_string(),
-        };
-        run_str_replace(&replace_args2, dir.path(), &history)
+        // Already normalized content is left alone
+        let output = run_normalize_whitespace(&args, dir.path(), &history)
             .await
             .unwrap();
+        assert!(output.contains("No changes were made"));
 
-        // Undo once
         let undo_args = UndoEditArgs {
-            path: "test.txt".to_string(),
+            path: "main.py".to_string(),
         };
-        run_undo_edit(&undo_args, dir.path(), &history)
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
             .await
             .unwrap();
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "edit1");
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+    }
 
-        // Undo again
-        run_undo_edit(&undo_args, dir.path(), &history)
+    #[tokio::test]
+    async fn test_normalize_whitespace_toggles_are_independent() {
+        let dir = tempdir().unwrap();
+        let history = Mutex::new(HashMap::new());
+        let file_path = dir.path().join("Makefile");
+        fs::write(&file_path, "all:  \r\n\techo hi\r\n").unwrap();
+
+        let args = NormalizeWhitespaceArgs {
+            path: "Makefile".to_string(),
+            trim_trailing: Some(true),
+            tabs_to_spaces: None,
+            collapse_blank_lines: None,
+        };
+        run_normalize_whitespace(&args, dir.path(), &history)
             .await
             .unwrap();
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert_eq!(content, "original");
+        // Tabs and CRLF line endings are kept
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "all:\r\n\techo hi\r\n"
+        );
     }
 
-    // ========== list_directory tests ==========
+    // ========== toggle_comment tests ==========
 
     #[tokio::test]
-    async fn test_list_directory_basic() {
+    async fn test_toggle_comment_rust_range() {
         let dir = tempdir().unwrap();
-        fs::write(dir.path().join("file1.txt"), "content1").unwrap();
-        fs::write(dir.path().join("file2.txt"), "content2").unwrap();
-        fs::create_dir(dir.path().join("subdir")).unwrap();
+        let original = "fn main() {\n    let x = 1;\n\n    if x > 0 {\n        println!(\"{}\", x);\n    }\n}\n";
+        let file_path = dir.path().join("main.rs");
+        fs::write(&file_path, original).unwrap();
+        let history = Mutex::new(HashMap::new());
 
-        let args = ListDirectoryArgs {
-            path: ".".to_string(),
+        let args = ToggleCommentArgs {
+            path: "main.rs".to_string(),
+            start_line: 2,
+            end_line: 6,
+            style: None,
         };
+        let output = run_toggle_comment(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert!(output.contains("commented out"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "fn main() {\n    // let x = 1;\n\n    // if x > 0 {\n    //     println!(\"{}\", x);\n    // }\n}\n"
+        );
 
-        let result = run_list_directory(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("file1.txt"));
-        assert!(output.contains("file2.txt"));
-        assert!(output.contains("subdir/"));
+        let output = run_toggle_comment(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert!(output.contains("uncommented"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
     }
 
     #[tokio::test]
-    async fn test_list_directory_empty() {
+    async fn test_toggle_comment_python_range() {
         let dir = tempdir().unwrap();
+        let original = "def main():\n    x = 1\n    print(x)\n";
+        let file_path = dir.path().join("main.py");
+        fs::write(&file_path, original).unwrap();
+        let history = Mutex::new(HashMap::new());
+        let redo = Mutex::new(HashMap::new());
 
-        let args = ListDirectoryArgs {
-            path: ".".to_string(),
+        let args = ToggleCommentArgs {
+            path: "main.py".to_string(),
+            start_line: 2,
+            end_line: 3,
+            style: None,
         };
+        run_toggle_comment(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "def main():\n    # x = 1\n    # print(x)\n"
+        );
 
-        let result = run_list_directory(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.is_empty());
+        run_toggle_comment(&args, dir.path(), &history)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), original);
+
+        let undo_args = UndoEditArgs {
+            path: "main.py".to_string(),
+        };
+        run_undo_edit(&undo_args, dir.path(), &history, &redo)
+            .await
+            .unwrap();
+        assert!(fs::read_to_string(&file_path).unwrap().contains("# x = 1"));
     }
 
     #[tokio::test]
-    async fn test_list_directory_hidden_files_excluded() {
+    async fn test_view_file_filter_large_file() {
         let dir = tempdir().unwrap();
-        fs::write(dir.path().join("visible.txt"), "content").unwrap();
-        fs::write(dir.path().join(".hidden"), "secret").unwrap();
+        let mut content = String::new();
+        for i in 1..=200_000 {
+            if i % 50_000 == 0 {
+                content.push_str(&format!("ERROR at step {}\n", i));
+            } else {
+                content.push_str(&format!("INFO step {}\n", i));
+            }
+        }
+        fs::write(dir.path().join("big.log"), &content).unwrap();
 
-        let args = ListDirectoryArgs {
-            path: ".".to_string(),
+        let mut args = ViewFileArgs {
+            path: "big.log".to_string(),
+            filter: Some("^ERROR".to_string()),
+            ..Default::default()
         };
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert_eq!(
+            output,
+            " 50000\tERROR at step 50000\n100000\tERROR at step 100000\n\
+             150000\tERROR at step 150000\n200000\tERROR at step 200000"
+        );
 
-        let result = run_list_directory(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("visible.txt"));
-        assert!(!output.contains(".hidden"));
+        args.start_line = Some(60_000);
+        args.end_line = Some(150_000);
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert_eq!(
+            output,
+            "100000\tERROR at step 100000\n150000\tERROR at step 150000"
+        );
+
+        args.filter = Some("FATAL".to_string());
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert_eq!(output, "No lines in big.log match 'FATAL'.");
+
+        args.filter = Some("(".to_string());
+        let output = run_view_file(&args, dir.path()).await.unwrap();
+        assert!(output.starts_with("Error: Invalid filter regex"));
     }
 
+    // ========== view_with_matches tests ==========
+
     #[tokio::test]
-    async fn test_list_directory_not_found() {
+    async fn test_view_with_matches_marks_only_matching_lines() {
         let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("app.log"),
+            "start\nERROR disk full\nok\nerror lowercase\nERROR again\nend\n",
+        )
+        .unwrap();
 
-        let args = ListDirectoryArgs {
-            path: "nonexistent".to_string(),
+        let args = ViewWithMatchesArgs {
+            path: "app.log".to_string(),
+            pattern: "^ERROR".to_string(),
+            start_line: Some(2),
+            end_line: Some(5),
         };
-
-        let result = run_list_directory(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("does not exist"));
+        let output = run_view_with_matches(&args, dir.path()).await.unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "2 of 4 line(s) match '^ERROR':");
+        assert_eq!(lines[1], ">     2\tERROR disk full");
+        assert_eq!(lines[2], "      3\tok");
+        assert_eq!(lines[3], "      4\terror lowercase");
+        assert_eq!(lines[4], ">     5\tERROR again");
+        assert_eq!(lines.len(), 5);
     }
 
+    // ========== error_context tests ==========
+
     #[tokio::test]
-    async fn test_list_directory_on_file() {
+    async fn test_error_context_marks_reported_line() {
         let dir = tempdir().unwrap();
-        fs::write(dir.path().join("file.txt"), "content").unwrap();
+        let content: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        fs::write(dir.path().join("main.rs"), content).unwrap();
 
-        let args = ListDirectoryArgs {
-            path: "file.txt".to_string(),
+        let args = ErrorContextArgs {
+            path: "main.rs".to_string(),
+            line: 10,
+            context: Some(2),
         };
+        let output = run_error_context(&args, dir.path()).await.unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "main.rs:10");
+        assert_eq!(lines[1], "      8\tline 8");
+        assert_eq!(lines[3], ">    10\tline 10");
+        assert_eq!(lines[5], "     12\tline 12");
+        assert_eq!(lines.len(), 6);
+
+        // The window is clipped at the start of the file
+        let args = ErrorContextArgs {
+            path: "main.rs".to_string(),
+            line: 1,
+            context: None,
+        };
+        let output = run_error_context(&args, dir.path()).await.unwrap();
+        assert!(
+            output.contains(">     1\tline 1\n      2\tline 2"),
+            "{}",
+            output
+        );
+        assert_eq!(output.lines().count(), 7);
 
-        let result = run_list_directory(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("Error"));
-        assert!(output.contains("not a directory"));
+        let args = ErrorContextArgs {
+            path: "main.rs".to_string(),
+            line: 25,
+            context: Some(2),
+        };
+        let output = run_error_context(&args, dir.path()).await.unwrap();
+        assert!(output.contains("past the end"), "{}", output);
     }
 
+    // ========== extract_block tests ==========
+
     #[tokio::test]
-    async fn test_list_directory_with_line_counts() {
+    async fn test_extract_block_rust_function() {
         let dir = tempdir().unwrap();
-        fs::write(dir.path().join("file1.txt"), "line1\nline2\nline3").unwrap();
-        fs::write(dir.path().join("file2.txt"), "hello").unwrap();
-        fs::create_dir(dir.path().join("subdir")).unwrap();
+        fs::write(
+            dir.path().join("lib.rs"),
+            "fn before() {}\n\nfn target(x: i32) -> i32 {\n    let y = x * 2;\n    y + 1\n}\n\nfn after() {}\n",
+        )
+        .unwrap();
 
-        let args = ListDirectoryArgs {
-            path: ".".to_string(),
+        let args = ExtractBlockArgs {
+            path: "lib.rs".to_string(),
+            symbol: "target".to_string(),
         };
-
-        let result = run_list_directory(&args, dir.path()).await;
-        assert!(result.is_ok());
-        let output = result.unwrap();
-        assert!(output.contains("file1.txt (3 lines)"));
-        assert!(output.contains("file2.txt (1 line)"));
-        assert!(output.contains("subdir/"));
+        let output = run_extract_block(&args, dir.path()).await.unwrap();
+        assert!(output.contains("     3\tfn target(x: i32) -> i32 {"));
+        assert!(output.contains("     6\t}"));
+        assert!(!output.contains("before"));
+        assert!(!output.contains("after"));
     }
-}
 
-pub fn run_tree(
-    args: &TreeArgs,
-    workspace_dir: &Path,
-) -> Result<String, McpError> {
-    let rel_path = args.path.as_deref().unwrap_or(".");
-    let root_path = workspace_dir.join(rel_path);
+    #[tokio::test]
+    async fn test_extract_block_python_function() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("app.py"),
+            "import os\n\ndef target(x):\n    y = x * 2\n    return y\n\ndef after():\n    pass\n",
+        )
+        .unwrap();
 
-    if !root_path.exists() {
-        return Err(McpError {
-            code: ErrorCode(-32602),
-            message: format!("Path does not exist: {}", root_path.display()).into(),
-            data: None,
-        });
+        let args = ExtractBlockArgs {
+            path: "app.py".to_string(),
+            symbol: "target".to_string(),
+        };
+        let output = run_extract_block(&args, dir.path()).await.unwrap();
+        assert!(output.contains("     3\tdef target(x):"));
+        assert!(output.contains("     5\t    return y"));
+        assert!(!output.contains("import os"));
+        assert!(!output.contains("after"));
     }
 
-    let max_depth = args.max_depth.unwrap_or(usize::MAX);
-    let truncate = args.truncate.unwrap_or(10);
-    
-    let exclude_vec: Vec<String> = args.exclude.as_deref().unwrap_or("")
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
-
-    let mut output = String::new();
-    // Add root
-    output.push_str(&format!("{}\n", rel_path));
-
-    visit_dirs(
-        &root_path,
-        &mut output,
-        "",
-        0,
-        max_depth,
-        truncate,
-        &exclude_vec,
-    )?;
-
-    Ok(output)
-}
+    #[tokio::test]
+    async fn test_extract_block_symbol_not_found() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("lib.rs"), "fn present() {}\n").unwrap();
 
-fn visit_dirs(
-    dir: &Path,
-    output: &mut String,
-    prefix: &str,
-    current_depth: usize,
-    max_depth: usize,
-    truncate: usize,
-    exclude: &[String],
-) -> Result<(), McpError> {
-    if current_depth >= max_depth {
-        return Ok(());
+        let args = ExtractBlockArgs {
+            path: "lib.rs".to_string(),
+            symbol: "missing".to_string(),
+        };
+        let output = run_extract_block(&args, dir.path()).await.unwrap();
+        assert!(output.contains("Error"));
+        assert!(output.contains("was not found"));
     }
 
-    let entries = fs::read_dir(dir).map_err(|e| McpError {
-        code: ErrorCode(-32603),
-        message: format!("Failed to read directory: {}", e).into(),
-        data: None,
-    })?;
-
-    let mut entries_vec = Vec::new();
-    for entry in entries {
-        let entry = entry.map_err(|e| McpError {
-            code: ErrorCode(-32603),
-            message: format!("Failed to read entry: {}", e).into(),
-            data: None,
-        })?;
-        let name = entry.file_name().to_string_lossy().to_string();
+    // ========== workspace boundary tests ==========
 
-        // Filter excludes and hidden files
-        // Note: exclude matches exact name here.
-        if !name.starts_with('.') && !exclude.contains(&name) {
-            entries_vec.push((name, entry.path()));
+    #[tokio::test]
+    async fn test_glob_tools_stay_inside_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir(&workspace).unwrap();
+        fs::write(dir.path().join("secret.conf"), "secret").unwrap();
+        let history = Mutex::new(HashMap::new());
+        let absolute = dir.path().join("*.conf").to_string_lossy().to_string();
+
+        for pattern in ["../*.conf", "sub/../../*.conf", absolute.as_str()] {
+            let replace = ConditionalReplaceArgs {
+                glob: pattern.to_string(),
+                contains: "secret".to_string(),
+                old_str: "secret".to_string(),
+                new_str: "leaked".to_string(),
+            };
+            let err = run_conditional_replace(&replace, &workspace, &history)
+                .await
+                .unwrap_err();
+            assert!(
+                err.message.contains("outside the workspace"),
+                "{}",
+                err.message
+            );
+
+            let move_args = MoveGlobArgs {
+                pattern: pattern.to_string(),
+                dest_dir: "moved".to_string(),
+            };
+            let err = run_move_glob(&move_args, &workspace, &history)
+                .await
+                .unwrap_err();
+            assert!(
+                err.message.contains("outside the workspace"),
+                "{}",
+                err.message
+            );
         }
+        assert_eq!(
+            fs::read_to_string(dir.path().join("secret.conf")).unwrap(),
+            "secret"
+        );
+        assert!(!workspace.join("moved").exists());
     }
 
-    entries_vec.sort_by(|a, b| a.0.cmp(&b.0));
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_glob_tools_skip_symlinked_matches_outside_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir(&workspace).unwrap();
+        fs::create_dir(dir.path().join("etc")).unwrap();
+        fs::write(dir.path().join("etc/secret.conf"), "secret").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("etc"), workspace.join("linked")).unwrap();
+        let history = Mutex::new(HashMap::new());
 
-    let total_count = entries_vec.len();
-    let mut display_entries = entries_vec;
-    let mut remaining = 0;
+        let replace = ConditionalReplaceArgs {
+            glob: "linked/*.conf".to_string(),
+            contains: "secret".to_string(),
+            old_str: "secret".to_string(),
+            new_str: "leaked".to_string(),
+        };
+        let (output, changed) = run_conditional_replace(&replace, &workspace, &history)
+            .await
+            .unwrap();
+        assert!(changed.is_empty());
+        assert!(
+            output.contains("resolves outside the workspace"),
+            "{}",
+            output
+        );
 
-    if total_count > truncate {
-        remaining = total_count - truncate;
-        display_entries.truncate(truncate);
+        let move_args = MoveGlobArgs {
+            pattern: "linked/*.conf".to_string(),
+            dest_dir: "moved".to_string(),
+        };
+        assert!(run_move_glob(&move_args, &workspace, &history)
+            .await
+            .is_err());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("etc/secret.conf")).unwrap(),
+            "secret"
+        );
     }
 
-    for (i, (name, path)) in display_entries.iter().enumerate() {
-        let is_last_entry = i == display_entries.len() - 1;
-        let show_more = is_last_entry && remaining > 0;
+    #[tokio::test]
+    async fn test_paths_outside_workspace_are_rejected() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir(&workspace).unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        let history = Mutex::new(HashMap::new());
+        let absolute = dir.path().join("secret.txt").to_string_lossy().to_string();
 
-        // connector depends on whether this is logically the last thing printed.
-        // If we show more, this is NOT the last thing strings-wise.
-        let connector = if !show_more && is_last_entry {
-            "└── "
-        } else {
-            "├── "
-        };
+        for path in ["../secret.txt", absolute.as_str()] {
+            let view = ViewFileArgs {
+                path: path.to_string(),
+                ..Default::default()
+            };
+            let output = run_view_file(&view, &workspace).await.unwrap();
+            assert!(output.contains("outside the workspace"), "{}", output);
 
-        output.push_str(&format!("{}{}{}\n", prefix, connector, name));
+            let create = CreateFileArgs {
+                path: path.to_string(),
+                content: "x".to_string(),
+                executable: None,
+            };
+            assert!(run_create_file(&create, &workspace, &history)
+                .await
+                .is_err());
+
+            let replace = StrReplaceArgs {
+                path: path.to_string(),
+                old_str: "secret".to_string(),
+                new_str: "leaked".to_string(),
+                ..Default::default()
+            };
+            assert!(run_str_replace(&replace, &workspace, &history)
+                .await
+                .is_err());
+
+            let insert = InsertLinesArgs {
+                path: path.to_string(),
+                insert_line: 0,
+                content: "x".to_string(),
+            };
+            assert!(run_insert_lines(&insert, &workspace, &history)
+                .await
+                .is_err());
 
-        if path.is_dir() {
-            let new_prefix = if !show_more && is_last_entry {
-                format!("{}    ", prefix)
-            } else {
-                format!("{}│   ", prefix)
+            let delete = DeleteFileArgs {
+                path: path.to_string(),
             };
-            visit_dirs(
-                path,
-                output,
-                &new_prefix,
-                current_depth + 1,
-                max_depth,
-                truncate,
-                exclude,
-            )?;
+            assert!(run_delete_file(&delete, &workspace, &history)
+                .await
+                .is_err());
         }
+        assert_eq!(
+            fs::read_to_string(dir.path().join("secret.txt")).unwrap(),
+            "secret"
+        );
 
-        if show_more {
-            output.push_str(&format!("{}{}... ({} more)\n", prefix, "└── ", remaining));
-        }
+        let list = ListDirectoryArgs {
+            path: "..".to_string(),
+            ..Default::default()
+        };
+        let output = run_list_directory(&list, &workspace).await.unwrap();
+        assert!(output.contains("outside the workspace"), "{}", output);
+
+        let tree = TreeArgs {
+            path: Some("../".to_string()),
+            exclude: None,
+            max_depth: None,
+            truncate: None,
+            include_hidden: None,
+            format: None,
+        };
+        assert!(run_tree(&tree, &workspace).is_err());
     }
-
-    Ok(())
 }
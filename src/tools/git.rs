@@ -0,0 +1,134 @@
+use rmcp::{model::ErrorCode, ErrorData as McpError};
+use serde::Serialize;
+use std::path::Path;
+use tokio::process::Command;
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct GitInfo {
+    pub is_repo: bool,
+    /// Current branch, or `None` when HEAD is detached
+    pub branch: Option<String>,
+    /// Commit HEAD points to, if any commit exists yet
+    pub commit: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: Option<u32>,
+    pub behind: Option<u32>,
+    /// Whether there are staged, unstaged or untracked changes
+    pub dirty: bool,
+}
+
+/// Summarises the branch and working tree state of the workspace using
+/// `git status --porcelain=v2 --branch`.
+pub async fn run_git_info(workspace_dir: &Path) -> Result<GitInfo, McpError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(workspace_dir)
+        .args(["status", "--porcelain=v2", "--branch"])
+        .output()
+        .await
+        .map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to run git: {}", e).into(),
+            data: None,
+        })?;
+
+    if !output.status.success() {
+        // Most commonly "not a git repository"
+        return Ok(GitInfo::default());
+    }
+
+    Ok(parse_porcelain_v2(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_porcelain_v2(status: &str) -> GitInfo {
+    let mut info = GitInfo {
+        is_repo: true,
+        ..Default::default()
+    };
+
+    for line in status.lines() {
+        let Some(header) = line.strip_prefix("# ") else {
+            if !line.is_empty() {
+                info.dirty = true;
+            }
+            continue;
+        };
+
+        let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+        match key {
+            "branch.oid" if value != "(initial)" => info.commit = Some(value.to_string()),
+            "branch.head" if value != "(detached)" => info.branch = Some(value.to_string()),
+            "branch.upstream" => info.upstream = Some(value.to_string()),
+            "branch.ab" => {
+                for part in value.split_whitespace() {
+                    if let Some(n) = part.strip_prefix('+') {
+                        info.ahead = n.parse().ok();
+                    } else if let Some(n) = part.strip_prefix('-') {
+                        info.behind = n.parse().ok();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command as StdCommand;
+    use tempfile::tempdir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = StdCommand::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[tokio::test]
+    async fn test_git_info_named_branch_dirty() {
+        let dir = tempdir().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "feature/info"]);
+        fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        git(dir.path(), &["add", "a.txt"]);
+        git(dir.path(), &["commit", "-q", "-m", "init"]);
+
+        let info = run_git_info(dir.path()).await.unwrap();
+        assert!(info.is_repo);
+        assert_eq!(info.branch.as_deref(), Some("feature/info"));
+        assert!(info.commit.is_some());
+        assert!(!info.dirty);
+
+        fs::write(dir.path().join("a.txt"), "two\n").unwrap();
+        let info = run_git_info(dir.path()).await.unwrap();
+        assert!(info.dirty);
+    }
+
+    #[tokio::test]
+    async fn test_git_info_not_a_repo() {
+        let dir = tempdir().unwrap();
+        let info = run_git_info(dir.path()).await.unwrap();
+        assert_eq!(info, GitInfo::default());
+    }
+
+    #[test]
+    fn test_parse_detached_with_upstream_counts() {
+        let info = parse_porcelain_v2(
+            "# branch.oid abc123\n# branch.head (detached)\n# branch.upstream origin/main\n# branch.ab +2 -3\n",
+        );
+        assert_eq!(info.branch, None);
+        assert_eq!(info.commit.as_deref(), Some("abc123"));
+        assert_eq!(info.upstream.as_deref(), Some("origin/main"));
+        assert_eq!(info.ahead, Some(2));
+        assert_eq!(info.behind, Some(3));
+        assert!(!info.dirty);
+    }
+}
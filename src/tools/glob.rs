@@ -1,64 +1,117 @@
-use glob::glob;
+use glob::{glob, MatchOptions, Pattern};
 use rmcp::schemars;
 use rmcp::ErrorData as McpError;
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+
+use crate::tools::utils;
+
+const DEFAULT_MAX_RESULTS: usize = 100;
 
 #[derive(Deserialize, schemars::JsonSchema)]
 pub struct GlobArgs {
     pub pattern: String,
     pub path: Option<String>,
+    /// Group results under their parent directory
+    pub group_by_dir: Option<bool>,
+    /// Most paths to return (default 100)
+    pub max_results: Option<usize>,
+    /// Skip paths ignored by .gitignore and the .git directory (default true); set false to
+    /// match ignored paths such as build output too
+    pub respect_gitignore: Option<bool>,
 }
 
-pub fn run_glob(args: &GlobArgs, workspace_dir: &Path) -> Result<String, McpError> {
-    let base_path = if let Some(p) = &args.path {
-        PathBuf::from(p)
-    } else {
-        workspace_dir.to_path_buf()
+/// Lists up to `limit` paths matching `pattern` (absolute) by walking from its longest literal
+/// directory prefix, skipping gitignored paths.
+fn walk_matches(pattern: &str, limit: usize) -> Result<Vec<PathBuf>, glob::PatternError> {
+    let compiled = Pattern::new(pattern)?;
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
     };
+    let root: PathBuf = Path::new(pattern)
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect();
+    Ok(utils::search_paths(&root, true, Some(limit), |entry| {
+        compiled.matches_path_with(entry.path(), options)
+    }))
+}
+
+/// Collects the first `max_results` paths in path order matching the pattern, together with
+/// the searched base path and whether more paths matched. Matching stops at the first path
+/// past the cap. On failure the error message to show the caller is returned.
+pub fn glob_matches(
+    args: &GlobArgs,
+    workspace_dir: &Path,
+) -> Result<(PathBuf, Vec<String>, bool), String> {
+    let base_path = utils::search_base(workspace_dir, args.path.as_deref())?;
 
     if !base_path.is_dir() {
-        return Ok(format!(
+        return Err(format!(
             "Path '{}' is not a valid directory",
             base_path.display()
         ));
     }
 
-    let pattern_str = if Path::new(&args.pattern).is_absolute() {
-        args.pattern.clone()
-    } else {
-        base_path.join(&args.pattern).to_string_lossy().to_string()
-    };
+    // Absolute patterns are fine as long as they point into the workspace
+    let pattern = Path::new(&args.pattern);
+    if pattern.components().any(|c| c == Component::ParentDir)
+        || (pattern.is_absolute() && !pattern.starts_with(workspace_dir))
+    {
+        return Err(format!(
+            "Error: Glob pattern reaches outside the workspace: {}",
+            args.pattern
+        ));
+    }
+    let pattern_str = base_path.join(pattern).to_string_lossy().to_string();
 
+    let max_results = args.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+    let limit = max_results.saturating_add(1);
     let mut matches = Vec::new();
-    // glob returns Result<Paths, PatternError>
-    let paths = match glob(&pattern_str) {
+    let paths: Result<Vec<_>, _> = if args.respect_gitignore.unwrap_or(true) {
+        walk_matches(&pattern_str, limit).map(|paths| paths.into_iter().map(Ok).collect())
+    } else {
+        glob(&pattern_str).map(|paths| paths.take(limit).collect())
+    };
+    let paths = match paths {
         Ok(p) => p,
         Err(e) => {
-            return Ok(format!(
+            return Err(format!(
                 "Error: Invalid glob pattern '{}': {}",
                 args.pattern, e
             ))
         }
     };
 
+    let mut truncated = false;
     for entry in paths {
         match entry {
-            Ok(path) => {
-                matches.push(path.to_string_lossy().to_string());
-                if matches.len() >= 100 {
-                    break;
-                }
-            }
+            // At most one path past the cap is read, to tell whether there are more
+            Ok(_) if matches.len() >= max_results => truncated = true,
+            Ok(path) => matches.push(path.to_string_lossy().to_string()),
             Err(e) => {
-                return Ok(format!("Error while iterating glob matches: {}", e));
+                return Err(format!("Error while iterating glob matches: {}", e));
             }
         }
     }
 
-    let truncated = matches.len() >= 100;
+    Ok((base_path, matches, truncated))
+}
+
+pub fn run_glob(args: &GlobArgs, workspace_dir: &Path) -> Result<String, McpError> {
+    let (base_path, matches, truncated) = match glob_matches(args, workspace_dir) {
+        Ok(found) => found,
+        Err(message) => return Ok(message),
+    };
+
     let count = matches.len();
-    let matches_str = matches.join("\n");
+    let matches_str = if args.group_by_dir.unwrap_or(false) {
+        utils::group_paths_by_dir(&matches)
+    } else {
+        matches.join("\n")
+    };
     let mut output = format!(
         "Found {} file(s) matching pattern '{}' in '{}':\n{}",
         count,
@@ -69,7 +122,7 @@ pub fn run_glob(args: &GlobArgs, workspace_dir: &Path) -> Result<String, McpErro
 
     if truncated {
         output.push_str(
-            "\n\n[Results truncated to first 100 files. Consider using a more specific pattern.]",
+            "\n... (more matches truncated; use a more specific pattern or raise max_results)",
         );
     }
 
@@ -99,6 +152,9 @@ mod tests {
         let args = GlobArgs {
             pattern: "*.txt".to_string(),
             path: Some(dir.path().to_string_lossy().to_string()),
+            group_by_dir: None,
+            max_results: None,
+            respect_gitignore: None,
         };
 
         let result = run_glob(&args, dir.path()).unwrap();
@@ -112,6 +168,9 @@ mod tests {
         let args = GlobArgs {
             pattern: "*.rs".to_string(),
             path: Some(dir.path().to_string_lossy().to_string()),
+            group_by_dir: None,
+            max_results: None,
+            respect_gitignore: None,
         };
 
         let result = run_glob(&args, dir.path()).unwrap();
@@ -128,10 +187,129 @@ mod tests {
         let args = GlobArgs {
             pattern: "**/*.json".to_string(),
             path: Some(dir.path().to_string_lossy().to_string()),
+            group_by_dir: None,
+            max_results: None,
+            respect_gitignore: None,
         };
 
         let result = run_glob(&args, dir.path()).unwrap();
         assert!(result.contains("Found 1 file(s)"));
         assert!(result.contains("test.json"));
     }
+
+    #[test]
+    fn test_glob_group_by_dir() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("a")).unwrap();
+        std::fs::create_dir(dir.path().join("b")).unwrap();
+        File::create(dir.path().join("a/one.txt")).unwrap();
+        File::create(dir.path().join("a/two.txt")).unwrap();
+        File::create(dir.path().join("b/three.txt")).unwrap();
+
+        let args = GlobArgs {
+            pattern: "**/*.txt".to_string(),
+            path: Some(dir.path().to_string_lossy().to_string()),
+            group_by_dir: Some(true),
+            max_results: None,
+            respect_gitignore: None,
+        };
+
+        let result = run_glob(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 3 file(s)"));
+        let dir_a = dir.path().join("a");
+        let dir_b = dir.path().join("b");
+        assert!(result.contains(&format!("{}/ (2)\n  one.txt\n  two.txt", dir_a.display())));
+        assert!(result.contains(&format!("{}/ (1)\n  three.txt", dir_b.display())));
+    }
+
+    #[test]
+    fn test_glob_max_results() {
+        let dir = tempdir().unwrap();
+        for i in 0..30 {
+            File::create(dir.path().join(format!("file{:02}.txt", i))).unwrap();
+        }
+
+        let args = GlobArgs {
+            pattern: "*.txt".to_string(),
+            path: None,
+            group_by_dir: None,
+            max_results: Some(10),
+            respect_gitignore: None,
+        };
+        let result = run_glob(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 10 file(s)"), "{}", result);
+        // Always the first files in path order
+        assert!(result.contains("file09.txt") && !result.contains("file10.txt"));
+        assert!(result.ends_with(
+            "... (more matches truncated; use a more specific pattern or raise max_results)"
+        ));
+
+        let args = GlobArgs {
+            max_results: Some(30),
+            ..args
+        };
+        let result = run_glob(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 30 file(s)"));
+        assert!(!result.contains("truncated"));
+    }
+
+    #[test]
+    fn test_glob_respects_gitignore() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        File::create(dir.path().join("src/lib.rs")).unwrap();
+        File::create(dir.path().join("target/debug/build.rs")).unwrap();
+
+        let args = GlobArgs {
+            pattern: "**/*.rs".to_string(),
+            path: None,
+            group_by_dir: None,
+            max_results: None,
+            respect_gitignore: None,
+        };
+        let result = run_glob(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 1 file(s)"), "{}", result);
+        assert!(result.contains("src/lib.rs"));
+        assert!(!result.contains("target"));
+
+        // Ignored paths can still be searched on request
+        let args = GlobArgs {
+            respect_gitignore: Some(false),
+            ..args
+        };
+        let result = run_glob(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 2 file(s)"), "{}", result);
+        assert!(result.contains("target/debug/build.rs"));
+    }
+
+    #[test]
+    fn test_glob_stays_inside_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        std::fs::create_dir_all(dir.path().join("outside")).unwrap();
+        File::create(dir.path().join("outside/secret.txt")).unwrap();
+        std::fs::create_dir(&workspace).unwrap();
+
+        let outside = dir.path().join("outside");
+        let outside_pattern = outside.join("*.txt");
+        for (pattern, path) in [
+            ("*.txt", Some("../outside")),
+            ("*.txt", outside.to_str()),
+            ("../outside/*.txt", None),
+            (outside_pattern.to_str().unwrap(), None),
+        ] {
+            let args = GlobArgs {
+                pattern: pattern.to_string(),
+                path: path.map(str::to_string),
+                group_by_dir: None,
+                max_results: None,
+                respect_gitignore: None,
+            };
+            let result = run_glob(&args, &workspace).unwrap();
+            assert!(result.contains("outside the workspace"), "{}", result);
+            assert!(!result.contains("secret.txt"));
+        }
+    }
 }
@@ -1,47 +1,77 @@
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use rmcp::schemars;
 use rmcp::ErrorData as McpError;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
 
-#[derive(Deserialize, schemars::JsonSchema)]
+use crate::tools::utils;
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+
+#[derive(Default, Deserialize, schemars::JsonSchema)]
 pub struct GrepArgs {
     pub pattern: String,
     pub path: Option<String>,
     pub include: Option<String>,
+    /// Group results under their parent directory
+    pub group_by_dir: Option<bool>,
+    /// "files" (default) lists matching paths; "summary" adds the hit count and first
+    /// matching line of each file
+    pub output_mode: Option<String>,
+    /// List every matching line as `path:line_number: text` instead of only the file paths
+    pub show_lines: Option<bool>,
+    /// Lines shown before each matching line (implies show_lines)
+    pub context_before: Option<usize>,
+    /// Lines shown after each matching line (implies show_lines)
+    pub context_after: Option<usize>,
+    /// Match letters regardless of case, like `grep -i`
+    pub case_insensitive: Option<bool>,
+    /// Only match the pattern at word boundaries, like `grep -w`
+    pub whole_word: Option<bool>,
+    /// Most files to return (default 100)
+    pub max_results: Option<usize>,
+    /// Skip paths ignored by .gitignore and the .git directory (default true); set false to
+    /// search ignored paths such as build output too
+    pub respect_gitignore: Option<bool>,
 }
 
-pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpError> {
-    let base_path = if let Some(p) = &args.path {
-        PathBuf::from(p)
+/// Compiles the search pattern with the case and word-boundary options applied.
+fn build_regex(args: &GrepArgs) -> Result<Regex, String> {
+    let pattern = if args.whole_word.unwrap_or(false) {
+        format!(r"\b(?:{})\b", args.pattern)
     } else {
-        workspace_dir.to_path_buf()
+        args.pattern.clone()
     };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(args.case_insensitive.unwrap_or(false))
+        .build()
+        .map_err(|e| format!("Error: Invalid regex pattern '{}': {}", args.pattern, e))
+}
+
+/// Collects the first `max_results` files in path order whose content matches the pattern,
+/// together with the searched base path, whether more files matched and the compiled pattern.
+/// The walk stops at the first match past the cap. On failure the error message to show the
+/// caller is returned.
+pub fn grep_matches(
+    args: &GrepArgs,
+    workspace_dir: &Path,
+) -> Result<(PathBuf, Vec<String>, bool, Regex), String> {
+    let base_path = utils::search_base(workspace_dir, args.path.as_deref())?;
 
     if !base_path.is_dir() {
-        return Ok(format!(
+        return Err(format!(
             "Path '{}' is not a valid directory",
             base_path.display()
         ));
     }
 
-    let re = match Regex::new(&args.pattern) {
-        Ok(r) => r,
-        Err(e) => {
-            return Ok(format!(
-                "Error: Invalid regex pattern '{}': {}",
-                args.pattern, e
-            ))
-        }
-    };
+    let re = build_regex(args)?;
 
-    let include_pattern = args.include.as_deref();
-    let include_glob = if let Some(p) = include_pattern {
+    let include_glob = if let Some(p) = args.include.as_deref() {
         match glob::Pattern::new(p) {
             Ok(pat) => Some(pat),
             Err(e) => {
-                return Ok(format!(
+                return Err(format!(
                     "Error: Invalid include glob pattern '{}': {}",
                     p, e
                 ))
@@ -51,34 +81,119 @@ pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpErro
         None
     };
 
-    let mut matches = Vec::new();
-    let walker = WalkDir::new(&base_path).follow_links(true).into_iter();
+    let respect_gitignore = args.respect_gitignore.unwrap_or(true);
+    let max_results = args.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+    let limit = Some(max_results.saturating_add(1));
+    let found = utils::search_paths(&base_path, respect_gitignore, limit, |entry| {
+        entry.file_type().is_some_and(|t| t.is_file())
+            && include_glob
+                .as_ref()
+                .is_none_or(|pat| pat.matches_path(Path::new(entry.file_name())))
+            && std::fs::read_to_string(entry.path()).is_ok_and(|content| re.is_match(&content))
+    });
 
-    for entry in walker.filter_map(|e| e.ok()) {
-        if matches.len() >= 100 {
-            break;
-        }
-        if !entry.file_type().is_file() {
+    let truncated = found.len() > max_results;
+    let matches = found
+        .iter()
+        .take(max_results)
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    Ok((base_path, matches, truncated, re))
+}
+
+/// Formats a matching file as its path, hit count and first matching line.
+fn summarize_file(path: &str, re: &Regex) -> String {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let hits = re.find_iter(&content).count();
+    let first = content
+        .lines()
+        .enumerate()
+        .find(|(_, line)| re.is_match(line))
+        .map(|(i, line)| format!("\n  {}: {}", i + 1, line.trim()))
+        .unwrap_or_default();
+    format!(
+        "{} ({} match{}){}",
+        path,
+        hits,
+        if hits == 1 { "" } else { "es" },
+        first
+    )
+}
+
+/// Formats the matching lines of a file like ripgrep: `path:N: text` for matches and
+/// `path-N- text` for context, with `--` between windows that do not touch.
+fn matching_lines(path: &str, re: &Regex, before: usize, after: usize) -> String {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let lines: Vec<&str> = content.lines().collect();
+    let mut output = Vec::new();
+    // One past the last line already printed
+    let mut printed_to = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if !re.is_match(line) {
             continue;
         }
-
-        if let Some(ref pat) = include_glob {
-            if !pat.matches_path(Path::new(entry.file_name())) {
-                continue;
-            }
+        let start = i.saturating_sub(before).max(printed_to);
+        if printed_to > 0 && start > printed_to {
+            output.push("--".to_string());
+        }
+        let end = (i + after + 1).min(lines.len());
+        for (j, text) in lines.iter().enumerate().take(end).skip(start) {
+            let sep = if re.is_match(text) { ':' } else { '-' };
+            output.push(format!("{}{}{}{} {}", path, sep, j + 1, sep, text));
         }
+        printed_to = printed_to.max(end);
+    }
+    output.join("\n")
+}
 
-        let path = entry.path();
-        if let Ok(content) = std::fs::read_to_string(path) {
-            if re.is_match(&content) {
-                matches.push(path.to_string_lossy().to_string());
-            }
+pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpError> {
+    let summary = match args.output_mode.as_deref() {
+        None | Some("files") => false,
+        Some("summary") => true,
+        Some(other) => {
+            return Ok(format!(
+                "Error: Unknown output_mode '{}'. Supported modes are 'files' and 'summary'.",
+                other
+            ));
         }
+    };
+
+    let context_before = args.context_before.unwrap_or(0);
+    let context_after = args.context_after.unwrap_or(0);
+    let show_lines = args.show_lines.unwrap_or(false)
+        || args.context_before.is_some()
+        || args.context_after.is_some();
+    if show_lines && summary {
+        return Ok(
+            "Error: show_lines and context cannot be combined with output_mode 'summary'."
+                .to_string(),
+        );
     }
 
-    let truncated = matches.len() >= 100;
+    let (base_path, matches, truncated, re) = match grep_matches(args, workspace_dir) {
+        Ok(found) => found,
+        Err(message) => return Ok(message),
+    };
+    let include_pattern = args.include.as_deref();
+
     let count = matches.len();
-    let matches_str = matches.join("\n");
+    let matches_str = if summary {
+        matches
+            .iter()
+            .map(|path| summarize_file(path, &re))
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else if show_lines {
+        matches
+            .iter()
+            .map(|path| matching_lines(path, &re, context_before, context_after))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    } else if args.group_by_dir.unwrap_or(false) {
+        utils::group_paths_by_dir(&matches)
+    } else {
+        matches.join("\n")
+    };
     let mut output = format!(
         "Found {} file(s) containing pattern '{}' in '{}'",
         count,
@@ -93,7 +208,7 @@ pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpErro
 
     if truncated {
         output.push_str(
-            "\n\n[Results truncated to first 100 files. Consider using a more specific pattern.]",
+            "\n... (more matches truncated; use a more specific pattern or raise max_results)",
         );
     }
 
@@ -128,7 +243,7 @@ mod tests {
         let args = GrepArgs {
             pattern: "world".to_string(),
             path: Some(dir.path().to_string_lossy().to_string()),
-            include: None,
+            ..Default::default()
         };
 
         let result = run_grep(&args, dir.path()).unwrap();
@@ -146,7 +261,7 @@ mod tests {
         let args = GrepArgs {
             pattern: r"\d+".to_string(),
             path: Some(dir.path().to_string_lossy().to_string()),
-            include: None,
+            ..Default::default()
         };
 
         let result = run_grep(&args, dir.path()).unwrap();
@@ -164,7 +279,7 @@ mod tests {
         let args = GrepArgs {
             pattern: "(?i)hello".to_string(),
             path: Some(dir.path().to_string_lossy().to_string()),
-            include: None,
+            ..Default::default()
         };
 
         let result = run_grep(&args, dir.path()).unwrap();
@@ -183,6 +298,7 @@ mod tests {
             pattern: "match".to_string(),
             path: Some(dir.path().to_string_lossy().to_string()),
             include: Some("*.rs".to_string()),
+            ..Default::default()
         };
 
         let result = run_grep(&args, dir.path()).unwrap();
@@ -196,8 +312,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let args = GrepArgs {
             pattern: "[".to_string(), // Invalid regex
-            path: None,
-            include: None,
+            ..Default::default()
         };
         let result = run_grep(&args, dir.path()).unwrap();
         assert!(result.contains("Error: Invalid regex pattern"));
@@ -208,10 +323,253 @@ mod tests {
         let dir = tempdir().unwrap();
         let args = GrepArgs {
             pattern: "test".to_string(),
-            path: None,
             include: Some("[".to_string()), // Invalid glob
+            ..Default::default()
         };
         let result = run_grep(&args, dir.path()).unwrap();
         assert!(result.contains("Error: Invalid include glob pattern"));
     }
+
+    #[test]
+    fn test_grep_group_by_dir() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("a")).unwrap();
+        std::fs::create_dir(dir.path().join("b")).unwrap();
+        writeln!(File::create(dir.path().join("a/one.txt")).unwrap(), "match").unwrap();
+        writeln!(File::create(dir.path().join("a/two.txt")).unwrap(), "match").unwrap();
+        writeln!(
+            File::create(dir.path().join("b/three.txt")).unwrap(),
+            "match"
+        )
+        .unwrap();
+        writeln!(
+            File::create(dir.path().join("b/other.txt")).unwrap(),
+            "nope"
+        )
+        .unwrap();
+
+        let args = GrepArgs {
+            pattern: "match".to_string(),
+            path: Some(dir.path().to_string_lossy().to_string()),
+            group_by_dir: Some(true),
+            ..Default::default()
+        };
+
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 3 file(s)"));
+        let dir_a = dir.path().join("a");
+        let dir_b = dir.path().join("b");
+        assert!(result.contains(&format!("{}/ (2)\n  one.txt\n  two.txt", dir_a.display())));
+        assert!(result.contains(&format!("{}/ (1)\n  three.txt", dir_b.display())));
+        assert!(!result.contains("other.txt"));
+    }
+
+    #[test]
+    fn test_grep_summary_mode() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.rs"),
+            "fn main() {}\n// TODO: first\nlet x = 1; // TODO: second TODO\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("b.rs"), "// TODO: only one\n").unwrap();
+        std::fs::write(dir.path().join("c.rs"), "nothing here\n").unwrap();
+
+        let args = GrepArgs {
+            pattern: "TODO".to_string(),
+            output_mode: Some("summary".to_string()),
+            ..Default::default()
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 2 file(s)"));
+        assert!(result.contains("a.rs (3 matches)\n  2: // TODO: first"));
+        assert!(result.contains("b.rs (1 match)\n  1: // TODO: only one"));
+        assert!(!result.contains("c.rs"));
+
+        let args = GrepArgs {
+            output_mode: Some("lines".to_string()),
+            ..args
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.starts_with("Error: Unknown output_mode"));
+    }
+
+    #[test]
+    fn test_grep_show_lines() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\nmatch two\nthree\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "nothing\n").unwrap();
+
+        let args = GrepArgs {
+            pattern: "match".to_string(),
+            show_lines: Some(true),
+            ..Default::default()
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        let path = dir.path().join("a.txt");
+        assert!(result.contains("Found 1 file(s)"));
+        assert!(result.ends_with(&format!(":\n{}:2: match two", path.display())));
+    }
+
+    #[test]
+    fn test_grep_context_windows() {
+        let dir = tempdir().unwrap();
+        let content: String = (1..=12)
+            .map(|i| match i {
+                3 | 5 | 11 => format!("hit {}\n", i),
+                _ => format!("line {}\n", i),
+            })
+            .collect();
+        std::fs::write(dir.path().join("a.txt"), content).unwrap();
+
+        let args = GrepArgs {
+            pattern: "hit".to_string(),
+            context_before: Some(1),
+            context_after: Some(1),
+            ..Default::default()
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        let path = dir.path().join("a.txt").display().to_string();
+        let expected = [
+            format!("{}-2- line 2", path),
+            format!("{}:3: hit 3", path),
+            format!("{}-4- line 4", path),
+            format!("{}:5: hit 5", path),
+            format!("{}-6- line 6", path),
+            "--".to_string(),
+            format!("{}-10- line 10", path),
+            format!("{}:11: hit 11", path),
+            format!("{}-12- line 12", path),
+        ]
+        .join("\n");
+        assert!(result.ends_with(&format!(":\n{}", expected)), "{}", result);
+
+        let args = GrepArgs {
+            output_mode: Some("summary".to_string()),
+            ..args
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.starts_with("Error: show_lines"));
+    }
+
+    #[test]
+    fn test_grep_case_insensitive_option() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "struct Foo;\n").unwrap();
+
+        let mut args = GrepArgs {
+            pattern: "foo".to_string(),
+            ..Default::default()
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.starts_with("No files found"), "{}", result);
+
+        args.case_insensitive = Some(true);
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 1 file(s)"), "{}", result);
+    }
+
+    #[test]
+    fn test_grep_whole_word() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a keyword here\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "one word here\n").unwrap();
+
+        let args = GrepArgs {
+            pattern: "word".to_string(),
+            whole_word: Some(true),
+            ..Default::default()
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 1 file(s)"), "{}", result);
+        assert!(result.contains("b.txt"));
+        assert!(!result.contains("a.txt"));
+
+        // The error names the pattern as given, not the wrapped one
+        let args = GrepArgs {
+            pattern: "(".to_string(),
+            ..args
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(
+            result.starts_with("Error: Invalid regex pattern '('"),
+            "{}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_grep_max_results() {
+        let dir = tempdir().unwrap();
+        for i in 0..30 {
+            std::fs::write(dir.path().join(format!("file{:02}.txt", i)), "match\n").unwrap();
+        }
+
+        let args = GrepArgs {
+            pattern: "match".to_string(),
+            max_results: Some(10),
+            ..Default::default()
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 10 file(s)"), "{}", result);
+        // Always the first files in path order
+        assert!(result.contains("file09.txt") && !result.contains("file10.txt"));
+        assert!(result.ends_with(
+            "... (more matches truncated; use a more specific pattern or raise max_results)"
+        ));
+
+        let args = GrepArgs {
+            max_results: Some(30),
+            ..args
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 30 file(s)"));
+        assert!(!result.contains("truncated"));
+    }
+
+    #[test]
+    fn test_grep_respects_gitignore() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn needle() {}\n").unwrap();
+        std::fs::write(dir.path().join("target/out.rs"), "fn needle() {}\n").unwrap();
+
+        let args = GrepArgs {
+            pattern: "needle".to_string(),
+            ..Default::default()
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 1 file(s)"), "{}", result);
+        assert!(!result.contains("target"));
+
+        let args = GrepArgs {
+            respect_gitignore: Some(false),
+            ..args
+        };
+        let result = run_grep(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 2 file(s)"), "{}", result);
+        assert!(result.contains("out.rs"));
+    }
+
+    #[test]
+    fn test_grep_stays_inside_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        std::fs::create_dir_all(dir.path().join("outside")).unwrap();
+        std::fs::write(dir.path().join("outside/secret.txt"), "token\n").unwrap();
+        std::fs::create_dir(&workspace).unwrap();
+
+        let outside = dir.path().join("outside");
+        for path in ["../outside", outside.to_str().unwrap()] {
+            let args = GrepArgs {
+                pattern: "token".to_string(),
+                path: Some(path.to_string()),
+                ..Default::default()
+            };
+            let result = run_grep(&args, &workspace).unwrap();
+            assert!(result.contains("outside the workspace"), "{}", result);
+            assert!(!result.contains("secret.txt"));
+        }
+    }
 }
@@ -0,0 +1,271 @@
+use regex::Regex;
+use rmcp::schemars;
+use rmcp::ErrorData as McpError;
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+
+use crate::tools::outline::{self, Language};
+use crate::tools::utils;
+
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "d.ts", "js", "jsx", "mjs", "cjs"];
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct ResolveImportArgs {
+    /// File containing the import, relative to the workspace
+    pub from_file: String,
+    /// Import path or statement, e.g. `./utils`, `mod foo;` or `crate::net::http`
+    pub import: String,
+}
+
+pub fn run_resolve_import(
+    args: &ResolveImportArgs,
+    workspace_dir: &Path,
+) -> Result<String, McpError> {
+    let from_path = utils::resolve_within_workspace(workspace_dir, &args.from_file)?;
+    if !from_path.is_file() {
+        return Ok(format!(
+            "Error: The file {} does not exist.",
+            from_path.display()
+        ));
+    }
+
+    let resolved = match outline::detect_language(&from_path) {
+        Language::Rust => resolve_rust(&from_path, &args.import),
+        Language::JavaScript => resolve_js(&from_path, &args.import),
+        _ => {
+            return Ok(format!(
+                "Error: Import resolution is only supported for Rust and TypeScript/JavaScript files, not {}.",
+                from_path.display()
+            ));
+        }
+    };
+
+    // Never report files outside the workspace
+    match resolved.filter(|p| p.starts_with(workspace_dir)) {
+        Some(path) => Ok(format!(
+            "Resolved `{}` to {}",
+            args.import,
+            path.strip_prefix(workspace_dir).unwrap_or(&path).display()
+        )),
+        None => Ok(format!(
+            "Not found: `{}` could not be resolved from {}.",
+            args.import, args.from_file
+        )),
+    }
+}
+
+/// Removes `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Directory holding the child modules of the module defined in `file`.
+fn rust_module_dir(file: &Path) -> PathBuf {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    match file.file_stem().and_then(|s| s.to_str()) {
+        Some("mod" | "lib" | "main") | None => parent.to_path_buf(),
+        Some(stem) => parent.join(stem),
+    }
+}
+
+/// The `src` directory of the closest crate containing `file`.
+fn rust_crate_root(file: &Path) -> Option<PathBuf> {
+    file.ancestors()
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(|dir| dir.join("src"))
+}
+
+/// Resolves `mod foo;`, `./foo`, `self::`, `super::` and `crate::` paths to the file
+/// defining the module. Trailing item names (`crate::net::Client`) are tolerated by
+/// trying the longest module prefix first.
+fn resolve_rust(from_file: &Path, import: &str) -> Option<PathBuf> {
+    let import = import
+        .trim()
+        .trim_end_matches(';')
+        .trim_start_matches("pub ")
+        .trim_start_matches("use ")
+        .trim_start_matches("mod ")
+        .trim();
+
+    if import.starts_with("./") || import.starts_with("../") {
+        let base = normalize(&from_file.parent()?.join(import));
+        return rust_module_file(&base);
+    }
+
+    let mut segments: Vec<&str> = import.split("::").collect();
+    let base = match segments.first().copied() {
+        Some("crate") => {
+            segments.remove(0);
+            rust_crate_root(from_file)?
+        }
+        Some("self") => {
+            segments.remove(0);
+            rust_module_dir(from_file)
+        }
+        Some("super") => {
+            let mut dir = rust_module_dir(from_file);
+            while segments.first() == Some(&"super") {
+                segments.remove(0);
+                dir.pop();
+            }
+            dir
+        }
+        _ => rust_module_dir(from_file),
+    };
+    if segments.is_empty() {
+        return None;
+    }
+
+    for len in (1..=segments.len()).rev() {
+        let candidate = segments[..len]
+            .iter()
+            .fold(base.clone(), |path, segment| path.join(segment));
+        if let Some(found) = rust_module_file(&candidate) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn rust_module_file(base: &Path) -> Option<PathBuf> {
+    let as_file = base.with_extension("rs");
+    if as_file.is_file() {
+        return Some(as_file);
+    }
+    let as_dir = base.join("mod.rs");
+    as_dir.is_file().then_some(as_dir)
+}
+
+/// Resolves relative specifiers (`./foo`, `../bar/baz`) the way bundlers do: the exact
+/// file, then known extensions, then an `index` file inside the directory.
+fn resolve_js(from_file: &Path, import: &str) -> Option<PathBuf> {
+    // Accept a whole statement such as `import { x } from './foo';`
+    let quoted = Regex::new(r#"['"]([^'"]+)['"]"#).unwrap();
+    let specifier = quoted
+        .captures(import)
+        .map(|c| c[1].to_string())
+        .unwrap_or_else(|| import.trim().to_string());
+
+    // Bare specifiers refer to packages, which are out of scope
+    if !specifier.starts_with("./") && !specifier.starts_with("../") {
+        return None;
+    }
+
+    let base = normalize(&from_file.parent()?.join(&specifier));
+    if base.is_file() {
+        return Some(base);
+    }
+
+    // TypeScript sources are often imported with a `.js` extension under ESM
+    let stem = match base.extension().and_then(|e| e.to_str()) {
+        Some("js" | "jsx" | "mjs" | "cjs") => base.with_extension(""),
+        _ => base.clone(),
+    };
+
+    let with_ext = |path: &Path, ext: &str| {
+        let mut s = path.as_os_str().to_os_string();
+        s.push(".");
+        s.push(ext);
+        PathBuf::from(s)
+    };
+    JS_EXTENSIONS
+        .iter()
+        .map(|ext| with_ext(&stem, ext))
+        .chain(
+            JS_EXTENSIONS
+                .iter()
+                .map(|ext| with_ext(&base.join("index"), ext)),
+        )
+        .find(|p| p.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn touch(root: &Path, rel: &str) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    fn resolve(root: &Path, from_file: &str, import: &str) -> String {
+        let args = ResolveImportArgs {
+            from_file: from_file.to_string(),
+            import: import.to_string(),
+        };
+        run_resolve_import(&args, root).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_rust_imports() {
+        let dir = tempdir().unwrap();
+        touch(dir.path(), "Cargo.toml");
+        touch(dir.path(), "src/main.rs");
+        touch(dir.path(), "src/config.rs");
+        touch(dir.path(), "src/net/mod.rs");
+        touch(dir.path(), "src/net/http.rs");
+
+        let output = resolve(dir.path(), "src/main.rs", "mod config;");
+        assert!(output.contains("to src/config.rs"));
+
+        let output = resolve(dir.path(), "src/main.rs", "./net");
+        assert!(output.contains("to src/net/mod.rs"));
+
+        let output = resolve(dir.path(), "src/net/http.rs", "crate::net::http::Client");
+        assert!(output.contains("to src/net/http.rs"));
+
+        let output = resolve(dir.path(), "src/net/http.rs", "super::super::config");
+        assert!(output.contains("to src/config.rs"));
+
+        let output = resolve(dir.path(), "src/main.rs", "mod missing;");
+        assert!(output.starts_with("Not found"));
+    }
+
+    #[test]
+    fn test_resolve_ts_imports() {
+        let dir = tempdir().unwrap();
+        touch(dir.path(), "src/app.ts");
+        touch(dir.path(), "src/utils/index.ts");
+        touch(dir.path(), "lib/format.tsx");
+
+        let output = resolve(dir.path(), "src/app.ts", "import { a } from './utils';");
+        assert!(output.contains("to src/utils/index.ts"));
+
+        let output = resolve(dir.path(), "src/app.ts", "../lib/format.js");
+        assert!(output.contains("to lib/format.tsx"));
+
+        let output = resolve(dir.path(), "src/app.ts", "react");
+        assert!(output.starts_with("Not found"));
+    }
+
+    #[test]
+    fn test_resolve_import_stays_inside_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        touch(dir.path(), "outside/main.rs");
+        fs::create_dir(&workspace).unwrap();
+
+        let outside = dir.path().join("outside/main.rs");
+        for from_file in ["../outside/main.rs", outside.to_str().unwrap()] {
+            let args = ResolveImportArgs {
+                from_file: from_file.to_string(),
+                import: "mod config;".to_string(),
+            };
+            let err = run_resolve_import(&args, &workspace).unwrap_err();
+            assert!(err.message.contains("outside the workspace"));
+        }
+    }
+}
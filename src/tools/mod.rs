@@ -1,4 +1,16 @@
+pub mod checkpoint;
+pub mod code_stats;
+pub mod disk_usage;
+pub mod fetch;
 pub mod file_tools;
+pub mod git;
 pub mod glob;
 pub mod grep;
+pub mod imports;
+pub mod outline;
+pub mod patch;
+pub mod search_all;
+pub mod tool_versions;
 pub mod utils;
+pub mod version;
+pub mod watch;
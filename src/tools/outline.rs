@@ -0,0 +1,137 @@
+use regex::Regex;
+use std::path::Path;
+
+/// Source languages recognised by the outline heuristics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+    /// Any other brace-delimited language (C, C++, Java, ...)
+    BraceLike,
+}
+
+pub fn detect_language(path: &Path) -> Language {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "rs" => Language::Rust,
+        "py" | "pyi" => Language::Python,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => Language::JavaScript,
+        "go" => Language::Go,
+        _ => Language::BraceLike,
+    }
+}
+
+fn definition_regex(language: Language, symbol: &str) -> Regex {
+    let name = regex::escape(symbol);
+    let pattern = match language {
+        Language::Rust => format!(
+            r"^\s*(pub(\([^)]*\))?\s+)?((async|const|unsafe|extern\s+\S+)\s+)*(fn|struct|enum|trait|union|mod|type|const|static|macro_rules!)\s+{name}\b|^\s*impl(<.*>)?\s+(.*\bfor\s+)?{name}\b"
+        ),
+        Language::Python => format!(r"^\s*(async\s+)?(def|class)\s+{name}\b"),
+        Language::JavaScript => format!(
+            r"^\s*(export\s+)?(default\s+)?((async\s+)?function\*?\s+{name}\b|(abstract\s+)?class\s+{name}\b|(interface|type|enum)\s+{name}\b|(const|let|var)\s+{name}\s*=)"
+        ),
+        Language::Go => format!(r"^\s*(func\s+(\([^)]*\)\s*)?{name}\b|type\s+{name}\b)"),
+        Language::BraceLike => format!(r"^\s*[\w<>:,\*&\s]*\b{name}\s*(\(|\{{|$)"),
+    };
+    Regex::new(&pattern).expect("definition pattern should be valid")
+}
+
+/// Returns the 0-based line index where `symbol` is defined.
+pub fn find_definition(lines: &[&str], symbol: &str, language: Language) -> Option<usize> {
+    let re = definition_regex(language, symbol);
+    lines.iter().position(|line| re.is_match(line))
+}
+
+/// Returns the 0-based inclusive span of the block whose definition starts at `start`,
+/// including attributes, decorators and doc comments directly above it.
+pub fn block_span(lines: &[&str], start: usize, language: Language) -> (usize, usize) {
+    let end = match language {
+        Language::Python => indent_block_end(lines, start),
+        _ => brace_block_end(lines, start),
+    };
+
+    let mut first = start;
+    while first > 0 {
+        let prev = lines[first - 1].trim_start();
+        let is_preamble = match language {
+            Language::Rust => prev.starts_with("#[") || prev.starts_with("///"),
+            Language::Python => prev.starts_with('@'),
+            _ => prev.starts_with('@') || prev.starts_with("//") || prev.starts_with("/**"),
+        };
+        if !is_preamble {
+            break;
+        }
+        first -= 1;
+    }
+    (first, end)
+}
+
+fn indent_block_end(lines: &[&str], start: usize) -> usize {
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+    let base_indent = indent_of(lines[start]);
+    let mut end = start;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent_of(line) <= base_indent {
+            break;
+        }
+        end = i;
+    }
+    end
+}
+
+fn brace_block_end(lines: &[&str], start: usize) -> usize {
+    let mut depth = 0i32;
+    let mut opened = false;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        for c in line.chars() {
+            match c {
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                // Declarations without a body, e.g. `struct Marker;`
+                ';' if !opened => return i,
+                _ => {}
+            }
+        }
+        if opened && depth <= 0 {
+            return i;
+        }
+    }
+    lines.len().saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_block_span() {
+        let source = "use std::fmt;\n\n/// Adds numbers\n#[inline]\npub fn add(a: i32, b: i32) -> i32 {\n    if a > 0 {\n        a + b\n    } else {\n        b\n    }\n}\n\nstruct Marker;\n";
+        let lines: Vec<&str> = source.lines().collect();
+
+        let start = find_definition(&lines, "add", Language::Rust).unwrap();
+        assert_eq!(start, 4);
+        assert_eq!(block_span(&lines, start, Language::Rust), (2, 10));
+
+        let marker = find_definition(&lines, "Marker", Language::Rust).unwrap();
+        assert_eq!(block_span(&lines, marker, Language::Rust), (12, 12));
+    }
+
+    #[test]
+    fn test_python_block_span() {
+        let source = "class Greeter:\n    @staticmethod\n    def greet(name):\n        msg = 'hi'\n\n        return msg\n\n    def other(self):\n        pass\n";
+        let lines: Vec<&str> = source.lines().collect();
+
+        let start = find_definition(&lines, "greet", Language::Python).unwrap();
+        assert_eq!(start, 2);
+        assert_eq!(block_span(&lines, start, Language::Python), (1, 5));
+        assert!(find_definition(&lines, "missing", Language::Python).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+/// One file's section of a unified diff.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    /// Path on the `---` line; `None` for `/dev/null` (a new file)
+    pub old_path: Option<String>,
+    /// Path on the `+++` line; `None` for `/dev/null` (a deleted file)
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    /// The `@@ ... @@` line, for error messages
+    pub header: String,
+    /// First line of the hunk in the old file (1-based; the line before it when `old_count`
+    /// is 0)
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HunkLine {
+    pub kind: LineKind,
+    /// The line without its prefix or line ending
+    pub text: String,
+    /// Followed by `\ No newline at end of file`
+    pub no_newline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Remove,
+    Add,
+}
+
+impl FilePatch {
+    /// Workspace-relative path the patch applies to.
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// Splits a unified diff into per-file patches. Text outside of file sections, such as
+/// `diff --git` and `index` lines, is ignored. `a/` and `b/` prefixes are dropped when every
+/// side other than `/dev/null` carries them.
+pub fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(old) = lines[i].strip_prefix("--- ") else {
+            i += 1;
+            continue;
+        };
+        let Some(new) = lines.get(i + 1).and_then(|l| l.strip_prefix("+++ ")) else {
+            return Err(format!("line {}: '---' is not followed by '+++'", i + 1));
+        };
+        let (mut old_path, mut new_path) = (patch_path(old), patch_path(new));
+        if old_path.as_deref().is_none_or(|p| p.starts_with("a/"))
+            && new_path.as_deref().is_none_or(|p| p.starts_with("b/"))
+        {
+            old_path = old_path.map(|p| p[2..].to_string());
+            new_path = new_path.map(|p| p[2..].to_string());
+        }
+        i += 2;
+
+        let mut hunks = Vec::new();
+        while let Some(header) = lines.get(i).filter(|l| l.starts_with("@@ ")) {
+            let (old_start, old_count, new_count) = parse_hunk_header(header)
+                .ok_or_else(|| format!("line {}: malformed hunk header '{}'", i + 1, header))?;
+            let mut hunk = Hunk {
+                header: header.to_string(),
+                old_start,
+                lines: Vec::new(),
+            };
+            i += 1;
+            let (mut old_left, mut new_left) = (old_count, new_count);
+            while old_left > 0 || new_left > 0 {
+                let Some(line) = lines.get(i) else {
+                    return Err(format!("hunk '{}' ends before all its lines", header));
+                };
+                // Some editors strip the single space of an empty context line
+                let (kind, text) = match line.chars().next() {
+                    Some(' ') | None => (LineKind::Context, line.get(1..).unwrap_or("")),
+                    Some('-') => (LineKind::Remove, &line[1..]),
+                    Some('+') => (LineKind::Add, &line[1..]),
+                    Some('\\') => {
+                        i += 1;
+                        continue;
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {}: unexpected line in hunk '{}': {}",
+                            i + 1,
+                            header,
+                            line
+                        ));
+                    }
+                };
+                if kind != LineKind::Add {
+                    old_left = old_left.saturating_sub(1);
+                }
+                if kind != LineKind::Remove {
+                    new_left = new_left.saturating_sub(1);
+                }
+                hunk.lines.push(HunkLine {
+                    kind,
+                    text: text.to_string(),
+                    no_newline: false,
+                });
+                i += 1;
+            }
+            if lines.get(i).is_some_and(|l| l.starts_with('\\'))
+                && let Some(last) = hunk.lines.last_mut()
+            {
+                last.no_newline = true;
+                i += 1;
+            }
+            hunks.push(hunk);
+        }
+        files.push(FilePatch {
+            old_path,
+            new_path,
+            hunks,
+        });
+    }
+    if files.is_empty() {
+        return Err("no '---'/'+++' file headers found".to_string());
+    }
+    Ok(files)
+}
+
+/// The path on a `---`/`+++` line, without a trailing timestamp.
+fn patch_path(rest: &str) -> Option<String> {
+    let path = rest.split('\t').next().unwrap_or(rest).trim_end();
+    (path != "/dev/null").then(|| path.to_string())
+}
+
+/// Reads `@@ -a,b +c,d @@`; omitted counts are 1.
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = header.strip_prefix("@@ ")?.split_whitespace();
+    let range = |part: &str| -> Option<(usize, usize)> {
+        match part.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((part.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(parts.next()?.strip_prefix('-')?)?;
+    let (_, new_count) = range(parts.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_count, new_count))
+}
+
+/// Applies `hunks` in order to `content`. A hunk is placed at its stated line, or else at the
+/// first line after the previous hunk where its context and removed lines match exactly.
+/// Line endings are ignored when comparing. Returns the header of the first hunk that
+/// matches nowhere.
+pub fn apply_hunks(content: &str, hunks: &[Hunk]) -> Result<String, String> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut output = String::new();
+    let mut cursor = 0;
+    for hunk in hunks {
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|l| l.kind != LineKind::Add)
+            .map(|l| l.text.as_str())
+            .collect();
+        let matches_at = |at: usize| {
+            at + old.len() <= lines.len()
+                && old
+                    .iter()
+                    .zip(&lines[at..])
+                    .all(|(expected, actual)| actual.trim_end_matches(['\n', '\r']) == *expected)
+        };
+        let stated = if !old.is_empty() {
+            hunk.old_start.saturating_sub(1)
+        } else {
+            hunk.old_start
+        };
+        let at = std::iter::once(stated.max(cursor))
+            .chain(cursor..=lines.len())
+            .find(|&at| matches_at(at))
+            .ok_or_else(|| hunk.header.clone())?;
+
+        output.extend(lines[cursor..at].iter().copied());
+        let mut original = lines[at..at + old.len()].iter();
+        for line in &hunk.lines {
+            match line.kind {
+                LineKind::Context => output.push_str(original.next().unwrap()),
+                LineKind::Remove => {
+                    original.next();
+                }
+                LineKind::Add => {
+                    output.push_str(&line.text);
+                    if !line.no_newline {
+                        output.push('\n');
+                    }
+                }
+            }
+        }
+        cursor = at + old.len();
+    }
+    output.extend(lines[cursor..].iter().copied());
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_apply_git_patch() {
+        let patch = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                     index 1111111..2222222 100644\n\
+                     --- a/src/lib.rs\n\
+                     +++ b/src/lib.rs\n\
+                     @@ -1,3 +1,3 @@\n \
+                     one\n\
+                     -two\n\
+                     +TWO\n \
+                     three\n\
+                     --- /dev/null\n\
+                     +++ b/new.txt\n\
+                     @@ -0,0 +1 @@\n\
+                     +fresh\n\
+                     \\ No newline at end of file\n";
+        let files = parse_patch(patch).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].old_path.as_deref(), Some("src/lib.rs"));
+        assert_eq!(files[0].path(), "src/lib.rs");
+        assert_eq!(files[1].old_path, None);
+        assert_eq!(files[1].path(), "new.txt");
+
+        let patched = apply_hunks("one\ntwo\nthree\nfour\n", &files[0].hunks).unwrap();
+        assert_eq!(patched, "one\nTWO\nthree\nfour\n");
+        assert_eq!(apply_hunks("", &files[1].hunks).unwrap(), "fresh");
+
+        // Lines inserted above the hunk only shift where it applies
+        let patched = apply_hunks("zero\none\ntwo\nthree\n", &files[0].hunks).unwrap();
+        assert_eq!(patched, "zero\none\nTWO\nthree\n");
+    }
+}
@@ -0,0 +1,170 @@
+use rmcp::schemars;
+use rmcp::ErrorData as McpError;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+const DEFAULT_LIMIT: usize = 50;
+// Filename hits always outrank content hits
+const FILENAME_WEIGHT: usize = 1_000_000;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct SearchAllArgs {
+    /// Text to look for (case-insensitive) in file names and file contents
+    pub query: String,
+    /// Maximum number of results (default 50)
+    pub limit: Option<usize>,
+}
+
+#[derive(Default)]
+struct Hit {
+    name_match: bool,
+    exact_name: bool,
+    content_matches: usize,
+}
+
+impl Hit {
+    fn score(&self) -> usize {
+        let name_score = match (self.exact_name, self.name_match) {
+            (true, _) => 2 * FILENAME_WEIGHT,
+            (false, true) => FILENAME_WEIGHT,
+            _ => 0,
+        };
+        name_score + self.content_matches.min(FILENAME_WEIGHT - 1)
+    }
+}
+
+/// Matches the query against file names and contents in one pass over the workspace,
+/// returning a single ranked list with filename matches first. Hidden entries are skipped.
+pub fn run_search_all(args: &SearchAllArgs, workspace_dir: &Path) -> Result<String, McpError> {
+    let query = args.query.to_lowercase();
+    if query.is_empty() {
+        return Ok("Error: query must not be empty".to_string());
+    }
+    let limit = args.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let mut hits: HashMap<String, Hit> = HashMap::new();
+    let walker = WalkDir::new(workspace_dir)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'));
+
+    for entry in walker.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        let mut hit = Hit {
+            name_match: name.contains(&query),
+            exact_name: name == query
+                || Path::new(&name)
+                    .file_stem()
+                    .is_some_and(|s| s == query.as_str()),
+            ..Default::default()
+        };
+        if let Ok(content) = std::fs::read_to_string(entry.path()) {
+            hit.content_matches = content.to_lowercase().matches(&query).count();
+        }
+
+        if hit.name_match || hit.content_matches > 0 {
+            hits.insert(entry.path().to_string_lossy().to_string(), hit);
+        }
+    }
+
+    if hits.is_empty() {
+        return Ok(format!(
+            "No file names or contents matching '{}' in '{}'",
+            args.query,
+            workspace_dir.display()
+        ));
+    }
+
+    let mut ranked: Vec<(String, Hit)> = hits.into_iter().collect();
+    ranked.sort_by(|(a_path, a), (b_path, b)| {
+        b.score().cmp(&a.score()).then_with(|| a_path.cmp(b_path))
+    });
+
+    let total = ranked.len();
+    let lines: Vec<String> = ranked
+        .iter()
+        .take(limit)
+        .map(|(path, hit)| {
+            let mut kinds = Vec::new();
+            if hit.name_match {
+                kinds.push("filename".to_string());
+            }
+            if hit.content_matches > 0 {
+                kinds.push(format!("content: {} match(es)", hit.content_matches));
+            }
+            format!("{} ({})", path, kinds.join(", "))
+        })
+        .collect();
+
+    let mut output = format!(
+        "Found {} result(s) for '{}':\n{}",
+        total,
+        args.query,
+        lines.join("\n")
+    );
+    if total > limit {
+        output.push_str(&format!("\n\n[Results truncated to first {}.]", limit));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_search_all_ranks_filename_hits_first() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        // Many content hits must still rank below a filename hit
+        fs::write(
+            dir.path().join("src/main.rs"),
+            "use parser;\nparser::run();\nparser::stop();\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/parser.rs"), "pub fn run() {}\n").unwrap();
+        fs::write(dir.path().join("src/other.rs"), "fn unrelated() {}\n").unwrap();
+
+        let args = SearchAllArgs {
+            query: "Parser".to_string(),
+            limit: None,
+        };
+        let output = run_search_all(&args, dir.path()).unwrap();
+        assert!(output.starts_with("Found 2 result(s)"));
+
+        let lines: Vec<&str> = output.lines().skip(1).collect();
+        assert!(lines[0].contains("parser.rs (filename)"));
+        assert!(lines[1].contains("main.rs (content: 3 match(es))"));
+        assert!(!output.contains("other.rs"));
+    }
+
+    #[test]
+    fn test_search_all_limit_and_no_results() {
+        let dir = tempdir().unwrap();
+        for i in 0..3 {
+            fs::write(dir.path().join(format!("note{}.txt", i)), "todo").unwrap();
+        }
+
+        let args = SearchAllArgs {
+            query: "todo".to_string(),
+            limit: Some(2),
+        };
+        let output = run_search_all(&args, dir.path()).unwrap();
+        assert!(output.contains("Found 3 result(s)"));
+        assert!(output.contains("[Results truncated to first 2.]"));
+
+        let args = SearchAllArgs {
+            query: "missing".to_string(),
+            limit: None,
+        };
+        let output = run_search_all(&args, dir.path()).unwrap();
+        assert!(output.starts_with("No file names or contents matching"));
+    }
+}
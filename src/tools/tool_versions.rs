@@ -0,0 +1,103 @@
+use crate::models::ExecuteBashRequest;
+use crate::runtime::bash::BashEventService;
+use regex::Regex;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const DEFAULT_TOOLS: &[&str] = &["rustc", "cargo", "node", "npm", "python3", "go", "git"];
+const PROBE_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct ToolVersionsArgs {
+    /// Tools to probe with `--version` (defaults to common toolchains)
+    pub tools: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ToolVersions {
+    pub versions: BTreeMap<String, String>,
+    pub missing: Vec<String>,
+}
+
+/// Probes each tool with `--version` in the terminal session and collects the first output line.
+pub async fn run_tool_versions(args: &ToolVersionsArgs, bash: &BashEventService) -> ToolVersions {
+    let tools: Vec<String> = match &args.tools {
+        Some(tools) => tools.clone(),
+        None => DEFAULT_TOOLS.iter().map(|t| t.to_string()).collect(),
+    };
+    // Tool names are interpolated into a shell command, so only allow plain names
+    let valid_name = Regex::new(r"^[A-Za-z0-9._+-]+$").unwrap();
+
+    let mut result = ToolVersions::default();
+    for tool in tools {
+        if !valid_name.is_match(&tool) {
+            result.missing.push(tool);
+            continue;
+        }
+
+        let req = ExecuteBashRequest {
+            command: format!(
+                "command -v {tool} >/dev/null 2>&1 && {tool} --version 2>&1 | head -n 1"
+            ),
+            cwd: None,
+            timeout: Some(PROBE_TIMEOUT_SECS),
+            abort_on: None,
+        };
+        let probe = bash.execute_and_wait(req).await;
+        let version = probe.output.trim();
+        if probe.exit_code == Some(0) && !version.is_empty() {
+            result.versions.insert(tool, version.to_string());
+        } else {
+            result.missing.push(tool);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_tool_versions() {
+        // Only meaningful where cargo is on PATH
+        if std::process::Command::new("cargo")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().to_path_buf(), None, None);
+        let args = ToolVersionsArgs {
+            tools: Some(vec![
+                "cargo".to_string(),
+                "definitely-not-a-real-tool".to_string(),
+            ]),
+        };
+
+        let result = run_tool_versions(&args, &bash).await;
+        assert!(result.versions["cargo"].starts_with("cargo "));
+        assert_eq!(
+            result.missing,
+            vec!["definitely-not-a-real-tool".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_versions_rejects_unsafe_names() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().to_path_buf(), None, None);
+        let args = ToolVersionsArgs {
+            tools: Some(vec!["git; rm -rf /".to_string()]),
+        };
+
+        let result = run_tool_versions(&args, &bash).await;
+        assert!(result.versions.is_empty());
+        assert_eq!(result.missing.len(), 1);
+    }
+}
@@ -1,3 +1,7 @@
+use rmcp::model::ErrorCode;
+use rmcp::ErrorData as McpError;
+use std::path::{Component, Path, PathBuf};
+
 pub fn make_numbered_output(content: &str, start_line: usize) -> String {
     let lines: Vec<&str> = content.lines().collect();
     let numbered_lines: Vec<String> = lines
@@ -8,3 +12,277 @@ pub fn make_numbered_output(content: &str, start_line: usize) -> String {
 
     numbered_lines.join("\n")
 }
+
+/// Shortens `line` to `max_width` characters, noting how many were cut off.
+pub fn truncate_line(line: &str, max_width: usize) -> String {
+    let total = line.chars().count();
+    if total <= max_width {
+        return line.to_string();
+    }
+    let kept: String = line.chars().take(max_width).collect();
+    format!("{}…(+{} chars)", kept, total - max_width)
+}
+
+/// Formats a list of paths under their parent directory headings, with a count per directory.
+pub fn group_paths_by_dir(paths: &[String]) -> String {
+    let mut groups: std::collections::BTreeMap<String, Vec<String>> =
+        std::collections::BTreeMap::new();
+    for path in paths {
+        let p = std::path::Path::new(path);
+        let dir = p
+            .parent()
+            .map(|d| d.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = p
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone());
+        groups.entry(dir).or_default().push(name);
+    }
+    for names in groups.values_mut() {
+        names.sort();
+    }
+
+    groups
+        .iter()
+        .map(|(dir, names)| {
+            let entries: Vec<String> = names.iter().map(|n| format!("  {}", n)).collect();
+            format!("{}/ ({})\n{}", dir, names.len(), entries.join("\n"))
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Joins `rel` onto the workspace and checks that the result, with symlinks resolved, stays
+/// inside the workspace. The path need not exist yet. Returns the joined (unresolved) path so
+/// callers keep reporting paths the way the client wrote them.
+pub fn resolve_within_workspace(workspace_dir: &Path, rel: &str) -> Result<PathBuf, McpError> {
+    let outside = || McpError {
+        code: ErrorCode(-32602),
+        message: format!("Path is outside the workspace: {}", rel).into(),
+        data: None,
+    };
+    let root = workspace_dir.canonicalize().map_err(|e| McpError {
+        code: ErrorCode(-32603),
+        message: format!(
+            "Failed to resolve workspace {}: {}",
+            workspace_dir.display(),
+            e
+        )
+        .into(),
+        data: None,
+    })?;
+    let joined = workspace_dir.join(rel);
+
+    // Resolve the deepest existing ancestor, then apply the remaining components lexically
+    let mut existing = joined.as_path();
+    let mut rest = Vec::new();
+    let mut resolved = loop {
+        match existing.canonicalize() {
+            Ok(path) => break path,
+            Err(_) => {
+                let Some(parent) = existing.parent() else {
+                    return Err(outside());
+                };
+                // Only a trailing `..` has no file name
+                rest.push(match existing.file_name() {
+                    Some(name) => Component::Normal(name),
+                    None => Component::ParentDir,
+                });
+                existing = parent;
+            }
+        }
+    };
+    for component in rest.into_iter().rev() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+
+    if resolved.starts_with(&root) {
+        Ok(joined)
+    } else {
+        Err(outside())
+    }
+}
+
+/// The directory a search starts from: `path` resolved within the workspace, or the workspace
+/// itself. On failure the error message to show the caller is returned.
+pub fn search_base(workspace_dir: &Path, path: Option<&str>) -> Result<PathBuf, String> {
+    match path {
+        Some(p) => {
+            resolve_within_workspace(workspace_dir, p).map_err(|e| format!("Error: {}", e.message))
+        }
+        None => Ok(workspace_dir.to_path_buf()),
+    }
+}
+
+/// Joins a glob `pattern` onto the workspace. Absolute patterns and `..` components are
+/// rejected, since what they match can lie outside the workspace; matches reached through
+/// symlinks still need `resolve_within_workspace`.
+pub fn workspace_glob(workspace_dir: &Path, pattern: &str) -> Result<String, McpError> {
+    let escapes = Path::new(pattern).components().any(|c| {
+        matches!(
+            c,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes {
+        return Err(McpError {
+            code: ErrorCode(-32602),
+            message: format!("Glob pattern reaches outside the workspace: {}", pattern).into(),
+            data: None,
+        });
+    }
+    Ok(workspace_dir.join(pattern).to_string_lossy().to_string())
+}
+
+/// Threads used by `search_paths`; unset or `0` lets the walker pick from the CPU count.
+const SEARCH_THREADS_ENV: &str = "CODER_MCP_SEARCH_THREADS";
+
+/// Walks `root` on several threads, following symlinks, and returns the sorted paths for which
+/// `keep` is true. With `respect_gitignore`, paths ignored by `.gitignore` files (including
+/// those in parent directories) and `.git` itself are skipped; hidden files are always
+/// included. With a `limit`, the walk runs on one thread in path order instead and stops at
+/// the first `limit` paths, so the same tree always gives the same result.
+pub fn search_paths<F>(
+    root: &Path,
+    respect_gitignore: bool,
+    limit: Option<usize>,
+    keep: F,
+) -> Vec<PathBuf>
+where
+    F: Fn(&ignore::DirEntry) -> bool + Sync,
+{
+    let threads = std::env::var(SEARCH_THREADS_ENV)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let mut builder = ignore::WalkBuilder::new(root);
+    builder
+        .standard_filters(false)
+        .git_ignore(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .parents(respect_gitignore)
+        .require_git(false)
+        .follow_links(true)
+        .threads(threads);
+    if respect_gitignore {
+        builder.filter_entry(|e| e.file_name() != ".git");
+    }
+
+    // Visiting sorted siblings depth-first yields paths in sorted order
+    if let Some(limit) = limit {
+        return builder
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| keep(entry))
+            .take(limit)
+            .map(ignore::DirEntry::into_path)
+            .collect();
+    }
+
+    let found = std::sync::Mutex::new(Vec::new());
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            if let Ok(entry) = entry
+                && keep(&entry)
+            {
+                found.lock().unwrap().push(entry.into_path());
+            }
+            ignore::WalkState::Continue
+        })
+    });
+    let mut found = found.into_inner().unwrap();
+    found.sort();
+    found
+}
+
+/// The tool result for a failed write. On a read-only filesystem every write fails the same
+/// way, so the OS error is replaced with one clear message.
+pub fn write_error(e: &std::io::Error, message: String) -> String {
+    if e.kind() == std::io::ErrorKind::ReadOnlyFilesystem {
+        "Error: workspace filesystem is read-only".to_string()
+    } else {
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_within_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+
+        assert_eq!(
+            resolve_within_workspace(&workspace, "src/new/file.rs").unwrap(),
+            workspace.join("src/new/file.rs")
+        );
+        assert!(resolve_within_workspace(&workspace, "src/../Cargo.toml").is_ok());
+        assert!(resolve_within_workspace(&workspace, "../outside.txt").is_err());
+        assert!(resolve_within_workspace(&workspace, "src/missing/../../../x").is_err());
+        assert!(resolve_within_workspace(&workspace, "/etc/passwd").is_err());
+        let inside = workspace.join("src").to_string_lossy().to_string();
+        assert!(resolve_within_workspace(&workspace, &inside).is_ok());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), workspace.join("escape")).unwrap();
+            assert!(resolve_within_workspace(&workspace, "escape/secret").is_err());
+        }
+    }
+
+    #[test]
+    fn test_write_error_reports_read_only_filesystem() {
+        let read_only = std::io::Error::from(std::io::ErrorKind::ReadOnlyFilesystem);
+        assert_eq!(
+            write_error(&read_only, "Error: Failed to write to a.txt".to_string()),
+            "Error: workspace filesystem is read-only"
+        );
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            write_error(&denied, "Error: Failed to write to a.txt".to_string()),
+            "Error: Failed to write to a.txt"
+        );
+    }
+
+    #[test]
+    fn test_search_paths_matches_serial_walk() {
+        let dir = tempdir().unwrap();
+        for d in 0..20 {
+            let sub = dir.path().join(format!("dir{}/nested", d));
+            std::fs::create_dir_all(&sub).unwrap();
+            for f in 0..100 {
+                std::fs::write(sub.join(format!("f{}.txt", f)), "").unwrap();
+            }
+        }
+
+        let parallel = search_paths(dir.path(), true, None, |e| {
+            e.file_type().is_some_and(|t| t.is_file())
+        });
+        let mut serial: Vec<PathBuf> = walkdir::WalkDir::new(dir.path())
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .collect();
+        serial.sort();
+        assert_eq!(parallel.len(), 2000);
+        assert_eq!(parallel, serial);
+
+        let limited = search_paths(dir.path(), true, Some(5), |e| {
+            e.file_type().is_some_and(|t| t.is_file())
+        });
+        assert_eq!(limited, serial[..5]);
+    }
+}
@@ -0,0 +1,46 @@
+use chrono::DateTime;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VersionInfo {
+    pub name: String,
+    pub version: String,
+    pub git_commit: String,
+    pub build_timestamp: String,
+    pub features: Vec<String>,
+}
+
+pub fn version_info() -> VersionInfo {
+    let build_timestamp = env!("CODER_MCP_BUILD_TIMESTAMP")
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    VersionInfo {
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_commit: env!("CODER_MCP_GIT_COMMIT").to_string(),
+        build_timestamp,
+        features: env!("CODER_MCP_FEATURES")
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(|f| f.to_string())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_matches_crate_version() {
+        let info = version_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.name, "coder-mcp");
+        assert!(!info.git_commit.is_empty());
+        assert!(DateTime::parse_from_rfc3339(&info.build_timestamp).is_ok());
+    }
+}
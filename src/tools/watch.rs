@@ -0,0 +1,175 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{EventKind, RecursiveMode, Watcher};
+use rmcp::ErrorData as McpError;
+use rmcp::model::ErrorCode;
+use rmcp::schemars;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::tools::utils;
+
+const MAX_WATCH_DURATION_SECS: u64 = 60;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct WatchDirArgs {
+    pub path: String,
+    /// How long to collect changes for, in seconds (capped at 60)
+    pub duration_secs: u64,
+}
+
+pub async fn run_watch_dir(args: &WatchDirArgs, workspace_dir: &Path) -> Result<String, McpError> {
+    let path = utils::resolve_within_workspace(workspace_dir, &args.path)?;
+
+    if !path.is_dir() {
+        return Ok(format!(
+            "Error: The path {} is not a valid directory.",
+            path.display()
+        ));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| McpError {
+        code: ErrorCode(-32603),
+        message: format!("Failed to create file watcher: {}", e).into(),
+        data: None,
+    })?;
+    watcher
+        .watch(&path, RecursiveMode::Recursive)
+        .map_err(|e| McpError {
+            code: ErrorCode(-32603),
+            message: format!("Failed to watch {}: {}", path.display(), e).into(),
+            data: None,
+        })?;
+
+    let duration_secs = args.duration_secs.min(MAX_WATCH_DURATION_SECS);
+    tokio::time::sleep(Duration::from_secs(duration_secs)).await;
+    drop(watcher);
+
+    let gitignore = load_gitignore(workspace_dir);
+
+    // Workspace-relative path -> kinds of change, in the order they were first seen
+    let mut changes: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for event in rx.try_iter().flatten() {
+        let kind = match event.kind {
+            EventKind::Create(_) => "created",
+            EventKind::Modify(_) => "modified",
+            EventKind::Remove(_) => "deleted",
+            _ => continue,
+        };
+        for changed in &event.paths {
+            let Ok(rel) = changed.strip_prefix(workspace_dir) else {
+                continue;
+            };
+            if is_hidden(rel)
+                || gitignore
+                    .matched_path_or_any_parents(rel, changed.is_dir())
+                    .is_ignore()
+            {
+                continue;
+            }
+            let kinds = changes
+                .entry(rel.to_string_lossy().to_string())
+                .or_default();
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok(format!(
+            "No changes detected in {} during {}s",
+            path.display(),
+            duration_secs
+        ));
+    }
+
+    let lines: Vec<String> = changes
+        .iter()
+        .map(|(rel, kinds)| format!("{} ({})", rel, kinds.join(", ")))
+        .collect();
+    Ok(format!(
+        "Detected {} changed path(s) in {} during {}s:\n{}",
+        changes.len(),
+        path.display(),
+        duration_secs,
+        lines.join("\n")
+    ))
+}
+
+fn is_hidden(rel: &Path) -> bool {
+    rel.components().any(|c| match c {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+fn load_gitignore(workspace_dir: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(workspace_dir);
+    builder.add(workspace_dir.join(".gitignore"));
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_watch_dir_reports_changes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "ignored.txt\n").unwrap();
+        fs::write(dir.path().join("existing.txt"), "before").unwrap();
+
+        let workspace = dir.path().to_path_buf();
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            fs::write(workspace.join("existing.txt"), "after").unwrap();
+            fs::write(workspace.join("new.txt"), "new").unwrap();
+            fs::write(workspace.join("ignored.txt"), "ignored").unwrap();
+            fs::write(workspace.join(".hidden"), "hidden").unwrap();
+        });
+
+        let args = WatchDirArgs {
+            path: ".".to_string(),
+            duration_secs: 1,
+        };
+        let output = run_watch_dir(&args, dir.path()).await.unwrap();
+        writer.await.unwrap();
+
+        assert!(output.contains("existing.txt (modified"));
+        assert!(output.contains("new.txt (created"));
+        assert!(!output.contains("ignored.txt"));
+        assert!(!output.contains(".hidden"));
+    }
+
+    #[tokio::test]
+    async fn test_watch_dir_not_a_directory() {
+        let dir = tempdir().unwrap();
+        let args = WatchDirArgs {
+            path: "missing".to_string(),
+            duration_secs: 1,
+        };
+        let output = run_watch_dir(&args, dir.path()).await.unwrap();
+        assert!(output.contains("Error"));
+    }
+
+    #[tokio::test]
+    async fn test_watch_dir_stays_inside_workspace() {
+        let dir = tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir(&workspace).unwrap();
+        for path in ["..", dir.path().to_str().unwrap()] {
+            let args = WatchDirArgs {
+                path: path.to_string(),
+                duration_secs: 0,
+            };
+            let err = run_watch_dir(&args, &workspace).await.unwrap_err();
+            assert!(err.message.contains("outside the workspace"));
+        }
+    }
+}